@@ -0,0 +1,336 @@
+// Copyright (c) 2023, Oskar Ohlenmacher
+// All rights reserved
+//
+// This source code is licensed under the BSD-style license found in the
+// LICENSE file in the root directory of this source tree.
+
+// The model only describes the classes, every language they can be written in
+// is a backend which reads the model through its getters.
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::config::{BraceStyle, Config};
+use crate::generate::Options;
+use crate::model::{Attribute, Class};
+use crate::template::TemplateError;
+
+pub mod csharp;
+pub mod java;
+pub mod kotlin;
+pub mod python;
+pub mod rust;
+pub mod typescript;
+
+pub use csharp::CSharp;
+pub use java::Java;
+pub use kotlin::Kotlin;
+pub use python::Python;
+pub use rust::Rust;
+pub use typescript::TypeScript;
+
+/// Writes the classes of the model in one language
+pub trait Backend {
+    /// Extension of the generated files
+    fn extension(&self) -> &'static str;
+
+    /// Path of the file of a top level class relative to the output location
+    fn path(&self, class: &Class, config: &Config) -> PathBuf {
+        let mut path = PathBuf::new();
+        if let Some(package) = &config.package {
+            path.extend(package.split('.'));
+        }
+        path.push(class.name());
+        path.with_extension(self.extension())
+    }
+
+    /// Code of the class with its inner classes, without anything belonging to the file
+    fn class(&self, class: &Class) -> String;
+
+    /// The complete file of a top level class
+    fn file(&self, class: &Class, options: &Options) -> Result<String, TemplateError>;
+
+    /// Files of all top level classes of the model, every class has its own file
+    /// unless the language needs to know the other classes
+    fn files(
+        &self,
+        classes: &[Class],
+        options: &Options,
+    ) -> Result<Vec<SourceFile>, TemplateError> {
+        classes
+            .iter()
+            .map(|class| {
+                Ok(SourceFile {
+                    path: self.path(class, &options.config),
+                    code: self.file(class, options)?,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Generated file
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceFile {
+    /// Path relative to the output directory, including the package
+    pub path: PathBuf,
+    pub code: String,
+}
+
+/// Language the classes are generated in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Target {
+    #[default]
+    Java,
+    Kotlin,
+    #[value(name = "typescript")]
+    #[serde(rename = "typescript")]
+    TypeScript,
+    #[value(name = "csharp")]
+    #[serde(rename = "csharp")]
+    CSharp,
+    Rust,
+    Python,
+}
+
+impl Target {
+    pub fn backend(self) -> &'static dyn Backend {
+        match self {
+            Self::Java => &Java,
+            Self::Kotlin => &Kotlin,
+            Self::TypeScript => &TypeScript,
+            Self::CSharp => &CSharp,
+            Self::Rust => &Rust,
+            Self::Python => &Python,
+        }
+    }
+}
+
+/// Javadoc style comment of a note, every line after the first is indented for
+/// the member which follows it
+pub(crate) fn doc_comment(doc: &str, parameters: &[Attribute], indent: &str) -> String {
+    let mut str = String::from("/**\n");
+    for line in doc.lines() {
+        str.push_str(indent);
+        str.push_str(" *");
+        if !line.is_empty() {
+            str.push(' ');
+            str.push_str(&escape_comment_end(line));
+        }
+        str.push('\n');
+    }
+    if !parameters.is_empty() {
+        str.push_str(indent);
+        str.push_str(" *\n");
+    }
+    for p in parameters.iter() {
+        str.push_str(&format!("{} * @param {}\n", indent, p.name()));
+    }
+    str.push_str(indent);
+    str.push_str(" */\n");
+    str.push_str(indent);
+    str
+}
+
+/// A "*/" in a note would end the comment early
+pub(crate) fn escape_comment_end(line: &str) -> String {
+    line.replace("*/", "*&#47;")
+}
+
+/// Indents every non empty line by one level, used for nested classes
+pub(crate) fn indent(s: &str) -> String {
+    let mut str = String::new();
+    for line in s.lines() {
+        if !line.is_empty() {
+            str.push_str("    ");
+            str.push_str(line);
+        }
+        str.push('\n');
+    }
+    str
+}
+
+/// Indents the code, which is indented by 4 spaces, as configured and moves opening
+/// braces to the next line if wanted, `braces` is the style used if none is configured
+pub(crate) fn reindent(code: &str, config: &Config, braces: BraceStyle) -> String {
+    let braces = config.brace_style.unwrap_or(braces);
+    let mut str = String::new();
+    let unit = match config.tabs {
+        true => "\t".to_string(),
+        false => " ".repeat(config.indent),
+    };
+    for line in code.lines() {
+        let content = line.trim_start_matches(' ');
+        let spaces = line.len() - content.len();
+        let content = content.trim_end();
+        if content.is_empty() {
+            str.push('\n');
+            continue;
+        }
+        let indent = unit.repeat(spaces / 4) + &" ".repeat(spaces % 4);
+        match content.strip_suffix(" {") {
+            Some(head) if braces == BraceStyle::NextLine => {
+                str.push_str(&format!("{indent}{head}\n{indent}{{\n"));
+            }
+            _ => str.push_str(&format!("{indent}{content}\n")),
+        }
+    }
+    str
+}
+
+/// Splits the arguments of a generic type at the commas which aren't nested
+pub(crate) fn split_arguments(args: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&args[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    parts.push(&args[start..]);
+    parts
+}
+
+/// Names like `getName` written as `get_name`
+pub(crate) fn snake_case(name: &str) -> String {
+    let mut str = String::new();
+    let mut previous: Option<char> = None;
+    for c in name.chars() {
+        if c.is_uppercase() && previous.is_some_and(|p| p.is_lowercase() || p.is_numeric()) {
+            str.push('_');
+        }
+        str.extend(c.to_lowercase());
+        previous = Some(c);
+    }
+    str
+}
+
+/// Replaces qualified type names by their simple name and returns the imports
+/// needed, names which would be ambiguous or are the class itself stay qualified.
+/// Comments and literals are kept as they are, outside of them the generated code
+/// only has qualified names in type positions.
+pub(crate) fn shorten_names(
+    code: &str,
+    class_name: &str,
+    is_implicit: &dyn Fn(&str) -> bool,
+) -> (String, Vec<String>) {
+    let mut imports: Vec<String> = Vec::new();
+    let mut str = String::new();
+    let mut rest = code;
+    while let Some(start) =
+        rest.find(|c: char| c.is_alphanumeric() || matches!(c, '_' | '/' | '"' | '\''))
+    {
+        str.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(len) = literal_len(rest) {
+            str.push_str(&rest[..len]);
+            rest = &rest[len..];
+            continue;
+        }
+        if let Some(after) = rest.strip_prefix('/') {
+            str.push('/');
+            rest = after;
+            continue;
+        }
+        let end = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+            .unwrap_or(rest.len());
+        let word = rest[..end].trim_end_matches('.');
+        rest = &rest[word.len()..];
+
+        let Some((package, name)) = qualified_name(word) else {
+            str.push_str(word);
+            continue;
+        };
+        let conflicts = name == class_name
+            || imports
+                .iter()
+                .any(|i| i != word && i.rsplit('.').next() == Some(name));
+        if conflicts {
+            str.push_str(word);
+            continue;
+        }
+        if !is_implicit(package) && !imports.iter().any(|i| i == word) {
+            imports.push(word.to_string());
+        }
+        str.push_str(name);
+    }
+    str.push_str(rest);
+    imports.sort();
+    (str, imports)
+}
+
+/// Length of the comment, string or character literal code starts with
+fn literal_len(code: &str) -> Option<usize> {
+    if let Some(comment) = code.strip_prefix("/*") {
+        return Some(comment.find("*/").map_or(code.len(), |end| end + 4));
+    }
+    if code.starts_with("//") {
+        return Some(code.find('\n').unwrap_or(code.len()));
+    }
+    let quote = code.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+    let mut escaped = false;
+    let end = code[1..].char_indices().find(|&(_, c)| {
+        let closes = !escaped && c == quote;
+        escaped = !escaped && c == '\\';
+        closes
+    });
+    Some(end.map_or(code.len(), |(i, _)| i + 2))
+}
+
+/// Splits names like `java.util.List` into package and class name
+fn qualified_name(word: &str) -> Option<(&str, &str)> {
+    let (package, name) = word.rsplit_once('.')?;
+    let is_package = package
+        .split('.')
+        .all(|p| p.starts_with(|c: char| c.is_ascii_lowercase()));
+    let is_class = name.starts_with(|c: char| c.is_ascii_uppercase());
+    (is_package && is_class).then_some((package, name))
+}
+
+/// The diagram the backends are tested with
+#[cfg(test)]
+pub(crate) mod fixture {
+    use super::{Backend, SourceFile};
+    use crate::generate::Options;
+
+    /// An abstract class with a subclass which implements an interface, and an enum
+    pub(crate) const HEROES: &str = "@startuml
+abstract class Held {
+ # name : String
+ - punkte : int[*]
+ + {abstract} angreifen(ziel : Monster) : boolean
+ + getName() : String
+}
+class Krieger {
+ - waffe : Waffe?
+ + {static} anzahl : int
+ + angreifen(ziel : Monster) : boolean
+}
+interface Monster {
+ + brüllen() : void
+}
+enum Farbe {
+ ROT
+ DUNKEL_GRUEN
+}
+Held <|-- Krieger
+Monster <|.. Krieger
+note top of Krieger : Kämpft\\nmit Waffen
+@enduml";
+
+    /// The files the backend writes for the classes of a diagram
+    pub(crate) fn files(input: &str, backend: &dyn Backend, options: &Options) -> Vec<SourceFile> {
+        let tokens = crate::tokenize(input).unwrap();
+        let classes = crate::parse(&tokens, options).unwrap();
+        backend.files(&classes, options).unwrap()
+    }
+}
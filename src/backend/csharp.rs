@@ -0,0 +1,726 @@
+// Copyright (c) 2023, Oskar Ohlenmacher
+// All rights reserved
+//
+// This source code is licensed under the BSD-style license found in the
+// LICENSE file in the root directory of this source tree.
+
+use log::warn;
+
+use super::{indent, reindent, split_arguments, Backend};
+use crate::config::BraceStyle;
+use crate::generate::Options;
+use crate::model::{Attribute, Class, ClassKind, Function, Sealing, Type, View};
+use crate::template::TemplateError;
+
+/// C# classes with auto-properties, records, interfaces and enums, the package
+/// becomes the namespace
+#[derive(Debug, Clone, Copy)]
+pub struct CSharp;
+
+/// Body of methods which aren't abstract
+const STUB: &str = "throw new NotImplementedException();";
+
+/// Types which need `using System.Collections.Generic;`
+const GENERIC_COLLECTIONS: [&str; 6] = [
+    "Dictionary",
+    "HashSet",
+    "IEnumerable",
+    "List",
+    "SortedDictionary",
+    "SortedSet",
+];
+
+/// Types which need `using System;`
+const SYSTEM_TYPES: [&str; 3] = ["DateTime", "Guid", "NotImplementedException"];
+
+/// Reserved words, which are only names with an `@` in front
+const KEYWORDS: [&str; 77] = [
+    "abstract",
+    "as",
+    "base",
+    "bool",
+    "break",
+    "byte",
+    "case",
+    "catch",
+    "char",
+    "checked",
+    "class",
+    "const",
+    "continue",
+    "decimal",
+    "default",
+    "delegate",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "event",
+    "explicit",
+    "extern",
+    "false",
+    "finally",
+    "fixed",
+    "float",
+    "for",
+    "foreach",
+    "goto",
+    "if",
+    "implicit",
+    "in",
+    "int",
+    "interface",
+    "internal",
+    "is",
+    "lock",
+    "long",
+    "namespace",
+    "new",
+    "null",
+    "object",
+    "operator",
+    "out",
+    "override",
+    "params",
+    "private",
+    "protected",
+    "public",
+    "readonly",
+    "ref",
+    "return",
+    "sbyte",
+    "sealed",
+    "short",
+    "sizeof",
+    "stackalloc",
+    "static",
+    "string",
+    "struct",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "uint",
+    "ulong",
+    "unchecked",
+    "unsafe",
+    "ushort",
+    "using",
+    "virtual",
+    "void",
+    "volatile",
+    "while",
+];
+
+impl Backend for CSharp {
+    fn extension(&self) -> &'static str {
+        "cs"
+    }
+
+    fn class(&self, class: &Class) -> String {
+        let mut str = String::new();
+        if let Some(doc) = class.doc() {
+            str.push_str(&xml_doc(doc, &[], ""));
+        }
+        match class.get_kind() {
+            ClassKind::Class => push_class(class, &mut str, self),
+            ClassKind::Record => push_record(class, &mut str, self),
+            ClassKind::Interface => push_interface(class, &mut str),
+            ClassKind::Enum => push_enum(class, &mut str),
+        }
+        str
+    }
+
+    fn file(&self, class: &Class, options: &Options) -> Result<String, TemplateError> {
+        let code = self.class(class);
+        let mut str = String::new();
+        let usings = usings(&code);
+        for using in usings.iter() {
+            str.push_str(&format!("using {};\n", using));
+        }
+        if !usings.is_empty() {
+            str.push('\n');
+        }
+        let code = match &options.config.package {
+            Some(package) => format!("namespace {} {{\n{}}}\n", namespace(package), indent(&code)),
+            None => code,
+        };
+        str.push_str(&reindent(&code, &options.config, BraceStyle::NextLine));
+        Ok(str)
+    }
+}
+
+fn push_class(class: &Class, str: &mut String, backend: &CSharp) {
+    str.push_str(visibility(class.visibility(), "internal "));
+    if class.is_abstract() {
+        str.push_str("abstract ");
+    } else if class.get_sealing() == Sealing::Final {
+        str.push_str("sealed ");
+    }
+    str.push_str("class ");
+    str.push_str(class.name());
+    push_supertypes(class, str);
+    str.push_str(" {\n");
+
+    let mut sections = Vec::new();
+    let properties: Vec<String> = class.attributes().iter().map(|a| property(*a)).collect();
+    if !properties.is_empty() {
+        sections.push(properties.join("\n"));
+    }
+    if let Some(constructor) = constructor(class) {
+        sections.push(constructor);
+    }
+    let methods = methods(class);
+    if !methods.is_empty() {
+        sections.push(methods.join("\n\n"));
+    }
+    for inner in class.inner().iter() {
+        sections.push(indent(&backend.class(inner)).trim_end().to_string());
+    }
+    str.push_str(&sections.join("\n\n"));
+    if !sections.is_empty() {
+        str.push('\n');
+    }
+    str.push_str("}\n");
+}
+
+/// Positional records, the components are public properties
+fn push_record(class: &Class, str: &mut String, backend: &CSharp) {
+    str.push_str(visibility(class.visibility(), "internal "));
+    str.push_str("record ");
+    str.push_str(class.name());
+    let components: Vec<String> = class
+        .instance_attributes()
+        .iter()
+        .map(|a| format!("{} {}", type_name(a.vartype()), pascal_case(a.name())))
+        .collect();
+    str.push_str(&format!("({})", components.join(", ")));
+    push_supertypes(class, str);
+
+    let mut sections = Vec::new();
+    let properties: Vec<String> = class
+        .attributes()
+        .iter()
+        .filter(|a| a.is_static())
+        .map(|a| property(*a))
+        .collect();
+    if !properties.is_empty() {
+        sections.push(properties.join("\n"));
+    }
+    let methods = methods(class);
+    if !methods.is_empty() {
+        sections.push(methods.join("\n\n"));
+    }
+    for inner in class.inner().iter() {
+        sections.push(indent(&backend.class(inner)).trim_end().to_string());
+    }
+    // the body can be left out
+    match sections.is_empty() {
+        true => str.push_str(";\n"),
+        false => {
+            str.push_str(" {\n");
+            str.push_str(&sections.join("\n\n"));
+            str.push_str("\n}\n");
+        }
+    }
+}
+
+fn push_interface(class: &Class, str: &mut String) {
+    str.push_str(visibility(class.visibility(), "internal "));
+    str.push_str("interface ");
+    str.push_str(class.name());
+    push_supertypes(class, str);
+    str.push_str(" {\n");
+    let mut members: Vec<String> = class
+        .attributes()
+        .iter()
+        .filter(|a| !a.is_static())
+        .map(|a| {
+            format!(
+                "    {}{} {} {}",
+                member_doc(a.doc(), &[]),
+                type_name(a.vartype()),
+                pascal_case(a.name()),
+                accessors(*a)
+            )
+        })
+        .collect();
+    for f in class.methods().iter() {
+        members.push(match f.is_static() {
+            true => method(f, "public static ", false),
+            false => format!(
+                "    {}{};",
+                member_doc(f.doc(), f.parameters()),
+                signature(f)
+            ),
+        });
+    }
+    str.push_str(&members.join("\n"));
+    if !members.is_empty() {
+        str.push('\n');
+    }
+    str.push_str("}\n");
+}
+
+fn push_enum(class: &Class, str: &mut String) {
+    if !class.attributes().is_empty() || !class.methods().is_empty() {
+        warn!(
+            "C# enums can't have members, the members of {} are left out",
+            class.name()
+        );
+    }
+    str.push_str(visibility(class.visibility(), "internal "));
+    str.push_str("enum ");
+    str.push_str(class.name());
+    str.push_str(" {\n");
+    let constants: Vec<String> = class
+        .constants()
+        .iter()
+        .map(|c| format!("    {}", pascal_case(c)))
+        .collect();
+    str.push_str(&constants.join(",\n"));
+    if !constants.is_empty() {
+        str.push('\n');
+    }
+    str.push_str("}\n");
+}
+
+/// Base class followed by the interfaces
+fn push_supertypes(class: &Class, str: &mut String) {
+    let names: Vec<&str> = class
+        .extends()
+        .into_iter()
+        .chain(class.implements().iter())
+        .map(|c| c.name())
+        .collect();
+    if !names.is_empty() {
+        str.push_str(" : ");
+        str.push_str(&names.join(", "));
+    }
+}
+
+/// Auto-property of an attribute, final attributes can only be set in the constructor
+fn property(a: Attribute) -> String {
+    let mut str = String::from("    ");
+    str.push_str(&member_doc(a.doc(), &[]));
+    str.push_str(visibility(a.visibility(), "internal "));
+    if a.is_static() {
+        str.push_str("static ");
+    }
+    str.push_str(&format!(
+        "{} {} {}",
+        type_name(a.vartype()),
+        pascal_case(a.name()),
+        accessors(a)
+    ));
+    str
+}
+
+fn accessors(a: Attribute) -> &'static str {
+    match a.is_final() {
+        true => "{ get; }",
+        false => "{ get; set; }",
+    }
+}
+
+/// Constructor setting the instance attributes and passing the ones of the super
+/// class to its constructor, not needed if there is nothing to set
+fn constructor(class: &Class) -> Option<String> {
+    let constructor = class.get_constructor_func();
+    if constructor.parameters().is_empty() {
+        return None;
+    }
+    let view = match class.is_abstract() {
+        true => "protected ",
+        false => visibility(class.visibility(), "internal "),
+    };
+    let mut str = format!("    {}{}({})", view, class.name(), parameters(&constructor));
+    if let Some(master) = class.extends() {
+        let args: Vec<String> = master
+            .instance_attributes()
+            .iter()
+            .map(|a| parameter(a.name()))
+            .collect();
+        if !args.is_empty() {
+            str.push_str(&format!(" : base({})", args.join(", ")));
+        }
+    }
+    str.push_str(" {\n");
+    for a in class.instance_attributes().iter() {
+        str.push_str(&format!(
+            "        {} = {};\n",
+            pascal_case(a.name()),
+            parameter(a.name())
+        ));
+    }
+    str.push_str("    }");
+    Some(str)
+}
+
+/// Declared methods followed by the ones which have to be implemented
+fn methods(class: &Class) -> Vec<String> {
+    let is_open = class.is_abstract()
+        || !class.subtypes().is_empty()
+        || class.get_sealing() == Sealing::Sealed;
+    let mut methods = Vec::new();
+    for f in class.methods().iter() {
+        let mut modifiers = visibility(f.visibility(), "internal ").to_string();
+        if f.is_static() {
+            modifiers.push_str("static ");
+        } else if overrides_base(class, f) {
+            modifiers.push_str("override ");
+        } else if f.is_abstract() {
+            modifiers.push_str("abstract ");
+        } else if is_open && !matches!(f.visibility(), View::Private) {
+            modifiers.push_str("virtual ");
+        }
+        methods.push(method(f, &modifiers, f.is_abstract()));
+    }
+    for f in class.inherited_methods().iter() {
+        // interface methods are implemented without override
+        let modifiers = match overrides_base(class, f) {
+            true => "public override ",
+            false => "public ",
+        };
+        methods.push(method(f, modifiers, false));
+    }
+    methods
+}
+
+fn method(f: &Function, modifiers: &str, is_abstract: bool) -> String {
+    let mut str = String::from("    ");
+    str.push_str(&member_doc(f.doc(), f.parameters()));
+    str.push_str(modifiers);
+    str.push_str(&signature(f));
+    match is_abstract {
+        true => str.push(';'),
+        false => str.push_str(&format!(" {{\n        {}\n    }}", STUB)),
+    }
+    str
+}
+
+/// Return type, name and parameters of the method
+fn signature(f: &Function) -> String {
+    let return_type = match type_name(f.return_type()).as_str() {
+        "" => "void".to_string(),
+        t => t.to_string(),
+    };
+    format!(
+        "{} {}({})",
+        return_type,
+        pascal_case(f.name()),
+        parameters(f)
+    )
+}
+
+fn parameters(f: &Function) -> String {
+    let parameters: Vec<String> = f
+        .parameters()
+        .iter()
+        .map(|p| format!("{} {}", type_name(p.vartype()), parameter(p.name())))
+        .collect();
+    parameters.join(", ")
+}
+
+/// Whether a super class declares the method, methods of interfaces aren't overridden
+fn overrides_base(class: &Class, f: &Function) -> bool {
+    let mut master = class.extends();
+    while let Some(m) = master {
+        let declared = m
+            .methods()
+            .iter()
+            .any(|g| !g.is_static() && !matches!(g.visibility(), View::Private) && f.overrides(g));
+        if declared {
+            return true;
+        }
+        master = m.extends();
+    }
+    false
+}
+
+/// XML documentation comment of a note, every line after the first is indented
+/// for the member which follows it
+fn xml_doc(doc: &str, parameters: &[Attribute], indent: &str) -> String {
+    let mut str = String::from("/// <summary>\n");
+    for line in doc.lines() {
+        str.push_str(indent);
+        str.push_str("///");
+        if !line.is_empty() {
+            str.push(' ');
+            str.push_str(&escape(line));
+        }
+        str.push('\n');
+    }
+    str.push_str(indent);
+    str.push_str("/// </summary>\n");
+    for p in parameters.iter() {
+        str.push_str(&format!(
+            "{}/// <param name=\"{}\"></param>\n",
+            indent,
+            camel_case(p.name())
+        ));
+    }
+    str.push_str(indent);
+    str
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Documentation of a member followed by the indentation of the member
+fn member_doc(doc: Option<&str>, parameters: &[Attribute]) -> String {
+    doc.map(|d| xml_doc(d, parameters, "    "))
+        .unwrap_or_default()
+}
+
+/// Members without visibility get the default visibility given
+fn visibility(view: View, default: &'static str) -> &'static str {
+    match view {
+        View::Normal => default,
+        View::Public => "public ",
+        View::Protected => "protected ",
+        View::Private => "private ",
+    }
+}
+
+/// Namespace of a package, `com.example` becomes `Com.Example`
+fn namespace(package: &str) -> String {
+    let parts: Vec<String> = package.split('.').map(pascal_case).collect();
+    parts.join(".")
+}
+
+/// Name of properties, methods and enum constants, constants like `DARK_RED`
+/// become `DarkRed`
+fn pascal_case(name: &str) -> String {
+    let is_constant = name.contains('_') || !name.chars().any(|c| c.is_lowercase());
+    let words: Vec<String> = match is_constant {
+        true => name
+            .split('_')
+            .map(|w| capitalize(&w.to_lowercase()))
+            .collect(),
+        false => vec![capitalize(name)],
+    };
+    words.concat()
+}
+
+/// Name of parameters
+fn camel_case(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Name of a parameter in camel case, reserved words are escaped with `@`
+fn parameter(name: &str) -> String {
+    let name = camel_case(name);
+    match KEYWORDS.contains(&name.as_str()) {
+        true => format!("@{}", name),
+        false => name,
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Namespaces the generated code needs
+fn usings(code: &str) -> Vec<&'static str> {
+    let mut usings = Vec::new();
+    let words: Vec<&str> = code
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .collect();
+    if words.iter().any(|w| SYSTEM_TYPES.contains(w)) {
+        usings.push("System");
+    }
+    if words.iter().any(|w| GENERIC_COLLECTIONS.contains(w)) {
+        usings.push("System.Collections.Generic");
+    }
+    usings
+}
+
+/// C# type of a type of the diagram, java types are replaced by the C# ones
+fn type_name(vartype: Type) -> String {
+    let name = match vartype {
+        Type::Other(s) => s.to_string(),
+        Type::Collection(c, e) => format!("{}<{}>", c, e),
+    };
+    map_type(name.trim())
+}
+
+fn map_type(name: &str) -> String {
+    if name.is_empty() {
+        return String::new();
+    }
+    if let Some(base) = name.strip_suffix('?') {
+        return format!("{}?", map_type(base));
+    }
+    if let Some(element) = name.strip_suffix("[]") {
+        return format!("{}[]", map_type(element));
+    }
+    let Some((base, args)) = name.strip_suffix('>').and_then(|n| n.split_once('<')) else {
+        return map_name(name).to_string();
+    };
+    let args: Vec<String> = split_arguments(args)
+        .iter()
+        .map(|a| map_type(a.trim()))
+        .collect();
+    let base = base.trim();
+    let simple = base.rsplit('.').next().unwrap_or(base);
+    let collection = match simple {
+        "List" | "ArrayList" | "LinkedList" | "Collection" => "List",
+        "Iterable" => "IEnumerable",
+        "Set" | "HashSet" => "HashSet",
+        "TreeSet" | "SortedSet" => "SortedSet",
+        "Map" | "HashMap" => "Dictionary",
+        "TreeMap" | "SortedMap" => "SortedDictionary",
+        "Optional" if args.len() == 1 => return format!("{}?", args[0]),
+        _ => map_name(base),
+    };
+    format!("{}<{}>", collection, args.join(", "))
+}
+
+fn map_name(name: &str) -> &str {
+    match name {
+        "Integer" => "int",
+        "Long" => "long",
+        "Short" => "short",
+        "Byte" => "byte",
+        "Float" => "float",
+        "Double" => "double",
+        "Character" => "char",
+        "boolean" | "Boolean" => "bool",
+        "String" | "java.lang.String" => "string",
+        "Object" | "java.lang.Object" => "object",
+        "Void" => "void",
+        "BigDecimal" | "java.math.BigDecimal" => "decimal",
+        "UUID" | "java.util.UUID" => "Guid",
+        "Date"
+        | "java.util.Date"
+        | "LocalDate"
+        | "java.time.LocalDate"
+        | "LocalDateTime"
+        | "java.time.LocalDateTime"
+        | "Instant"
+        | "java.time.Instant" => "DateTime",
+        // java packages don't exist in C#
+        _ => name.rsplit('.').next().unwrap_or(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::fixture::{self, HEROES};
+
+    #[test]
+    fn classes_get_properties_and_base_constructors() {
+        let mut options = Options::default();
+        options.config.package = Some("com.example".to_string());
+        let files: Vec<String> = fixture::files(HEROES, &CSharp, &options)
+            .into_iter()
+            .map(|f| f.code)
+            .collect();
+
+        assert_eq!(
+            files[0],
+            "using System;
+using System.Collections.Generic;
+
+namespace Com.Example
+{
+    public abstract class Held
+    {
+        protected string Name { get; set; }
+        private List<int> Punkte { get; set; }
+
+        protected Held(string name, List<int> punkte)
+        {
+            Name = name;
+            Punkte = punkte;
+        }
+
+        public abstract bool Angreifen(Monster ziel);
+
+        public virtual string GetName()
+        {
+            throw new NotImplementedException();
+        }
+    }
+}
+"
+        );
+        assert!(files[1].contains(
+            "    public class Krieger : Held, Monster
+    {
+        private Waffe? Waffe { get; set; }
+        public static int Anzahl { get; set; }
+
+        public Krieger(Waffe? waffe, string name, List<int> punkte) : base(name, punkte)
+        {
+            Waffe = waffe;
+        }
+"
+        ));
+        assert!(files[1].contains("\n        public override bool Angreifen(Monster ziel)\n"));
+        assert!(files[1].contains("\n        public void Brüllen()\n"));
+        assert!(files[2]
+            .contains("    public interface Monster\n    {\n        void Brüllen();\n    }\n"));
+        assert!(files[3]
+            .contains("    public enum Farbe\n    {\n        Rot,\n        DunkelGruen\n    }\n"));
+    }
+
+    #[test]
+    fn reserved_parameters_are_escaped() {
+        let input = "@startuml
+class Vertrag {
+ - object : String
+ - in : int
+ + lock(ref : Vertrag) : void
+}
+class Anhang
+Vertrag <|-- Anhang
+@enduml";
+        let files: Vec<String> = fixture::files(input, &CSharp, &Options::default())
+            .into_iter()
+            .map(|f| f.code)
+            .collect();
+
+        assert!(files[0].contains(
+            "    private string Object { get; set; }
+    private int In { get; set; }
+
+    public Vertrag(string @object, int @in)
+    {
+        Object = @object;
+        In = @in;
+    }
+"
+        ));
+        assert!(files[0].contains("    public virtual void Lock(Vertrag @ref)\n"));
+        assert!(
+            files[1].contains("    public Anhang(string @object, int @in) : base(@object, @in)\n")
+        );
+    }
+
+    #[test]
+    fn java_types_are_replaced() {
+        assert_eq!(map_type("java.util.List<Integer>"), "List<int>");
+        assert_eq!(
+            map_type("Map<String, Set<LocalDate?>>"),
+            "Dictionary<string, HashSet<DateTime?>>"
+        );
+        assert_eq!(map_type("Optional<Boolean>"), "bool?");
+        assert_eq!(map_type("com.example.Held[]"), "Held[]");
+    }
+}
@@ -0,0 +1,589 @@
+// Copyright (c) 2023, Oskar Ohlenmacher
+// All rights reserved
+//
+// This source code is licensed under the BSD-style license found in the
+// LICENSE file in the root directory of this source tree.
+
+use super::{doc_comment, indent, reindent, shorten_names, Backend};
+use crate::config::{BraceStyle, Config};
+use crate::generate::Options;
+use crate::model::{Attribute, Class, ClassKind, Function, Sealing, View};
+use crate::template::TemplateError;
+
+/// Java classes, interfaces, enums and records
+#[derive(Debug, Clone, Copy)]
+pub struct Java;
+
+impl Backend for Java {
+    fn extension(&self) -> &'static str {
+        "java"
+    }
+
+    fn class(&self, class: &Class) -> String {
+        let mut str = String::new();
+
+        if let Some(doc) = class.doc() {
+            // record components are documented at the record
+            let components = match class.get_kind() {
+                ClassKind::Record => class.instance_attributes(),
+                _ => Vec::new(),
+            };
+            str.push_str(&doc_comment(doc, &components, ""));
+        }
+
+        // top name and class dfinition
+        str.push_str(class.visibility().as_str());
+        str.push(' ');
+        if class.is_abstract() && class.get_kind() == ClassKind::Class {
+            str.push_str("abstract ");
+        }
+        // nested enums and interfaces are always static
+        if class.is_static() && class.get_kind() == ClassKind::Class {
+            str.push_str("static ");
+        }
+        if class.get_sealing() != Sealing::Open {
+            str.push_str(class.get_sealing().as_str());
+            str.push(' ');
+        }
+        str.push_str(class.get_kind().as_str());
+        str.push(' ');
+        str.push_str(class.name());
+        // record components
+        if class.get_kind() == ClassKind::Record {
+            let components = class.instance_attributes();
+            str.push('(');
+            for p in components.iter() {
+                str.push_str(&parameter(*p));
+            }
+            if !components.is_empty() {
+                str.pop();
+                str.pop();
+            }
+            str.push(')');
+        }
+        str.push(' ');
+        if let Some(s) = class.extends() {
+            str.push_str("extends ");
+            str.push_str(s.name());
+            str.push(' ');
+        }
+        // interfaces extend other interfaces
+        if !class.implements().is_empty() {
+            match class.get_kind() {
+                ClassKind::Interface => str.push_str("extends "),
+                _ => str.push_str("implements "),
+            }
+            for interface in class.implements().iter() {
+                str.push_str(interface.name());
+                str.push_str(", ");
+            }
+            str.pop();
+            str.pop();
+            str.push(' ');
+        }
+        if !class.permits().is_empty() {
+            str.push_str("permits ");
+            str.push_str(&class.permits().join(", "));
+            str.push(' ');
+        }
+        str.push('{');
+        str.push('\n');
+
+        match class.get_kind() {
+            ClassKind::Class => push_class_body(class, &mut str),
+            ClassKind::Interface => push_interface_body(class, &mut str),
+            ClassKind::Enum => push_enum_body(class, &mut str),
+            ClassKind::Record => push_record_body(class, &mut str),
+        }
+
+        // inner classes, enums and interfaces
+        for inner in class.inner().iter() {
+            str.push_str(&indent(&self.class(inner)));
+            str.push('\n');
+        }
+
+        str.push('}');
+        str.push('\n');
+
+        str
+    }
+
+    fn file(&self, class: &Class, options: &Options) -> Result<String, TemplateError> {
+        let java = match &options.templates {
+            Some(templates) => templates.render(class)?,
+            None => self.class(class),
+        };
+        Ok(format(class, &java, &options.config))
+    }
+}
+
+fn push_class_body(class: &Class, str: &mut String) {
+    // attrbutes
+    for p in class.attributes().iter() {
+        str.push_str("    ");
+        str.push_str(&attribute_javadoc(*p));
+        str.push_str(&attribute(*p));
+        str.push('\n');
+    }
+
+    // constructor func
+    str.push('\n');
+    push_constructor(class, class.visibility(), str);
+    str.push('\n');
+
+    // methods
+    push_methods(class.methods(), str);
+
+    str.push('\n');
+
+    push_accessors(class, str);
+    push_methods(&class.inherited_methods(), str);
+}
+
+/// Getters and setters for all attributes which aren't public
+fn push_accessors(class: &Class, str: &mut String) {
+    for accessor in class.accessors() {
+        let vartype = accessor.field.vartype().to_string();
+        let name = accessor.field.name();
+        match accessor.is_getter {
+            true => str.push_str(&format!(
+                "    public {} {}() {{\n        return {};\n    }}\n\n",
+                vartype, accessor.name, name
+            )),
+            false => str.push_str(&format!(
+                "    public void {}({} {}) {{\n        this.{} = {};\n    }}\n\n",
+                accessor.name, vartype, name, name, name
+            )),
+        }
+    }
+}
+
+fn push_methods(methods: &[Function], str: &mut String) {
+    for f in methods.iter() {
+        str.push_str("    ");
+        str.push_str(&method_javadoc(f));
+        str.push_str(&method(f, f.visibility(), f.is_abstract()));
+        str.push('\n');
+    }
+}
+
+fn push_record_body(class: &Class, str: &mut String) {
+    // only static fields can be declared in the body
+    for p in class.attributes().iter().filter(|p| p.is_static()) {
+        str.push_str("    ");
+        str.push_str(&attribute_javadoc(*p));
+        str.push_str(&attribute(*p));
+        str.push('\n');
+    }
+
+    // compact constructor
+    str.push_str("    ");
+    str.push_str(class.visibility().as_str());
+    str.push(' ');
+    str.push_str(class.name());
+    str.push_str(" {\n    }\n\n");
+
+    push_methods(class.methods(), str);
+    push_methods(&class.inherited_methods(), str);
+}
+
+fn push_interface_body(class: &Class, str: &mut String) {
+    // interface methods have no body unless they are static
+    for f in class.methods().iter() {
+        str.push_str("    ");
+        str.push_str(&method_javadoc(f));
+        let java = method(f, f.visibility(), !f.is_static());
+        str.push_str(&java.replacen("abstract ", "", 1));
+        str.push('\n');
+    }
+}
+
+fn push_enum_body(class: &Class, str: &mut String) {
+    // constants
+    let constants = class.constants();
+    for (i, c) in constants.iter().enumerate() {
+        str.push_str("    ");
+        str.push_str(c);
+        if i + 1 < constants.len() {
+            str.push(',');
+        } else {
+            str.push(';');
+        }
+        str.push('\n');
+    }
+    if constants.is_empty() {
+        str.push_str("    ;\n");
+    }
+    str.push('\n');
+
+    for p in class.attributes().iter() {
+        str.push_str("    ");
+        str.push_str(&attribute_javadoc(*p));
+        str.push_str(&attribute(*p));
+        str.push('\n');
+    }
+
+    // enum constructors are always private
+    let instance_attributes = class.instance_attributes();
+    if !instance_attributes.is_empty() {
+        str.push('\n');
+        push_constructor(class, View::Private, str);
+        str.push('\n');
+    }
+
+    push_methods(class.methods(), str);
+    push_methods(&class.inherited_methods(), str);
+}
+
+/// The constructor which takes all instance attributes, passing the inherited
+/// ones to the super class
+fn push_constructor(class: &Class, view: View, str: &mut String) {
+    let constructor = class.get_constructor_func();
+    str.push_str("    ");
+    if !view.as_str().is_empty() {
+        str.push_str(view.as_str());
+        str.push(' ');
+    }
+    str.push_str(constructor.name());
+    str.push('(');
+    let parameters: Vec<_> = constructor
+        .parameters()
+        .iter()
+        .map(|p| parameter(*p))
+        .collect();
+    str.push_str(parameters.concat().trim_end_matches(", "));
+    str.push_str(") {\n");
+    if let Some(master) = class.extends() {
+        let attributes: Vec<_> = master
+            .instance_attributes()
+            .iter()
+            .map(|a| a.name())
+            .collect();
+        str.push_str(&format!("        super({});\n", attributes.join(", ")));
+    }
+    for p in class.instance_attributes().iter() {
+        str.push_str(&format!("        this.{} = {};\n", p.name(), p.name()));
+    }
+    str.push_str("    }\n");
+}
+
+fn method(f: &Function, view: View, is_abstract: bool) -> String {
+    let mut str = String::new();
+    str.push_str(view.as_str());
+    str.push(' ');
+    if is_abstract {
+        str.push_str("abstract");
+        str.push(' ');
+    }
+    if f.is_static() {
+        str.push_str("static");
+        str.push(' ');
+    }
+    str.push_str(&f.return_type().to_string());
+    str.push(' ');
+    str.push_str(f.name());
+    str.push('(');
+    for p in f.parameters().iter() {
+        str.push_str(&parameter(*p));
+    }
+    if !f.parameters().is_empty() {
+        str.pop();
+        str.pop();
+    }
+    match is_abstract {
+        true => str.push_str(");"),
+        false => str.push_str(") {\n    }"),
+    }
+
+    str
+}
+
+fn parameter(p: Attribute) -> String {
+    let mut str = String::new();
+    if p.is_final() {
+        str.push_str("final ");
+    }
+    str.push_str(&p.vartype().to_string());
+    str.push(' ');
+    str.push_str(p.name());
+    str.push_str(", ");
+    str
+}
+
+fn attribute(p: Attribute) -> String {
+    let mut str = String::new();
+    if p.is_final() {
+        str.push_str("final ");
+    }
+    str.push_str(p.visibility().as_str());
+    str.push(' ');
+    if p.is_static() {
+        str.push_str("static ");
+    }
+    str.push_str(&p.vartype().to_string());
+    str.push(' ');
+    str.push_str(p.name());
+    str.push(';');
+
+    str
+}
+
+fn attribute_javadoc(p: Attribute) -> String {
+    p.doc()
+        .map(|doc| doc_comment(doc, &[], "    "))
+        .unwrap_or_default()
+}
+
+/// The documentation with the parameters of the method
+fn method_javadoc(f: &Function) -> String {
+    f.doc()
+        .map(|doc| doc_comment(doc, f.parameters(), "    "))
+        .unwrap_or_default()
+}
+
+/// The complete java file of the class with package, imports and formatting
+pub fn format(class: &Class, java: &str, config: &Config) -> String {
+    let own_package = config.package.as_deref();
+    let (java, imports) = shorten_names(java, class.name(), &|package| {
+        package == "java.lang" || Some(package) == own_package
+    });
+
+    let mut str = String::new();
+    if let Some(package) = &config.package {
+        str.push_str(&format!("package {};\n\n", package));
+    }
+    for import in imports.iter() {
+        str.push_str(&format!("import {};\n", import));
+    }
+    if !imports.is_empty() {
+        str.push('\n');
+    }
+
+    str.push_str(&reindent(&java, config, BraceStyle::SameLine));
+    str
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Accessors, Type};
+    use std::path::Path;
+
+    #[test]
+    fn nested_classes_are_indented_into_outer() {
+        let inner = Class::build("Inner", View::Public, false)
+            .with_static(true)
+            .with_attribute(Attribute::new(
+                View::Private,
+                "x",
+                Type::Other("int"),
+                false,
+            ));
+        let color = Class::build("Color", View::Public, false)
+            .with_kind(ClassKind::Enum)
+            .with_constant("RED")
+            .with_constant("GREEN");
+        let mut outer = Class::build("Outer", View::Public, false);
+        outer.add_inner(inner);
+        outer.add_inner(color);
+
+        let java = Java.class(&outer);
+        assert!(java.starts_with("public class Outer {"));
+        assert!(java.contains("\n    public static class Inner {\n        private int x;"));
+        assert!(java.contains("\n    public enum Color {\n        RED,\n        GREEN;\n"));
+        assert!(java.ends_with("    }\n\n}\n"));
+    }
+
+    #[test]
+    fn records_use_attributes_as_components() {
+        let method = Function::new(
+            "area",
+            View::Public,
+            Type::Other("int"),
+            Vec::new(),
+            false,
+            false,
+        );
+        let shape = Class::build("Shape", View::Public, false)
+            .with_kind(ClassKind::Interface)
+            .with_method(method.clone());
+        let mut point = Class::build("Point", View::Public, false)
+            .with_kind(ClassKind::Record)
+            .with_attribute(Attribute::new(
+                View::Private,
+                "x",
+                Type::Other("int"),
+                false,
+            ))
+            .with_attribute(Attribute::new(
+                View::Private,
+                "y",
+                Type::Other("int"),
+                false,
+            ));
+        point.add_implements(shape);
+
+        assert_eq!(
+            Java.class(&point),
+            "public record Point(int x, int y) implements Shape {\n    public Point {\n    }\n\n    public int area() {\n    }\n}\n"
+        );
+
+        // declared methods are not implemented twice
+        let point = point.with_method(method);
+        assert_eq!(Java.class(&point).matches("area()").count(), 1);
+    }
+
+    #[test]
+    fn accessors_are_generated_for_hidden_attributes() {
+        let mut person = Class::build("Person", View::Public, false)
+            .with_attribute(Attribute::new(
+                View::Private,
+                "name",
+                Type::Other("String"),
+                false,
+            ))
+            .with_attribute(Attribute::new(
+                View::Protected,
+                "active",
+                Type::Other("boolean"),
+                true,
+            ))
+            .with_attribute(Attribute::new(
+                View::Public,
+                "age",
+                Type::Other("int"),
+                false,
+            ));
+        person.set_accessors(Accessors::GettersAndSetters);
+
+        let java = Java.class(&person);
+        assert!(java.contains("    public String getName() {\n        return name;\n    }\n\n"));
+        assert!(java.contains(
+            "    public void setName(String name) {\n        this.name = name;\n    }\n\n"
+        ));
+        assert!(java.contains("public boolean isActive()"));
+        assert!(!java.contains("setActive"));
+        assert!(!java.contains("getAge"));
+    }
+
+    #[test]
+    fn static_attributes_are_not_constructor_parameters() {
+        let input = "@startuml
+class Counter {
+ - {static} count : int
+ - step : int
+}
+class Stepper {
+ - size : int
+}
+Counter <|-- Stepper
+@enduml";
+        let tokens = crate::tokenizer::get_identifiers_from_str(input.to_string()).unwrap();
+        let ast = crate::generate::parse(&tokens).unwrap();
+        let classes = crate::generate::resolve(ast, false).unwrap();
+
+        let java = Java.class(&classes[0]);
+        assert!(java.contains("    private static int count;\n"));
+        assert!(
+            java.contains("    public Counter(int step) {\n        this.step = step;\n    }\n\n")
+        );
+        assert!(!java.contains("this.count"));
+        let java = Java.class(&classes[1]);
+        assert!(java.contains(
+            "    public Stepper(int size, int step) {\n        super(step);\n        this.size = size;\n    }\n"
+        ));
+    }
+
+    #[test]
+    fn comment_end_in_notes_is_escaped() {
+        let mut class = Class::build("Glob", View::Public, false);
+        class.add_doc(None, "Matches src/**/*.java");
+
+        let java = Java.class(&class);
+        assert!(java.starts_with("/**\n * Matches src/**&#47;*.java\n */\n"));
+        let templates = crate::template::Templates::load(None).unwrap();
+        assert_eq!(templates.render(&class).unwrap(), java);
+    }
+
+    #[test]
+    fn only_type_names_are_shortened() {
+        let mut class = Class::build("Clock", View::Public, false).with_attribute(Attribute::new(
+            View::Private,
+            "today",
+            Type::Other("java.time.LocalDate"),
+            false,
+        ));
+        class.add_doc(None, "Wraps java.time.LocalDate");
+        let code = "/** Wraps java.time.LocalDate */\nString s = \"java.time.Clock\";\n";
+        let (code, imports) = shorten_names(code, "Clock", &|_| false);
+        assert_eq!(
+            code,
+            "/** Wraps java.time.LocalDate */\nString s = \"java.time.Clock\";\n"
+        );
+        assert!(imports.is_empty());
+
+        let java = format(&class, &Java.class(&class), &Config::default());
+        assert!(
+            java.starts_with("import java.time.LocalDate;\n\n/**\n * Wraps java.time.LocalDate\n")
+        );
+        assert!(java.contains("\n    private LocalDate today;\n"));
+    }
+
+    #[test]
+    fn interface_methods_have_no_body() {
+        let method = Function::new(
+            "run",
+            View::Public,
+            Type::Other("void"),
+            Vec::new(),
+            false,
+            false,
+        );
+        let runnable = Class::build("Runnable", View::Public, false)
+            .with_kind(ClassKind::Interface)
+            .with_method(method);
+
+        assert_eq!(
+            Java.class(&runnable),
+            "public interface Runnable {\n    public void run();\n}\n"
+        );
+    }
+
+    #[test]
+    fn qualified_types_are_imported() {
+        let mut config = Config {
+            package: Some("com.example".to_string()),
+            indent: 2,
+            ..Config::default()
+        };
+        config
+            .types
+            .insert("Date".to_string(), "java.time.LocalDate".to_string());
+
+        let mut class = Class::build("Person", View::Public, false)
+            .with_attribute(Attribute::new(
+                View::Private,
+                "birthday",
+                Type::Other("Date"),
+                false,
+            ))
+            .with_attribute(Attribute::new(
+                View::Private,
+                "scores",
+                Type::Other("int[*]"),
+                false,
+            ));
+        class.map_types(&|t| config.map_type(t));
+
+        let java = format(&class, &Java.class(&class), &config);
+        assert!(java.starts_with(
+            "package com.example;\n\nimport java.time.LocalDate;\nimport java.util.List;\n\n"
+        ));
+        assert!(java.contains("\n  private LocalDate birthday;\n"));
+        assert!(java.contains("\n  private List<Integer> scores;\n"));
+        assert_eq!(
+            Java.path(&class, &config),
+            Path::new("com/example/Person.java")
+        );
+    }
+}
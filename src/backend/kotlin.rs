@@ -0,0 +1,561 @@
+// Copyright (c) 2023, Oskar Ohlenmacher
+// All rights reserved
+//
+// This source code is licensed under the BSD-style license found in the
+// LICENSE file in the root directory of this source tree.
+
+use super::{doc_comment, indent, reindent, shorten_names, Backend};
+use crate::config::{BraceStyle, Config};
+use crate::generate::Options;
+use crate::model::{Attribute, Class, ClassKind, Function, Sealing, Type, View};
+use crate::template::TemplateError;
+
+/// Kotlin classes with primary constructors, data classes for value types and
+/// companion objects for static members
+#[derive(Debug, Clone, Copy)]
+pub struct Kotlin;
+
+/// Body of methods which aren't abstract
+const STUB: &str = "TODO(\"Not yet implemented\")";
+
+/// Hard keywords, which are only names in backticks
+const KEYWORDS: [&str; 28] = [
+    "as",
+    "break",
+    "class",
+    "continue",
+    "do",
+    "else",
+    "false",
+    "for",
+    "fun",
+    "if",
+    "in",
+    "interface",
+    "is",
+    "null",
+    "object",
+    "package",
+    "return",
+    "super",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typealias",
+    "typeof",
+    "val",
+    "var",
+    "when",
+    "while",
+];
+
+impl Backend for Kotlin {
+    fn extension(&self) -> &'static str {
+        "kt"
+    }
+
+    fn class(&self, class: &Class) -> String {
+        declaration(class, false)
+    }
+
+    fn file(&self, class: &Class, options: &Options) -> Result<String, TemplateError> {
+        Ok(format(class, &self.class(class), &options.config))
+    }
+}
+
+/// The class with its inner classes, nested is set for inner classes
+fn declaration(class: &Class, nested: bool) -> String {
+    let mut str = String::new();
+
+    if let Some(doc) = class.doc() {
+        str.push_str(&doc_comment(doc, &[], ""));
+    }
+
+    str.push_str(visibility(class.visibility()));
+    str.push_str(&modifiers(class, nested));
+    str.push_str(kind(class.get_kind()));
+    str.push(' ');
+    str.push_str(class.name());
+    str.push_str(&primary_constructor(class));
+
+    // the super class is called with its attributes, interfaces are listed
+    let mut supertypes = Vec::new();
+    if let Some(master) = class.extends() {
+        let args: Vec<String> = master
+            .instance_attributes()
+            .iter()
+            .map(|a| identifier(a.name()))
+            .collect();
+        supertypes.push(format!("{}({})", master.name(), args.join(", ")));
+    }
+    supertypes.extend(class.implements().iter().map(|c| c.name().to_string()));
+    if !supertypes.is_empty() {
+        str.push_str(" : ");
+        str.push_str(&supertypes.join(", "));
+    }
+
+    let mut sections = Vec::new();
+    if class.get_kind() == ClassKind::Enum {
+        sections.push(enum_constants(class));
+    }
+    let methods: Vec<String> = class
+        .methods()
+        .iter()
+        .filter(|f| !f.is_static())
+        .map(|f| method(class, f, false))
+        .chain(
+            class
+                .inherited_methods()
+                .iter()
+                .map(|f| method(class, f, true)),
+        )
+        .collect();
+    if !methods.is_empty() {
+        sections.push(methods.join("\n\n"));
+    }
+    let companion = companion_object(class);
+    if !companion.is_empty() {
+        sections.push(companion);
+    }
+    for inner in class.inner().iter() {
+        sections.push(indent(&declaration(inner, true)).trim_end().to_string());
+    }
+    // the body can be left out
+    if !sections.is_empty() {
+        str.push_str(" {\n");
+        str.push_str(&sections.join("\n\n"));
+        str.push_str("\n}");
+    }
+    str.push('\n');
+    str
+}
+
+/// Modifiers between the visibility and the kind of the class
+fn modifiers(class: &Class, nested: bool) -> String {
+    let mut str = String::new();
+    let is_class = class.get_kind() == ClassKind::Class;
+    if class.get_sealing() == Sealing::Sealed {
+        str.push_str("sealed ");
+    } else if class.is_abstract() && is_class {
+        str.push_str("abstract ");
+    } else if !class.subtypes().is_empty() && is_class {
+        str.push_str("open ");
+    } else if is_data_class(class) {
+        str.push_str("data ");
+    }
+    // classes are nested unless they are marked as inner
+    if nested && !class.is_static() && is_class {
+        str.push_str("inner ");
+    }
+    str
+}
+
+/// Records and classes which only hold values
+fn is_data_class(class: &Class) -> bool {
+    match class.get_kind() {
+        ClassKind::Record => !class.instance_attributes().is_empty(),
+        ClassKind::Class => {
+            !class.instance_attributes().is_empty()
+                && class.methods().is_empty()
+                && !class.is_abstract()
+                && class.extends().is_none()
+                && class.subtypes().is_empty()
+        }
+        _ => false,
+    }
+}
+
+fn kind(kind: ClassKind) -> &'static str {
+    match kind {
+        ClassKind::Class | ClassKind::Record => "class",
+        ClassKind::Interface => "interface",
+        ClassKind::Enum => "enum class",
+    }
+}
+
+/// Properties of the instance attributes and parameters passed to the super class
+fn primary_constructor(class: &Class) -> String {
+    if class.get_kind() == ClassKind::Interface {
+        return String::new();
+    }
+    let mut parameters: Vec<String> = class
+        .instance_attributes()
+        .iter()
+        .map(|a| {
+            // records and enums are immutable, record components are public
+            let (visibility, keyword) = match class.get_kind() {
+                ClassKind::Record => ("", "val"),
+                ClassKind::Enum => (visibility(a.visibility()), "val"),
+                _ if a.is_final() => (visibility(a.visibility()), "val"),
+                _ => (visibility(a.visibility()), "var"),
+            };
+            format!(
+                "{}{} {}: {}",
+                visibility,
+                keyword,
+                identifier(a.name()),
+                type_name(a.vartype())
+            )
+        })
+        .collect();
+    if let Some(master) = class.extends() {
+        parameters.extend(
+            master
+                .instance_attributes()
+                .iter()
+                .map(|a| format!("{}: {}", identifier(a.name()), type_name(a.vartype()))),
+        );
+    }
+    match parameters.is_empty() {
+        true => String::new(),
+        false => format!("({})", parameters.join(", ")),
+    }
+}
+
+fn enum_constants(class: &Class) -> String {
+    let constants: Vec<String> = class
+        .constants()
+        .iter()
+        .map(|c| format!("    {}", c))
+        .collect();
+    format!("{};", constants.join(",\n"))
+}
+
+/// Static attributes and methods
+fn companion_object(class: &Class) -> String {
+    let mut members = Vec::new();
+    for a in class.attributes().iter().filter(|a| a.is_static()) {
+        members.push(property(*a));
+    }
+    for f in class.methods().iter().filter(|f| f.is_static()) {
+        members.push(method(class, f, false));
+    }
+    if members.is_empty() {
+        return String::new();
+    }
+    let members: Vec<String> = members
+        .iter()
+        .map(|m| indent(m).trim_end().to_string())
+        .collect();
+    format!("    companion object {{\n{}\n    }}", members.join("\n\n"))
+}
+
+/// Property of a static attribute, initialized with the default of its type
+fn property(a: Attribute) -> String {
+    let mut str = String::from("    ");
+    if let Some(doc) = a.doc() {
+        str.push_str(&doc_comment(doc, &[], "    "));
+    }
+    let vartype = type_name(a.vartype());
+    str.push_str(visibility(a.visibility()));
+    let name = identifier(a.name());
+    match (default_value(&vartype), a.is_final()) {
+        (Some(value), true) => str.push_str(&format!("val {}: {} = {}", name, vartype, value)),
+        (Some(value), false) => str.push_str(&format!("var {}: {} = {}", name, vartype, value)),
+        (None, _) => str.push_str(&format!("lateinit var {}: {}", name, vartype)),
+    }
+    str
+}
+
+fn default_value(vartype: &str) -> Option<&'static str> {
+    if vartype.ends_with('?') {
+        return Some("null");
+    }
+    let base = vartype.split('<').next().unwrap_or_default();
+    match base {
+        "Int" | "Long" | "Short" | "Byte" => Some("0"),
+        "Double" => Some("0.0"),
+        "Float" => Some("0f"),
+        "Boolean" => Some("false"),
+        "Char" => Some("'\\u0000'"),
+        "String" => Some("\"\""),
+        "List" | "Collection" | "Iterable" => Some("emptyList()"),
+        "Set" => Some("emptySet()"),
+        "Map" => Some("emptyMap()"),
+        _ => None,
+    }
+}
+
+/// Declaration of the method, implements is set for methods of the super class and
+/// interfaces which aren't declared in the diagram
+fn method(class: &Class, f: &Function, implements: bool) -> String {
+    let mut str = String::from("    ");
+    if let Some(doc) = f.doc() {
+        str.push_str(&doc_comment(doc, f.parameters(), "    "));
+    }
+    let in_interface = class.get_kind() == ClassKind::Interface;
+    let is_abstract = match in_interface {
+        true => !f.is_static(),
+        false => f.is_abstract(),
+    };
+
+    if implements || overrides(class, f) {
+        str.push_str("override ");
+    } else {
+        str.push_str(visibility(f.visibility()));
+        if is_abstract && !in_interface {
+            str.push_str("abstract ");
+        } else if is_open(class) && !f.is_static() && !matches!(f.visibility(), View::Private) {
+            str.push_str("open ");
+        }
+    }
+    str.push_str("fun ");
+    str.push_str(&identifier(f.name()));
+    str.push('(');
+    let parameters: Vec<String> = f
+        .parameters()
+        .iter()
+        .map(|p| format!("{}: {}", identifier(p.name()), type_name(p.vartype())))
+        .collect();
+    str.push_str(&parameters.join(", "));
+    str.push(')');
+    let return_type = type_name(f.return_type());
+    if !matches!(return_type.as_str(), "Unit" | "") {
+        str.push_str(": ");
+        str.push_str(&return_type);
+    }
+    if !is_abstract {
+        str.push_str(&format!(" {{\n        {}\n    }}", STUB));
+    }
+    str
+}
+
+/// Classes whose methods can be overridden
+fn is_open(class: &Class) -> bool {
+    class.get_kind() == ClassKind::Class
+        && (class.is_abstract()
+            || !class.subtypes().is_empty()
+            || class.get_sealing() == Sealing::Sealed)
+}
+
+/// Whether a super class or interface declares the method
+fn overrides(class: &Class, f: &Function) -> bool {
+    class
+        .extends()
+        .into_iter()
+        .chain(class.implements().iter())
+        .any(|master| {
+            master
+                .methods()
+                .iter()
+                .any(|m| f.overrides(m) && !m.is_static())
+                || overrides(master, f)
+        })
+}
+
+/// Name of an attribute, method or parameter, keywords are put in backticks
+fn identifier(name: &str) -> String {
+    match KEYWORDS.contains(&name) {
+        true => format!("`{}`", name),
+        false => name.to_string(),
+    }
+}
+
+/// Public is the default
+fn visibility(view: View) -> &'static str {
+    match view {
+        View::Normal => "internal ",
+        View::Public => "",
+        View::Protected => "protected ",
+        View::Private => "private ",
+    }
+}
+
+/// Kotlin name of a type of the diagram, java types are replaced by the kotlin ones
+fn type_name(vartype: Type) -> String {
+    let name = match vartype {
+        Type::Other(s) => s.to_string(),
+        Type::Collection(c, e) => format!("{}<{}>", c, e),
+    };
+    map_type(name.trim())
+}
+
+fn map_type(name: &str) -> String {
+    if let Some(base) = name.strip_suffix('?') {
+        return format!("{}?", map_type(base));
+    }
+    if let Some(element) = name.strip_suffix("[]") {
+        return match map_type(element).as_str() {
+            primitive @ ("Int" | "Long" | "Short" | "Byte" | "Double" | "Float" | "Boolean"
+            | "Char") => format!("{}Array", primitive),
+            element => format!("Array<{}>", element),
+        };
+    }
+    // every name of generic types like Map<String, Integer>
+    let mut str = String::new();
+    let mut rest = name;
+    while let Some(start) = rest.find(|c: char| c.is_alphanumeric() || c == '_') {
+        str.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+            .unwrap_or(rest.len());
+        str.push_str(map_name(&rest[..end]));
+        rest = &rest[end..];
+    }
+    str.push_str(rest);
+    str
+}
+
+fn map_name(name: &str) -> &str {
+    match name {
+        "int" | "Integer" | "java.lang.Integer" => "Int",
+        "long" | "java.lang.Long" => "Long",
+        "short" | "java.lang.Short" => "Short",
+        "byte" | "java.lang.Byte" => "Byte",
+        "double" | "java.lang.Double" => "Double",
+        "float" | "java.lang.Float" => "Float",
+        "boolean" | "java.lang.Boolean" => "Boolean",
+        "char" | "Character" | "java.lang.Character" => "Char",
+        "void" | "Void" => "Unit",
+        "Object" | "java.lang.Object" => "Any",
+        "java.lang.String" => "String",
+        "java.util.List" | "java.util.ArrayList" => "List",
+        "java.util.Set" | "java.util.HashSet" => "Set",
+        "java.util.Map" | "java.util.HashMap" => "Map",
+        "java.util.Collection" => "Collection",
+        _ => name,
+    }
+}
+
+/// The complete kotlin file of the class with package and imports
+pub fn format(class: &Class, kotlin: &str, config: &Config) -> String {
+    let own_package = config.package.as_deref();
+    let (kotlin, imports) = shorten_names(kotlin, class.name(), &|package| {
+        package == "kotlin" || package.starts_with("kotlin.") || Some(package) == own_package
+    });
+
+    let mut str = String::new();
+    if let Some(package) = &config.package {
+        str.push_str(&format!("package {}\n\n", package));
+    }
+    for import in imports.iter() {
+        str.push_str(&format!("import {}\n", import));
+    }
+    if !imports.is_empty() {
+        str.push('\n');
+    }
+    str.push_str(&reindent(&kotlin, config, BraceStyle::SameLine));
+    str
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::fixture::{self, HEROES};
+
+    fn kotlin(input: &str) -> Vec<String> {
+        fixture::files(input, &Kotlin, &Options::default())
+            .into_iter()
+            .map(|f| f.code)
+            .collect()
+    }
+
+    #[test]
+    fn classes_get_primary_constructors() {
+        let classes = kotlin(HEROES);
+
+        assert_eq!(
+            classes[0],
+            "abstract class Held(protected var name: String, private var punkte: List<Int>) {
+    abstract fun angreifen(ziel: Monster): Boolean
+
+    open fun getName(): String {
+        TODO(\"Not yet implemented\")
+    }
+}
+"
+        );
+        assert!(classes[1].contains(
+            "class Krieger(private var waffe: Waffe?, name: String, punkte: List<Int>) : Held(name, punkte), Monster {
+    override fun angreifen(ziel: Monster): Boolean {
+        TODO(\"Not yet implemented\")
+    }
+
+    override fun brüllen() {
+        TODO(\"Not yet implemented\")
+    }
+
+    companion object {
+        var anzahl: Int = 0
+    }
+}
+"
+        ));
+        assert_eq!(classes[2], "interface Monster {\n    fun brüllen()\n}\n");
+        assert_eq!(
+            classes[3],
+            "enum class Farbe {\n    ROT,\n    DUNKEL_GRUEN;\n}\n"
+        );
+    }
+
+    #[test]
+    fn data_classes_and_companion_functions() {
+        let classes = kotlin(
+            "@startuml
+class Punkt {
+ + x : double
+ + y : double
+}
+class Krieger {
+ + heilen(ziel : Monster?) : void
+ + {static} rekrutieren() : Krieger
+}
+@enduml",
+        );
+
+        assert_eq!(
+            classes[0],
+            "data class Punkt(var x: Double, var y: Double)\n"
+        );
+        assert_eq!(
+            classes[1],
+            "class Krieger {
+    fun heilen(ziel: Monster?) {
+        TODO(\"Not yet implemented\")
+    }
+
+    companion object {
+        fun rekrutieren(): Krieger {
+            TODO(\"Not yet implemented\")
+        }
+    }
+}
+"
+        );
+    }
+
+    #[test]
+    fn keywords_are_escaped() {
+        let classes = kotlin(
+            "@startuml
+class Vertrag {
+ - object : String
+ - in : int
+ + {static} when : int
+ + match(fun : int, is : Vertrag) : void
+}
+class Anhang
+Vertrag <|-- Anhang
+@enduml",
+        );
+
+        assert!(classes[0].starts_with(
+            "open class Vertrag(private var `object`: String, private var `in`: Int) {\n"
+        ));
+        assert!(classes[0].contains("    open fun match(`fun`: Int, `is`: Vertrag) {\n"));
+        assert!(classes[0].contains("        var `when`: Int = 0\n"));
+        assert!(classes[1]
+            .starts_with("class Anhang(`object`: String, `in`: Int) : Vertrag(`object`, `in`)\n"));
+    }
+
+    #[test]
+    fn java_types_are_replaced() {
+        assert_eq!(map_type("java.util.List<Integer>"), "List<Int>");
+        assert_eq!(map_type("Map<String, Object>"), "Map<String, Any>");
+        assert_eq!(map_type("int[]"), "IntArray");
+        assert_eq!(map_type("String[]?"), "Array<String>?");
+        assert_eq!(default_value("Map<String, Int>"), Some("emptyMap()"));
+    }
+}
@@ -0,0 +1,523 @@
+// Copyright (c) 2023, Oskar Ohlenmacher
+// All rights reserved
+//
+// This source code is licensed under the BSD-style license found in the
+// LICENSE file in the root directory of this source tree.
+
+use std::path::PathBuf;
+
+use super::{indent, reindent, snake_case, split_arguments, Backend, SourceFile};
+use crate::config::{BraceStyle, Config};
+use crate::generate::Options;
+use crate::model::{Attribute, Class, ClassKind, Function, Type, View};
+use crate::template::TemplateError;
+
+/// Python dataclasses with type hints, abstract classes and interfaces derive from
+/// `ABC`. All classes of a package are one module, `com.example` is written to
+/// `com/example.py`
+#[derive(Debug, Clone, Copy)]
+pub struct Python;
+
+/// Body of methods which aren't abstract
+const STUB: &str = "raise NotImplementedError";
+
+/// Module of the classes if no package is configured
+const MODULE: &str = "models";
+
+/// Names of the standard library and the import providing them
+const LIBRARY: [(&str, &str, &str); 10] = [
+    ("ABC", "abc", "ABC"),
+    ("abstractmethod", "abc", "abstractmethod"),
+    ("dataclass", "dataclasses", "dataclass"),
+    ("date", "datetime", "date"),
+    ("datetime", "datetime", "datetime"),
+    ("Decimal", "decimal", "Decimal"),
+    ("Enum", "enum", "Enum"),
+    ("auto", "enum", "auto"),
+    ("ClassVar", "typing", "ClassVar"),
+    ("UUID", "uuid", "UUID"),
+];
+
+const KEYWORDS: [&str; 35] = [
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
+impl Backend for Python {
+    fn extension(&self) -> &'static str {
+        "py"
+    }
+
+    /// Module of the package the class is in
+    fn path(&self, _class: &Class, config: &Config) -> PathBuf {
+        let mut path: PathBuf = match &config.package {
+            Some(package) => package.split('.').map(identifier).collect(),
+            None => PathBuf::from(MODULE),
+        };
+        path.set_extension(self.extension());
+        path
+    }
+
+    fn class(&self, class: &Class) -> String {
+        let mut str = String::new();
+        match class.get_kind() {
+            ClassKind::Record => str.push_str("@dataclass(frozen=True)\n"),
+            ClassKind::Class => str.push_str("@dataclass\n"),
+            ClassKind::Interface | ClassKind::Enum => (),
+        }
+        str.push_str("class ");
+        str.push_str(class.name());
+        let bases = bases(class);
+        if !bases.is_empty() {
+            str.push_str(&format!("({})", bases.join(", ")));
+        }
+        str.push_str(":\n");
+
+        let mut sections = Vec::new();
+        if let Some(doc) = class.doc() {
+            sections.push(docstring(doc));
+        }
+        match class.get_kind() {
+            ClassKind::Enum => {
+                let constants: Vec<String> = class
+                    .constants()
+                    .iter()
+                    .map(|c| format!("    {} = auto()", identifier(c)))
+                    .collect();
+                if !constants.is_empty() {
+                    sections.push(constants.join("\n"));
+                }
+                sections.extend(properties(class));
+            }
+            ClassKind::Interface => sections.extend(properties(class)),
+            ClassKind::Class | ClassKind::Record => {
+                let fields: Vec<String> = class.attributes().iter().map(|a| field(*a)).collect();
+                if !fields.is_empty() {
+                    sections.push(fields.join("\n"));
+                }
+            }
+        }
+        let is_interface = class.get_kind() == ClassKind::Interface;
+        for f in class.methods().iter() {
+            sections.push(method(
+                f,
+                (is_interface && !f.is_static()) || f.is_abstract(),
+            ));
+        }
+        for f in class.inherited_methods().iter() {
+            sections.push(method(f, false));
+        }
+        for inner in class.inner().iter() {
+            sections.push(indent(&self.class(inner)).trim_end().to_string());
+        }
+        if sections.is_empty() {
+            sections.push("    pass".to_string());
+        }
+        str.push_str(&sections.join("\n\n"));
+        str.push('\n');
+        str
+    }
+
+    /// The module with only this class, the other classes of the package are
+    /// missing
+    fn file(&self, class: &Class, options: &Options) -> Result<String, TemplateError> {
+        Ok(module(&[class], options))
+    }
+
+    /// One module with all classes and an empty `__init__.py` in every directory
+    /// of the package
+    fn files(
+        &self,
+        classes: &[Class],
+        options: &Options,
+    ) -> Result<Vec<SourceFile>, TemplateError> {
+        let Some(first) = classes.first() else {
+            return Ok(Vec::new());
+        };
+        let path = self.path(first, &options.config);
+        let mut files: Vec<SourceFile> = path
+            .ancestors()
+            .skip(1)
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .map(|dir| SourceFile {
+                path: dir.join("__init__.py"),
+                code: String::new(),
+            })
+            .collect();
+        files.reverse();
+        files.push(SourceFile {
+            path,
+            code: module(&in_dependency_order(classes), options),
+        });
+        Ok(files)
+    }
+}
+
+/// Module with the classes, which are in the order they are defined at runtime
+fn module(classes: &[&Class], options: &Options) -> String {
+    let code: Vec<String> = classes.iter().map(|c| Python.class(c)).collect();
+    // top level definitions are separated by two blank lines
+    let code = code.join("\n\n");
+    let mut str = String::from("from __future__ import annotations\n\n");
+    let library = imports(&code);
+    for (module, names) in library.iter() {
+        str.push_str(&format!("from {} import {}\n", module, names.join(", ")));
+    }
+    if !library.is_empty() {
+        str.push('\n');
+    }
+    str.push('\n');
+    str.push_str(&reindent(&code, &options.config, BraceStyle::SameLine));
+    str
+}
+
+/// Super classes and interfaces have to be defined before the classes deriving
+/// from them, otherwise the order of the diagram is kept
+fn in_dependency_order<'c, 'a>(classes: &'c [Class<'a>]) -> Vec<&'c Class<'a>> {
+    let mut pending: Vec<&Class> = classes.iter().collect();
+    let mut ordered: Vec<&Class> = Vec::with_capacity(classes.len());
+    while !pending.is_empty() {
+        let is_defined = |name: &str| !pending.iter().any(|p| p.name() == name);
+        let next = pending
+            .iter()
+            .position(|c| supertypes(c).iter().all(|s| is_defined(s)))
+            // the diagram has an inheritance cycle
+            .unwrap_or(0);
+        ordered.push(pending.remove(next));
+    }
+    ordered
+}
+
+/// Names of the types the class and its inner classes derive from
+fn supertypes<'a>(class: &Class<'a>) -> Vec<&'a str> {
+    let mut names: Vec<&str> = class
+        .extends()
+        .into_iter()
+        .chain(class.implements().iter())
+        .map(|c| c.name())
+        .collect();
+    for inner in class.inner().iter() {
+        names.extend(supertypes(inner));
+    }
+    names
+}
+
+/// Super class and interfaces, abstract classes and interfaces are `ABC`s
+fn bases(class: &Class) -> Vec<String> {
+    let mut bases: Vec<String> = class
+        .extends()
+        .into_iter()
+        .chain(class.implements().iter())
+        .map(|c| c.name().to_string())
+        .collect();
+    match class.get_kind() {
+        ClassKind::Enum => bases.insert(0, "Enum".to_string()),
+        ClassKind::Interface if bases.is_empty() => bases.push("ABC".to_string()),
+        // the abstract methods are only checked for subclasses of ABC
+        ClassKind::Class
+            if class.is_abstract() && !class.extends().is_some_and(|m| m.is_abstract()) =>
+        {
+            bases.push("ABC".to_string())
+        }
+        _ => (),
+    }
+    bases
+}
+
+/// Field of the dataclass, static attributes are class variables
+fn field(a: Attribute) -> String {
+    let vartype = type_name(a.vartype());
+    let hint = match a.is_static() {
+        true => format!("ClassVar[{}]", vartype),
+        false => vartype,
+    };
+    let mut str = format!("    {}: {}", identifier(&snake_case(a.name())), hint);
+    if let Some(doc) = a.doc() {
+        str.push('\n');
+        str.push_str(&docstring(doc));
+    }
+    str
+}
+
+/// Attributes of interfaces and enums can only be read
+fn properties(class: &Class) -> Vec<String> {
+    let is_interface = class.get_kind() == ClassKind::Interface;
+    class
+        .attributes()
+        .iter()
+        .map(|a| {
+            let mut str = String::from("    @property\n");
+            if is_interface {
+                str.push_str("    @abstractmethod\n");
+            }
+            str.push_str(&format!(
+                "    def {}(self) -> {}:\n",
+                identifier(&snake_case(a.name())),
+                type_name(a.vartype())
+            ));
+            if let Some(doc) = a.doc() {
+                str.push_str(&indent(&docstring(doc)));
+            }
+            str.push_str("        ");
+            str.push_str(if is_interface { "..." } else { STUB });
+            str
+        })
+        .collect()
+}
+
+fn method(f: &Function, is_abstract: bool) -> String {
+    let mut str = String::new();
+    if f.is_static() {
+        str.push_str("    @staticmethod\n");
+    }
+    if is_abstract {
+        str.push_str("    @abstractmethod\n");
+    }
+    let mut parameters = Vec::new();
+    if !f.is_static() {
+        parameters.push("self".to_string());
+    }
+    parameters.extend(f.parameters().iter().map(|p| {
+        let name = identifier(&snake_case(p.name()));
+        format!("{}: {}", name, type_name(p.vartype()))
+    }));
+    let return_type = match type_name(f.return_type()).as_str() {
+        "" => "None".to_string(),
+        t => t.to_string(),
+    };
+    str.push_str(&format!(
+        "    def {}{}({}) -> {}:\n",
+        private(f.visibility()),
+        identifier(&snake_case(f.name())),
+        parameters.join(", "),
+        return_type
+    ));
+    if let Some(doc) = f.doc() {
+        str.push_str(&indent(&docstring(doc)));
+    }
+    str.push_str("        ");
+    str.push_str(if is_abstract { "..." } else { STUB });
+    str
+}
+
+/// Members which aren't public start with an underscore
+fn private(view: View) -> &'static str {
+    match view {
+        View::Public => "",
+        View::Private | View::Protected | View::Normal => "_",
+    }
+}
+
+/// Keywords used as names get a trailing underscore
+fn identifier(name: &str) -> String {
+    match KEYWORDS.contains(&name) {
+        true => format!("{}_", name),
+        false => name.to_string(),
+    }
+}
+
+/// Docstring of a note, indented for the body of a class
+fn docstring(doc: &str) -> String {
+    let mut lines = doc.lines();
+    let mut str = format!("    \"\"\"{}", lines.next().unwrap_or_default());
+    let rest: Vec<&str> = lines.collect();
+    if !rest.is_empty() {
+        str.push('\n');
+        for line in rest {
+            if !line.is_empty() {
+                str.push_str("    ");
+                str.push_str(line);
+            }
+            str.push('\n');
+        }
+        str.push_str("    ");
+    }
+    str.push_str("\"\"\"");
+    str
+}
+
+/// Modules of the standard library with the names the code takes from them, the
+/// other classes are in the same module
+fn imports(code: &str) -> Vec<(&'static str, Vec<&'static str>)> {
+    let mut library: Vec<(&str, Vec<&str>)> = Vec::new();
+    let mut in_docstring = false;
+    for line in code.lines() {
+        // notes aren't code
+        if line.matches("\"\"\"").count() % 2 == 1 {
+            in_docstring = !in_docstring;
+            continue;
+        }
+        if in_docstring || line.trim_start().starts_with("\"\"\"") {
+            continue;
+        }
+        for word in line.split(|c: char| !(c.is_alphanumeric() || c == '_')) {
+            if let Some((_, module, name)) = LIBRARY.iter().find(|(w, _, _)| *w == word) {
+                match library.iter_mut().find(|(m, _)| m == module) {
+                    Some((_, names)) if !names.contains(name) => names.push(name),
+                    Some(_) => (),
+                    None => library.push((module, vec![name])),
+                }
+            }
+        }
+    }
+    library.sort();
+    for (_, names) in library.iter_mut() {
+        names.sort();
+    }
+    library
+}
+
+/// Type hint of a type of the diagram, java types are replaced by the Python ones
+fn type_name(vartype: Type) -> String {
+    let name = match vartype {
+        Type::Other(s) => s.to_string(),
+        Type::Collection(c, e) => format!("{}<{}>", c, e),
+    };
+    map_type(name.trim())
+}
+
+fn map_type(name: &str) -> String {
+    if let Some(base) = name.strip_suffix('?') {
+        return format!("{} | None", map_type(base));
+    }
+    if let Some(element) = name.strip_suffix("[]") {
+        return format!("list[{}]", map_type(element));
+    }
+    let Some((base, args)) = name.strip_suffix('>').and_then(|n| n.split_once('<')) else {
+        return map_name(name).to_string();
+    };
+    let args: Vec<String> = split_arguments(args)
+        .iter()
+        .map(|a| map_type(a.trim()))
+        .collect();
+    let base = base.trim();
+    let simple = base.rsplit('.').next().unwrap_or(base);
+    let collection = match simple {
+        "List" | "ArrayList" | "LinkedList" | "Collection" | "Iterable" => "list",
+        "Set" | "HashSet" | "TreeSet" | "SortedSet" => "set",
+        "Map" | "HashMap" | "TreeMap" | "SortedMap" => "dict",
+        "Optional" if args.len() == 1 => return format!("{} | None", args[0]),
+        _ => map_name(base),
+    };
+    format!("{}[{}]", collection, args.join(", "))
+}
+
+fn map_name(name: &str) -> &str {
+    match name {
+        "int" | "long" | "short" | "byte" | "Integer" | "Long" | "Short" | "Byte" => "int",
+        "float" | "double" | "Float" | "Double" => "float",
+        "boolean" | "Boolean" => "bool",
+        "char" | "Character" | "String" | "java.lang.String" => "str",
+        "Object" | "java.lang.Object" => "object",
+        "void" | "Void" => "None",
+        "BigDecimal" | "java.math.BigDecimal" => "Decimal",
+        "UUID" | "java.util.UUID" => "UUID",
+        "Date" | "LocalDate" | "java.util.Date" | "java.time.LocalDate" => "date",
+        "LocalDateTime" | "Instant" | "java.time.LocalDateTime" | "java.time.Instant" => "datetime",
+        // java packages don't exist in Python
+        _ => name.rsplit('.').next().unwrap_or(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::backend::fixture::{self, HEROES};
+
+    #[test]
+    fn dataclasses_with_abstract_methods() {
+        let mut options = Options::default();
+        options.config.package = Some("com.example".to_string());
+        let files = fixture::files(HEROES, &Python, &options);
+
+        let paths: Vec<&Path> = files.iter().map(|f| f.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            [Path::new("com/__init__.py"), Path::new("com/example.py")]
+        );
+        assert_eq!(files[0].code, "");
+        // the super types are defined before Krieger
+        assert_eq!(
+            files[1].code,
+            "from __future__ import annotations
+
+from abc import ABC, abstractmethod
+from dataclasses import dataclass
+from enum import Enum, auto
+from typing import ClassVar
+
+
+@dataclass
+class Held(ABC):
+    name: str
+    punkte: list[int]
+
+    @abstractmethod
+    def angreifen(self, ziel: Monster) -> bool:
+        ...
+
+    def get_name(self) -> str:
+        raise NotImplementedError
+
+
+class Monster(ABC):
+    @abstractmethod
+    def brüllen(self) -> None:
+        ...
+
+
+@dataclass
+class Krieger(Held, Monster):
+    \"\"\"Kämpft
+    mit Waffen
+    \"\"\"
+
+    waffe: Waffe | None
+    anzahl: ClassVar[int]
+
+    def angreifen(self, ziel: Monster) -> bool:
+        raise NotImplementedError
+
+    def brüllen(self) -> None:
+        raise NotImplementedError
+
+
+class Farbe(Enum):
+    ROT = auto()
+    DUNKEL_GRUEN = auto()
+"
+        );
+    }
+
+    #[test]
+    fn keywords_are_escaped() {
+        let input = "@startuml
+class Vertrag {
+ + with : String
+ + import(from : Vertrag, lambda : int) : void
+}
+@enduml";
+        let files = fixture::files(input, &Python, &Options::default());
+
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, PathBuf::from("models.py"));
+        assert!(files[0].code.contains("    with_: str\n"));
+        assert!(files[0]
+            .code
+            .contains("    def import_(self, from_: Vertrag, lambda_: int) -> None:\n"));
+    }
+
+    #[test]
+    fn java_types_are_replaced() {
+        assert_eq!(map_type("java.util.List<Integer>"), "list[int]");
+        assert_eq!(
+            map_type("Map<String, Set<LocalDate?>>"),
+            "dict[str, set[date | None]]"
+        );
+        assert_eq!(map_type("Optional<double[]>"), "list[float] | None");
+    }
+}
@@ -0,0 +1,976 @@
+// Copyright (c) 2023, Oskar Ohlenmacher
+// All rights reserved
+//
+// This source code is licensed under the BSD-style license found in the
+// LICENSE file in the root directory of this source tree.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use super::{reindent, snake_case, split_arguments, Backend, SourceFile};
+use crate::config::{BraceStyle, Config};
+use crate::generate::Options;
+use crate::model::{Class, ClassKind, Function, Type, View};
+use crate::template::TemplateError;
+
+/// Rust structs, enums and traits. Abstract classes become a trait and a struct
+/// with their fields, sub classes contain the struct of their super class and
+/// implement its trait
+#[derive(Debug, Clone, Copy)]
+pub struct Rust;
+
+/// Body of functions
+const STUB: &str = "todo!()";
+
+/// Types which are always in scope or written with their path
+const PRELUDE: [&str; 13] = [
+    "Box",
+    "Clone",
+    "Copy",
+    "Debug",
+    "Eq",
+    "Option",
+    "PartialEq",
+    "Self",
+    "Sized",
+    "String",
+    "Vec",
+    "Any",
+    "std",
+];
+
+/// Types of `std::collections` the java collections are replaced by
+const COLLECTIONS: [&str; 4] = ["BTreeMap", "BTreeSet", "HashMap", "HashSet"];
+
+/// Strict and reserved keywords, which are written as raw identifiers
+const KEYWORDS: [&str; 47] = [
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop",
+    "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "static",
+    "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual",
+    "where", "while", "yield",
+];
+
+/// Keywords which can't be raw identifiers
+const PATH_KEYWORDS: [&str; 4] = ["crate", "self", "Self", "super"];
+
+impl Backend for Rust {
+    fn extension(&self) -> &'static str {
+        "rs"
+    }
+
+    /// Every class is a module named like the class in snake case
+    fn path(&self, class: &Class, config: &Config) -> PathBuf {
+        let mut path = PathBuf::new();
+        if let Some(package) = &config.package {
+            path.extend(package.split('.'));
+        }
+        path.push(snake_case(class.name()));
+        path.with_extension(self.extension())
+    }
+
+    fn class(&self, class: &Class) -> String {
+        module(class, &known_traits(class))
+    }
+
+    fn file(&self, class: &Class, options: &Options) -> Result<String, TemplateError> {
+        Ok(source(class, &known_traits(class), options))
+    }
+
+    /// Every type of the model which is a trait is written as a trait object
+    fn files(
+        &self,
+        classes: &[Class],
+        options: &Options,
+    ) -> Result<Vec<SourceFile>, TemplateError> {
+        let mut traits = Vec::new();
+        for class in classes.iter() {
+            trait_names(class, &mut traits);
+        }
+        Ok(classes
+            .iter()
+            .map(|class| SourceFile {
+                path: self.path(class, &options.config),
+                code: source(class, &traits, options),
+            })
+            .collect())
+    }
+}
+
+/// Code of the class, `traits` are the names of the types which are traits
+fn module(class: &Class, traits: &[String]) -> String {
+    let mut items = items(class, traits);
+    // there are no nested types, inner classes are put next to the outer class
+    items.extend(class.inner().iter().map(|c| module(c, traits)));
+    items.join("\n")
+}
+
+fn source(class: &Class, traits: &[String], options: &Options) -> String {
+    let code = module(class, traits);
+    let mut str = String::new();
+    let uses = uses(class, &code);
+    for using in uses.iter() {
+        str.push_str(&format!("use {};\n", using));
+    }
+    if !uses.is_empty() {
+        str.push('\n');
+    }
+    str.push_str(&reindent(&code, &options.config, BraceStyle::SameLine));
+    str
+}
+
+/// The items a class is translated to, each ending with a newline
+fn items(class: &Class, traits: &[String]) -> Vec<String> {
+    match class.get_kind() {
+        ClassKind::Interface => vec![declare_trait(class, traits)],
+        ClassKind::Enum => {
+            let mut items = vec![declare_enum(class)];
+            items.extend(enum_impl(class, traits));
+            items
+        }
+        ClassKind::Class if class.is_abstract() => {
+            let mut items = vec![declare_trait(class, traits)];
+            if has_base(class) {
+                items.push(declare_struct(class, &base_name(class), false, traits));
+                items.extend(inherent_impl(class, &base_name(class), &[], traits));
+            }
+            items
+        }
+        ClassKind::Class | ClassKind::Record => {
+            let is_record = class.get_kind() == ClassKind::Record;
+            let mut items = vec![declare_struct(class, class.name(), is_record, traits)];
+            let mut implemented = Vec::new();
+            let impls: Vec<String> = self::traits(class)
+                .iter()
+                .map(|t| trait_impl(class, t, &mut implemented, traits))
+                .collect();
+            items.extend(inherent_impl(class, class.name(), &implemented, traits));
+            items.extend(impls);
+            items
+        }
+    }
+}
+
+/// Struct with the instance attributes, the struct of a concrete super class or
+/// the fields of an abstract one are contained as `base`
+fn declare_struct(class: &Class, name: &str, is_record: bool, traits: &[String]) -> String {
+    let mut str = String::new();
+    if name == class.name() {
+        str.push_str(&doc(class.doc(), ""));
+    } else {
+        str.push_str(&format!(
+            "/// Fields of the implementations of [`{}`]\n",
+            class.name()
+        ));
+    }
+    if is_record {
+        str.push_str("#[derive(Debug, Clone, PartialEq)]\n");
+    }
+    str.push_str(visibility(class.visibility()));
+    str.push_str("struct ");
+    str.push_str(name);
+
+    let mut fields: Vec<String> = class
+        .instance_attributes()
+        .iter()
+        .map(|a| {
+            // record components are public
+            let view = match is_record {
+                true => "pub ",
+                false => visibility(a.visibility()),
+            };
+            format!(
+                "    {}{}{}: {},",
+                doc(a.doc(), "    "),
+                view,
+                identifier(&snake_case(a.name())),
+                owned_type(a.vartype(), traits)
+            )
+        })
+        .collect();
+    if let Some(base) = base_of(class) {
+        fields.push(format!("    base: {},", base));
+    }
+    match fields.is_empty() {
+        true => str.push_str(";\n"),
+        false => str.push_str(&format!(" {{\n{}\n}}\n", fields.join("\n"))),
+    }
+    str
+}
+
+/// Trait of an interface or abstract class, methods with a body have a default
+/// implementation
+fn declare_trait(class: &Class, traits: &[String]) -> String {
+    let mut str = doc(class.doc(), "");
+    str.push_str(visibility(class.visibility()));
+    str.push_str("trait ");
+    str.push_str(class.name());
+    let supertraits: Vec<&str> = class
+        .extends()
+        .filter(|m| m.is_abstract())
+        .into_iter()
+        .chain(class.implements().iter())
+        .map(|c| c.name())
+        .collect();
+    if !supertraits.is_empty() {
+        str.push_str(": ");
+        str.push_str(&supertraits.join(" + "));
+    }
+    str.push_str(" {\n");
+
+    let is_interface = class.get_kind() == ClassKind::Interface;
+    let mut members: Vec<String> = Vec::new();
+    // attributes of interfaces can only be read through a method
+    if is_interface {
+        members.extend(
+            class
+                .attributes()
+                .iter()
+                .filter(|a| !a.is_static())
+                .map(|a| {
+                    format!(
+                        "    {}fn {}(&self) -> {};",
+                        doc(a.doc(), "    "),
+                        identifier(&snake_case(a.name())),
+                        borrowed_type(a.vartype(), traits)
+                    )
+                }),
+        );
+    }
+    for f in class.methods().iter() {
+        let has_body = f.is_static() || !(is_interface || f.is_abstract());
+        members.push(function(f, "", class.name(), true, has_body, traits));
+    }
+    str.push_str(&members.join("\n\n"));
+    if !members.is_empty() {
+        str.push('\n');
+    }
+    str.push_str("}\n");
+    str
+}
+
+fn declare_enum(class: &Class) -> String {
+    let mut str = doc(class.doc(), "");
+    str.push_str("#[derive(Debug, Clone, Copy, PartialEq, Eq)]\n");
+    str.push_str(visibility(class.visibility()));
+    str.push_str("enum ");
+    str.push_str(class.name());
+    str.push_str(" {\n");
+    for constant in class.constants().iter() {
+        str.push_str(&format!("    {},\n", pascal_case(constant)));
+    }
+    str.push_str("}\n");
+    str
+}
+
+/// Attributes of enums become methods returning the value of the constant
+fn enum_impl(class: &Class, traits: &[String]) -> Option<String> {
+    let mut functions: Vec<String> = class
+        .attributes()
+        .iter()
+        .map(|a| {
+            let receiver = if a.is_static() { "" } else { "&self" };
+            format!(
+                "    {}{}fn {}({}) -> {} {{\n        {}\n    }}",
+                doc(a.doc(), "    "),
+                visibility(a.visibility()),
+                identifier(&snake_case(a.name())),
+                receiver,
+                owned_type(a.vartype(), traits),
+                STUB
+            )
+        })
+        .collect();
+    functions.extend(class.methods().iter().map(|f| {
+        let view = visibility(f.visibility());
+        function(f, view, class.name(), false, true, traits)
+    }));
+    if functions.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "impl {} {{\n{}\n}}\n",
+        class.name(),
+        functions.join("\n\n")
+    ))
+}
+
+/// Static attributes, constructor and the methods which don't belong to a trait
+fn inherent_impl(
+    class: &Class,
+    name: &str,
+    implemented: &[&str],
+    traits: &[String],
+) -> Option<String> {
+    let mut functions = Vec::new();
+    // static attributes become associated constants
+    for a in class.attributes().iter().filter(|a| a.is_static()) {
+        let vartype = owned_type(a.vartype(), traits);
+        functions.push(format!(
+            "    {}{}const {}: {} = {};",
+            doc(a.doc(), "    "),
+            visibility(a.visibility()),
+            snake_case(a.name()).to_uppercase(),
+            vartype,
+            default_value(&vartype)
+        ));
+    }
+    if let Some(new) = constructor(class, traits) {
+        functions.push(new);
+    }
+    // methods of abstract classes are part of their trait
+    if !class.is_abstract() {
+        for f in class
+            .methods()
+            .iter()
+            .filter(|f| !implemented.contains(&f.name()))
+        {
+            let view = visibility(f.visibility());
+            functions.push(function(f, view, name, false, true, traits));
+        }
+    }
+    if functions.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "impl {} {{\n{}\n}}\n",
+        name,
+        functions.join("\n\n")
+    ))
+}
+
+/// `new` taking the instance attributes followed by the ones of the super class,
+/// not needed for structs without fields
+fn constructor(class: &Class, traits: &[String]) -> Option<String> {
+    let new = class.get_constructor_func();
+    if new.parameters().is_empty() {
+        return None;
+    }
+    let mut fields: Vec<String> = class
+        .instance_attributes()
+        .iter()
+        .map(|a| identifier(&snake_case(a.name())))
+        .collect();
+    if let Some(base) = base_of(class) {
+        let args: Vec<String> = class
+            .extends()
+            .map(|m| m.get_constructor_func().parameters().to_vec())
+            .unwrap_or_default()
+            .iter()
+            .map(|a| identifier(&snake_case(a.name())))
+            .collect();
+        fields.push(format!("base: {}::new({})", base, args.join(", ")));
+    }
+    let parameters: Vec<String> = new
+        .parameters()
+        .iter()
+        .map(|p| {
+            format!(
+                "{}: {}",
+                identifier(&snake_case(p.name())),
+                owned_type(p.vartype(), traits)
+            )
+        })
+        .collect();
+    // short struct expressions fit on one line like rustfmt writes them
+    let body = match fields.join(", ") {
+        short if short.len() <= 40 => format!("Self {{ {} }}", short),
+        _ => {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|f| format!("            {},", f))
+                .collect();
+            format!("Self {{\n{}\n        }}", fields.join("\n"))
+        }
+    };
+    Some(format!(
+        "    {}fn new({}) -> Self {{\n        {}\n    }}",
+        visibility(class.visibility()),
+        parameters.join(", "),
+        body
+    ))
+}
+
+/// Implementation of a trait, methods declared by the class are taken over and
+/// added to `implemented`
+fn trait_impl<'a>(
+    class: &Class<'a>,
+    t: &Class,
+    implemented: &mut Vec<&'a str>,
+    traits: &[String],
+) -> String {
+    let is_interface = t.get_kind() == ClassKind::Interface;
+    let mut functions = Vec::new();
+    for m in t.methods().iter().filter(|m| !m.is_static()) {
+        match class.methods().iter().find(|f| f.overrides(m)) {
+            Some(f) => {
+                implemented.push(f.name());
+                functions.push(function(f, "", class.name(), false, true, traits));
+            }
+            // other methods have a default implementation
+            None if is_interface || m.is_abstract() => {
+                functions.push(function(m, "", class.name(), false, true, traits));
+            }
+            None => (),
+        }
+    }
+    if is_interface {
+        for a in t.attributes().iter().filter(|a| !a.is_static()) {
+            functions.push(format!(
+                "    fn {}(&self) -> {} {{\n        {}\n    }}",
+                identifier(&snake_case(a.name())),
+                borrowed_type(a.vartype(), traits),
+                STUB
+            ));
+        }
+    }
+    let mut str = format!("impl {} for {} {{\n", t.name(), class.name());
+    str.push_str(&functions.join("\n\n"));
+    if !functions.is_empty() {
+        str.push('\n');
+    }
+    str.push_str("}\n");
+    str
+}
+
+/// Function of a method, `owner` is the type `Self` refers to
+fn function(
+    f: &Function,
+    visibility: &str,
+    owner: &str,
+    in_trait: bool,
+    has_body: bool,
+    traits: &[String],
+) -> String {
+    let mut str = String::from("    ");
+    str.push_str(&doc(f.doc(), "    "));
+    str.push_str(visibility);
+    str.push_str("fn ");
+    str.push_str(&identifier(&snake_case(f.name())));
+    let mut parameters = Vec::new();
+    if !f.is_static() {
+        parameters.push("&self".to_string());
+    }
+    parameters.extend(f.parameters().iter().map(|p| {
+        format!(
+            "{}: {}",
+            identifier(&snake_case(p.name())),
+            self_type(parameter_type(p.vartype(), traits), owner)
+        )
+    }));
+    str.push_str(&format!("({})", parameters.join(", ")));
+    let return_type = self_type(owned_type(f.return_type(), traits), owner);
+    if !return_type.is_empty() {
+        str.push_str(" -> ");
+        str.push_str(&return_type);
+    }
+    // keeps traits usable as trait objects
+    if f.is_static() && in_trait {
+        str.push_str(" where Self: Sized");
+    }
+    match has_body {
+        true => str.push_str(&format!(" {{\n        {}\n    }}", STUB)),
+        false => str.push(';'),
+    }
+    str
+}
+
+/// The owner is `Self`, trait objects of it keep the name of the trait
+fn self_type(name: String, owner: &str) -> String {
+    match name == owner {
+        true => "Self".to_string(),
+        false => name,
+    }
+}
+
+/// Traits the class implements, the abstract super classes and all interfaces
+fn traits<'c, 'a>(class: &'c Class<'a>) -> Vec<&'c Class<'a>> {
+    let mut traits: Vec<&Class> = Vec::new();
+    let mut master = class.extends();
+    while let Some(m) = master.filter(|m| m.is_abstract()) {
+        traits.push(m);
+        traits.extend(m.implements().iter());
+        master = m.extends();
+    }
+    traits.extend(class.implements().iter());
+    let mut seen = Vec::new();
+    traits.retain(|t| {
+        let new = !seen.contains(&t.name());
+        seen.push(t.name());
+        new
+    });
+    traits
+}
+
+/// Names of the interfaces and abstract classes, including the inner ones
+fn trait_names(class: &Class, names: &mut Vec<String>) {
+    let is_trait = class.get_kind() == ClassKind::Interface
+        || (class.get_kind() == ClassKind::Class && class.is_abstract());
+    if is_trait && !names.iter().any(|n| n == class.name()) {
+        names.push(class.name().to_string());
+    }
+    for inner in class.inner().iter() {
+        trait_names(inner, names);
+    }
+}
+
+/// Traits which are known without the rest of the model, the class itself, its
+/// inner classes and the types it inherits from
+fn known_traits(class: &Class) -> Vec<String> {
+    let mut names = Vec::new();
+    trait_names(class, &mut names);
+    for ancestor in ancestors(class) {
+        trait_names(ancestor, &mut names);
+    }
+    let mut pending: Vec<&Class> = vec![class];
+    while let Some(c) = pending.pop() {
+        for interface in c.implements().iter() {
+            trait_names(interface, &mut names);
+            pending.push(interface);
+        }
+        pending.extend(c.extends());
+        pending.extend(c.inner().iter());
+    }
+    names
+}
+
+/// Struct contained as `base`, the super class or the fields of the abstract one
+fn base_of(class: &Class) -> Option<String> {
+    let master = class.extends()?;
+    match master.is_abstract() {
+        true => has_base(master).then(|| base_name(master)),
+        false => Some(master.name().to_string()),
+    }
+}
+
+/// Whether an abstract class needs a struct for its fields
+fn has_base(class: &Class) -> bool {
+    !class.get_constructor_func().parameters().is_empty()
+}
+
+fn base_name(class: &Class) -> String {
+    format!("{}Base", class.name())
+}
+
+/// Imports of the collections and the other classes, which are modules next to
+/// this one
+fn uses(class: &Class, code: &str) -> Vec<String> {
+    let mut declared = Vec::new();
+    declared_names(class, &mut declared);
+
+    let mut collections = Vec::new();
+    let mut modules: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    let code: String = code
+        .lines()
+        .filter(|l| !l.trim_start().starts_with("///"))
+        .collect::<Vec<&str>>()
+        .join("\n");
+    for word in code.split(|c: char| !(c.is_alphanumeric() || c == '_')) {
+        if !word.starts_with(|c: char| c.is_uppercase())
+            || PRELUDE.contains(&word)
+            || declared.iter().any(|d| d == word)
+        {
+            continue;
+        }
+        if COLLECTIONS.contains(&word) {
+            if !collections.contains(&word) {
+                collections.push(word);
+            }
+            continue;
+        }
+        // structs with the fields of abstract classes are in the module of the trait
+        let owner = ancestors(class)
+            .into_iter()
+            .find(|m| base_name(m) == word)
+            .map(|m| m.name())
+            .unwrap_or(word);
+        let names = modules.entry(snake_case(owner)).or_default();
+        if !names.contains(&word) {
+            names.push(word);
+        }
+    }
+
+    let mut uses = Vec::new();
+    collections.sort();
+    match collections.as_slice() {
+        [] => (),
+        [one] => uses.push(format!("std::collections::{}", one)),
+        many => uses.push(format!("std::collections::{{{}}}", many.join(", "))),
+    }
+    for (module, mut names) in modules {
+        names.sort();
+        match names.as_slice() {
+            [one] => uses.push(format!("super::{}::{}", module, one)),
+            many => uses.push(format!("super::{}::{{{}}}", module, many.join(", "))),
+        }
+    }
+    uses
+}
+
+fn declared_names(class: &Class, names: &mut Vec<String>) {
+    names.push(class.name().to_string());
+    if class.is_abstract() {
+        names.push(base_name(class));
+    }
+    names.extend(class.constants().iter().map(|c| pascal_case(c)));
+    names.extend(
+        class
+            .attributes()
+            .iter()
+            .filter(|a| a.is_static())
+            .map(|a| snake_case(a.name()).to_uppercase()),
+    );
+    for inner in class.inner().iter() {
+        declared_names(inner, names);
+    }
+}
+
+fn ancestors<'c, 'a>(class: &'c Class<'a>) -> Vec<&'c Class<'a>> {
+    let mut ancestors = Vec::new();
+    let mut master = class.extends();
+    while let Some(m) = master {
+        ancestors.push(m);
+        master = m.extends();
+    }
+    for inner in class.inner().iter() {
+        ancestors.extend(self::ancestors(inner));
+    }
+    ancestors
+}
+
+/// Doc comment of a note followed by the indentation of the item
+fn doc(doc: Option<&str>, indent: &str) -> String {
+    let Some(doc) = doc else {
+        return String::new();
+    };
+    let mut str = String::new();
+    for (i, line) in doc.lines().enumerate() {
+        if i > 0 {
+            str.push_str(indent);
+        }
+        str.push_str("///");
+        if !line.is_empty() {
+            str.push(' ');
+            str.push_str(line);
+        }
+        str.push('\n');
+    }
+    str.push_str(indent);
+    str
+}
+
+/// Public members are `pub`, protected and package members are visible in the crate
+fn visibility(view: View) -> &'static str {
+    match view {
+        View::Public => "pub ",
+        View::Protected | View::Normal => "pub(crate) ",
+        View::Private => "",
+    }
+}
+
+/// Names of enum variants, constants like `DARK_RED` become `DarkRed`
+fn pascal_case(name: &str) -> String {
+    if !name.contains('_') && name.chars().any(|c| c.is_lowercase()) {
+        return name.to_string();
+    }
+    name.split('_')
+        .map(|w| {
+            let w = w.to_lowercase();
+            let mut chars = w.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Keywords are used as raw identifiers, the ones which can't be get a trailing underscore
+fn identifier(name: &str) -> String {
+    if PATH_KEYWORDS.contains(&name) {
+        format!("{}_", name)
+    } else if KEYWORDS.contains(&name) {
+        format!("r#{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Rust type of a type of the diagram, java types are replaced by the Rust ones
+fn type_name(vartype: Type) -> String {
+    let name = match vartype {
+        Type::Other(s) => s.to_string(),
+        Type::Collection(c, e) => format!("{}<{}>", c, e),
+    };
+    map_type(name.trim())
+}
+
+/// Type of fields and return values, traits are boxed trait objects
+fn owned_type(vartype: Type, traits: &[String]) -> String {
+    trait_objects(&type_name(vartype), traits)
+}
+
+/// Parameters borrow a trait object, other types are passed as they are
+fn parameter_type(vartype: Type, traits: &[String]) -> String {
+    let name = type_name(vartype);
+    match traits.contains(&name) {
+        true => format!("&dyn {}", name),
+        false => trait_objects(&name, traits),
+    }
+}
+
+/// Type of getters returning a reference to a field
+fn borrowed_type(vartype: Type, traits: &[String]) -> String {
+    let name = type_name(vartype);
+    match traits.contains(&name) {
+        true => format!("&dyn {}", name),
+        false => format!("&{}", trait_objects(&name, traits)),
+    }
+}
+
+/// Replaces the names of traits in a type by `Box<dyn Trait>`
+fn trait_objects(name: &str, traits: &[String]) -> String {
+    let mut str = String::new();
+    let mut word = String::new();
+    for c in name.chars().chain(std::iter::once(' ')) {
+        if c.is_alphanumeric() || c == '_' {
+            word.push(c);
+            continue;
+        }
+        match traits.contains(&word) {
+            true => str.push_str(&format!("Box<dyn {}>", word)),
+            false => str.push_str(&word),
+        }
+        word.clear();
+        str.push(c);
+    }
+    str.pop();
+    str
+}
+
+/// Value of an associated constant, types without a constant default are left
+/// to be filled in
+fn default_value(vartype: &str) -> &'static str {
+    match vartype {
+        "i8" | "i16" | "i32" | "i64" => "0",
+        "f32" | "f64" => "0.0",
+        "bool" => "false",
+        "char" => "'\\0'",
+        "String" => "String::new()",
+        _ if vartype.starts_with("Vec<") => "Vec::new()",
+        _ if vartype.starts_with("Option<") => "None",
+        _ => STUB,
+    }
+}
+
+fn map_type(name: &str) -> String {
+    if let Some(base) = name.strip_suffix('?') {
+        return format!("Option<{}>", map_type(base));
+    }
+    if let Some(element) = name.strip_suffix("[]") {
+        return format!("Vec<{}>", map_type(element));
+    }
+    let Some((base, args)) = name.strip_suffix('>').and_then(|n| n.split_once('<')) else {
+        return map_name(name).to_string();
+    };
+    let args: Vec<String> = split_arguments(args)
+        .iter()
+        .map(|a| map_type(a.trim()))
+        .collect();
+    let base = base.trim();
+    let simple = base.rsplit('.').next().unwrap_or(base);
+    let collection = match simple {
+        "List" | "ArrayList" | "LinkedList" | "Collection" | "Iterable" => "Vec",
+        "Set" | "HashSet" => "HashSet",
+        "TreeSet" | "SortedSet" => "BTreeSet",
+        "Map" | "HashMap" => "HashMap",
+        "TreeMap" | "SortedMap" => "BTreeMap",
+        "Optional" => "Option",
+        _ => map_name(base),
+    };
+    format!("{}<{}>", collection, args.join(", "))
+}
+
+fn map_name(name: &str) -> &str {
+    match name {
+        "int" | "Integer" => "i32",
+        "long" | "Long" => "i64",
+        "short" | "Short" => "i16",
+        "byte" | "Byte" => "i8",
+        "float" | "Float" => "f32",
+        "double" | "Double" => "f64",
+        "boolean" | "Boolean" => "bool",
+        "char" | "Character" => "char",
+        "String" | "java.lang.String" => "String",
+        "Object" | "java.lang.Object" => "Box<dyn std::any::Any>",
+        // functions without a return type return the unit type
+        "void" | "Void" => "",
+        // java packages don't exist in Rust
+        _ => name.rsplit('.').next().unwrap_or(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::fixture::{self, HEROES};
+
+    fn rust(input: &str) -> Vec<String> {
+        fixture::files(input, &Rust, &Options::default())
+            .into_iter()
+            .map(|f| f.code)
+            .collect()
+    }
+
+    #[test]
+    fn abstract_classes_become_traits_with_a_base_struct() {
+        let files = rust(HEROES);
+
+        assert_eq!(
+            files[0],
+            "use super::monster::Monster;
+
+pub trait Held {
+    fn angreifen(&self, ziel: &dyn Monster) -> bool;
+
+    fn get_name(&self) -> String {
+        todo!()
+    }
+}
+
+/// Fields of the implementations of [`Held`]
+pub struct HeldBase {
+    pub(crate) name: String,
+    punkte: Vec<i32>,
+}
+
+impl HeldBase {
+    pub fn new(name: String, punkte: Vec<i32>) -> Self {
+        Self { name, punkte }
+    }
+}
+"
+        );
+        assert_eq!(
+            files[1],
+            "use super::held::{Held, HeldBase};
+use super::monster::Monster;
+use super::waffe::Waffe;
+
+/// Kämpft
+/// mit Waffen
+pub struct Krieger {
+    waffe: Option<Waffe>,
+    base: HeldBase,
+}
+
+impl Krieger {
+    pub const ANZAHL: i32 = 0;
+
+    pub fn new(waffe: Option<Waffe>, name: String, punkte: Vec<i32>) -> Self {
+        Self { waffe, base: HeldBase::new(name, punkte) }
+    }
+}
+
+impl Held for Krieger {
+    fn angreifen(&self, ziel: &dyn Monster) -> bool {
+        todo!()
+    }
+}
+
+impl Monster for Krieger {
+    fn brüllen(&self) {
+        todo!()
+    }
+}
+"
+        );
+        assert_eq!(files[2], "pub trait Monster {\n    fn brüllen(&self);\n}\n");
+        assert_eq!(
+            files[3],
+            "#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Farbe {
+    Rot,
+    DunkelGruen,
+}
+"
+        );
+    }
+
+    #[test]
+    fn traits_are_used_as_trait_objects() {
+        let input = "@startuml
+class Zoo {
+ - {static} zaehler : int
+ - tiere : List<Tier>
+ - waerter : Waerter
+ + fuettern(tier : Tier, zoo : Zoo) : Tier
+}
+interface Tier {
+ + freund : Tier
+}
+abstract class Waerter {
+ + {abstract} pflegen() : void
+}
+@enduml";
+        let files = rust(input);
+
+        assert_eq!(
+            files[0],
+            "use super::tier::Tier;
+use super::waerter::Waerter;
+
+pub struct Zoo {
+    tiere: Vec<Box<dyn Tier>>,
+    waerter: Box<dyn Waerter>,
+}
+
+impl Zoo {
+    const ZAEHLER: i32 = 0;
+
+    pub fn new(tiere: Vec<Box<dyn Tier>>, waerter: Box<dyn Waerter>) -> Self {
+        Self { tiere, waerter }
+    }
+
+    pub fn fuettern(&self, tier: &dyn Tier, zoo: Self) -> Box<dyn Tier> {
+        todo!()
+    }
+}
+"
+        );
+        assert_eq!(
+            files[1],
+            "pub trait Tier {\n    fn freund(&self) -> &dyn Tier;\n}\n"
+        );
+    }
+
+    #[test]
+    fn keywords_are_escaped() {
+        let files = rust(
+            "@startuml
+class Vertrag {
+ - type : int
+ - super : Vertrag
+ + try(crate : int, self : int, final : int) : void
+}
+@enduml",
+        );
+
+        assert!(files[0].contains("    r#type: i32,\n    super_: Vertrag,\n"));
+        assert!(files[0].contains("        Self { r#type, super_ }\n"));
+        assert!(
+            files[0].contains("    pub fn r#try(&self, crate_: i32, self_: i32, r#final: i32) {\n")
+        );
+    }
+
+    #[test]
+    fn java_types_are_replaced() {
+        assert_eq!(map_type("java.util.List<Integer>"), "Vec<i32>");
+        assert_eq!(
+            map_type("Map<String, Set<Long?>>"),
+            "HashMap<String, HashSet<Option<i64>>>"
+        );
+        assert_eq!(map_type("double[]"), "Vec<f64>");
+        assert_eq!(snake_case("getName"), "get_name");
+    }
+}
@@ -0,0 +1,611 @@
+// Copyright (c) 2023, Oskar Ohlenmacher
+// All rights reserved
+//
+// This source code is licensed under the BSD-style license found in the
+// LICENSE file in the root directory of this source tree.
+
+use std::collections::BTreeSet;
+
+use log::warn;
+
+use super::{doc_comment, indent, reindent, split_arguments, Backend, SourceFile};
+use crate::config::BraceStyle;
+use crate::generate::Options;
+use crate::model::{Attribute, Class, ClassKind, Function, Type, View};
+use crate::template::TemplateError;
+
+/// TypeScript classes, interfaces and string enums, inner classes are put into a
+/// namespace with the name of the outer class
+#[derive(Debug, Clone, Copy)]
+pub struct TypeScript;
+
+/// Body of methods which aren't abstract
+const STUB: &str = "throw new Error(\"Not implemented\");";
+
+/// Types which don't have to be imported
+const GLOBAL_TYPES: [&str; 7] = ["Array", "Date", "Error", "Map", "Promise", "Record", "Set"];
+
+/// Reserved words, which can name properties and methods but not parameters
+const KEYWORDS: [&str; 46] = [
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+];
+
+impl Backend for TypeScript {
+    fn extension(&self) -> &'static str {
+        "ts"
+    }
+
+    fn class(&self, class: &Class) -> String {
+        let mut str = declaration(class);
+
+        // classes and namespaces with the same name are merged
+        if !class.inner().is_empty() {
+            str.push('\n');
+            str.push_str(&format!("export namespace {} {{\n", class.name()));
+            let inner: Vec<String> = class
+                .inner()
+                .iter()
+                .map(|c| indent(&self.class(c)).trim_end().to_string())
+                .collect();
+            str.push_str(&inner.join("\n\n"));
+            str.push_str("\n}\n");
+        }
+        str
+    }
+
+    fn file(&self, class: &Class, options: &Options) -> Result<String, TemplateError> {
+        Ok(source(class, &known_classes(class), options))
+    }
+
+    /// Every top level class of the model can be imported from its file
+    fn files(
+        &self,
+        classes: &[Class],
+        options: &Options,
+    ) -> Result<Vec<SourceFile>, TemplateError> {
+        let model: Vec<String> = classes.iter().map(|c| c.name().to_string()).collect();
+        Ok(classes
+            .iter()
+            .map(|class| SourceFile {
+                path: self.path(class, &options.config),
+                code: source(class, &model, options),
+            })
+            .collect())
+    }
+}
+
+/// The file of the class, the types of the model which it uses are imported
+fn source(class: &Class, model: &[String], options: &Options) -> String {
+    let mut str = String::new();
+    let mut names = BTreeSet::new();
+    referenced_types(class, &mut names);
+    let mut declared = Vec::new();
+    declared_names(class, &mut declared);
+    for name in names
+        .iter()
+        .filter(|n| model.contains(n) && !declared.contains(n))
+    {
+        str.push_str(&format!("import {{ {} }} from \"./{}\";\n", name, name));
+    }
+    if !str.is_empty() {
+        str.push('\n');
+    }
+    str.push_str(&reindent(
+        &TypeScript.class(class),
+        &options.config,
+        BraceStyle::SameLine,
+    ));
+    str
+}
+
+/// Classes which are known to be in the model without the rest of it, the types
+/// the class inherits from
+fn known_classes(class: &Class) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut pending: Vec<&Class> = vec![class];
+    while let Some(c) = pending.pop() {
+        for master in c.extends().into_iter().chain(c.implements().iter()) {
+            names.push(master.name().to_string());
+            pending.push(master);
+        }
+        pending.extend(c.inner().iter());
+    }
+    names
+}
+
+fn declaration(class: &Class) -> String {
+    let mut str = String::new();
+    if let Some(doc) = class.doc() {
+        str.push_str(&doc_comment(doc, &[], ""));
+    }
+    str.push_str("export ");
+    match class.get_kind() {
+        ClassKind::Interface => push_interface(class, &mut str),
+        ClassKind::Enum => push_enum(class, &mut str),
+        ClassKind::Class | ClassKind::Record => push_class(class, &mut str),
+    }
+    str
+}
+
+fn push_class(class: &Class, str: &mut String) {
+    if class.is_abstract() {
+        str.push_str("abstract ");
+    }
+    str.push_str("class ");
+    str.push_str(class.name());
+    if let Some(master) = class.extends() {
+        str.push_str(" extends ");
+        str.push_str(master.name());
+    }
+    if !class.implements().is_empty() {
+        let names: Vec<&str> = class.implements().iter().map(|c| c.name()).collect();
+        str.push_str(" implements ");
+        str.push_str(&names.join(", "));
+    }
+    str.push_str(" {\n");
+
+    let mut sections = Vec::new();
+    // records are immutable
+    let is_record = class.get_kind() == ClassKind::Record;
+    let fields: Vec<String> = class
+        .attributes()
+        .iter()
+        .map(|a| field(*a, is_record))
+        .collect();
+    if !fields.is_empty() {
+        sections.push(fields.join("\n"));
+    }
+    sections.push(constructor(class));
+    let methods: Vec<String> = class
+        .methods()
+        .iter()
+        .map(|f| method(f, f.visibility(), f.is_abstract()))
+        .chain(
+            class
+                .inherited_methods()
+                .iter()
+                .map(|f| method(f, View::Public, false)),
+        )
+        .collect();
+    if !methods.is_empty() {
+        sections.push(methods.join("\n\n"));
+    }
+    str.push_str(&sections.join("\n\n"));
+    str.push_str("\n}\n");
+}
+
+fn push_interface(class: &Class, str: &mut String) {
+    str.push_str("interface ");
+    str.push_str(class.name());
+    if !class.implements().is_empty() {
+        let names: Vec<&str> = class.implements().iter().map(|c| c.name()).collect();
+        str.push_str(" extends ");
+        str.push_str(&names.join(", "));
+    }
+    str.push_str(" {\n");
+    // interfaces can't have static members
+    for a in class.attributes().iter().filter(|a| a.is_static()) {
+        warn!(
+            "TypeScript interfaces can't have static members, {}.{} is left out",
+            class.name(),
+            a.name()
+        );
+    }
+    for f in class.methods().iter().filter(|f| f.is_static()) {
+        warn!(
+            "TypeScript interfaces can't have static members, {}.{}() is left out",
+            class.name(),
+            f.name()
+        );
+    }
+    let mut members: Vec<String> = class
+        .attributes()
+        .iter()
+        .filter(|a| !a.is_static())
+        .map(|a| {
+            let readonly = if a.is_final() { "readonly " } else { "" };
+            format!(
+                "    {}{}{}: {};",
+                doc(a.doc(), &[]),
+                readonly,
+                a.name(),
+                type_name(a.vartype())
+            )
+        })
+        .collect();
+    members.extend(
+        class
+            .methods()
+            .iter()
+            .filter(|f| !f.is_static())
+            .map(|f| format!("    {}{};", doc(f.doc(), f.parameters()), signature(f))),
+    );
+    str.push_str(&members.join("\n"));
+    if !members.is_empty() {
+        str.push('\n');
+    }
+    str.push_str("}\n");
+}
+
+/// String enums, so the values stay readable in JSON
+fn push_enum(class: &Class, str: &mut String) {
+    str.push_str("enum ");
+    str.push_str(class.name());
+    str.push_str(" {\n");
+    for constant in class.constants().iter() {
+        str.push_str(&format!("    {} = \"{}\",\n", constant, constant));
+    }
+    str.push_str("}\n");
+}
+
+fn field(a: Attribute, readonly: bool) -> String {
+    let mut str = String::from("    ");
+    str.push_str(&doc(a.doc(), &[]));
+    str.push_str(visibility(a.visibility()));
+    if a.is_static() {
+        str.push_str("static ");
+    }
+    if a.is_final() || readonly {
+        str.push_str("readonly ");
+    }
+    str.push_str(a.name());
+    str.push_str(": ");
+    str.push_str(&type_name(a.vartype()));
+    str.push(';');
+    str
+}
+
+fn constructor(class: &Class) -> String {
+    let parameters: Vec<String> = class
+        .get_constructor_func()
+        .parameters()
+        .iter()
+        .map(|p| format!("{}: {}", parameter(p.name()), type_name(p.vartype())))
+        .collect();
+    let mut str = format!("    constructor({}) {{\n", parameters.join(", "));
+    if let Some(master) = class.extends() {
+        let args: Vec<String> = master
+            .instance_attributes()
+            .iter()
+            .map(|a| parameter(a.name()))
+            .collect();
+        str.push_str(&format!("        super({});\n", args.join(", ")));
+    }
+    for a in class.instance_attributes().iter() {
+        str.push_str(&format!(
+            "        this.{} = {};\n",
+            a.name(),
+            parameter(a.name())
+        ));
+    }
+    str.push_str("    }");
+    str
+}
+
+fn method(f: &Function, view: View, is_abstract: bool) -> String {
+    let mut str = String::from("    ");
+    str.push_str(&doc(f.doc(), f.parameters()));
+    str.push_str(visibility(view));
+    if f.is_static() {
+        str.push_str("static ");
+    }
+    if is_abstract {
+        str.push_str("abstract ");
+    }
+    str.push_str(&signature(f));
+    match is_abstract {
+        true => str.push(';'),
+        false => str.push_str(&format!(" {{\n        {}\n    }}", STUB)),
+    }
+    str
+}
+
+/// Name, parameters and return type of the method
+fn signature(f: &Function) -> String {
+    let parameters: Vec<String> = f
+        .parameters()
+        .iter()
+        .map(|p| format!("{}: {}", parameter(p.name()), type_name(p.vartype())))
+        .collect();
+    let return_type = match type_name(f.return_type()).as_str() {
+        "" => "void".to_string(),
+        t => t.to_string(),
+    };
+    format!("{}({}): {}", f.name(), parameters.join(", "), return_type)
+}
+
+/// Name of a parameter, reserved words get a trailing underscore
+fn parameter(name: &str) -> String {
+    match KEYWORDS.contains(&name) {
+        true => format!("{}_", name),
+        false => name.to_string(),
+    }
+}
+
+/// Documentation of a member followed by the indentation of the member
+fn doc(doc: Option<&str>, parameters: &[Attribute]) -> String {
+    doc.map(|d| doc_comment(d, parameters, "    "))
+        .unwrap_or_default()
+}
+
+/// Members without visibility are public
+fn visibility(view: View) -> &'static str {
+    match view {
+        View::Normal => "",
+        View::Public => "public ",
+        View::Protected => "protected ",
+        View::Private => "private ",
+    }
+}
+
+/// Every type the class uses, which has to be imported if it isn't declared in the file
+fn referenced_types(class: &Class, names: &mut BTreeSet<String>) {
+    let mut types = Vec::new();
+    for a in class.attributes().iter() {
+        types.push(type_name(a.vartype()));
+    }
+    for f in class
+        .methods()
+        .iter()
+        .chain(class.inherited_methods().iter())
+    {
+        types.push(type_name(f.return_type()));
+        types.extend(f.parameters().iter().map(|p| type_name(p.vartype())));
+    }
+    types.extend(class.extends().map(|c| c.name().to_string()));
+    types.extend(class.implements().iter().map(|c| c.name().to_string()));
+    for t in types.iter() {
+        for word in t.split(|c: char| !(c.is_alphanumeric() || c == '_')) {
+            let is_type = word.starts_with(|c: char| c.is_uppercase());
+            if is_type && !GLOBAL_TYPES.contains(&word) {
+                names.insert(word.to_string());
+            }
+        }
+    }
+    for inner in class.inner().iter() {
+        referenced_types(inner, names);
+    }
+}
+
+fn declared_names(class: &Class, names: &mut Vec<String>) {
+    names.push(class.name().to_string());
+    for inner in class.inner().iter() {
+        declared_names(inner, names);
+    }
+}
+
+/// TypeScript type of a type of the diagram, java types are replaced by the
+/// TypeScript ones
+fn type_name(vartype: Type) -> String {
+    let name = match vartype {
+        Type::Other(s) => s.to_string(),
+        Type::Collection(c, e) => format!("{}<{}>", c, e),
+    };
+    map_type(name.trim())
+}
+
+fn map_type(name: &str) -> String {
+    if let Some(base) = name.strip_suffix('?') {
+        return format!("{} | null", map_type(base));
+    }
+    if let Some(element) = name.strip_suffix("[]") {
+        return array(&map_type(element));
+    }
+    let Some((base, args)) = name.strip_suffix('>').and_then(|n| n.split_once('<')) else {
+        return map_name(name).to_string();
+    };
+    let args: Vec<String> = split_arguments(args)
+        .iter()
+        .map(|a| map_type(a.trim()))
+        .collect();
+    let base = base.trim();
+    let simple = base.rsplit('.').next().unwrap_or(base);
+    match (simple, args.as_slice()) {
+        ("List" | "ArrayList" | "LinkedList" | "Collection" | "Iterable", [element]) => {
+            array(element)
+        }
+        ("Optional", [element]) => format!("{} | null", element),
+        ("Set" | "HashSet" | "TreeSet", _) => format!("Set<{}>", args.join(", ")),
+        ("Map" | "HashMap" | "TreeMap", _) => format!("Map<{}>", args.join(", ")),
+        _ => format!("{}<{}>", map_name(base), args.join(", ")),
+    }
+}
+
+fn array(element: &str) -> String {
+    match element.contains(' ') {
+        true => format!("({})[]", element),
+        false => format!("{}[]", element),
+    }
+}
+
+fn map_name(name: &str) -> &str {
+    match name {
+        "int"
+        | "long"
+        | "short"
+        | "byte"
+        | "float"
+        | "double"
+        | "Integer"
+        | "Long"
+        | "Short"
+        | "Byte"
+        | "Float"
+        | "Double"
+        | "BigDecimal"
+        | "java.math.BigDecimal" => "number",
+        "String" | "char" | "Character" | "java.lang.String" | "UUID" | "java.util.UUID" => {
+            "string"
+        }
+        "boolean" | "Boolean" => "boolean",
+        "void" | "Void" => "void",
+        "Object" | "java.lang.Object" => "unknown",
+        "Date"
+        | "java.util.Date"
+        | "LocalDate"
+        | "java.time.LocalDate"
+        | "LocalDateTime"
+        | "java.time.LocalDateTime"
+        | "Instant"
+        | "java.time.Instant" => "Date",
+        // other packages don't exist in TypeScript
+        _ => name.rsplit('.').next().unwrap_or(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::fixture::{self, HEROES};
+
+    #[test]
+    fn classes_interfaces_and_enums_are_exported() {
+        let files: Vec<String> = fixture::files(HEROES, &TypeScript, &Options::default())
+            .into_iter()
+            .map(|f| f.code)
+            .collect();
+
+        assert_eq!(
+            files[0],
+            "import { Monster } from \"./Monster\";
+
+export abstract class Held {
+    protected name: string;
+    private punkte: number[];
+
+    constructor(name: string, punkte: number[]) {
+        this.name = name;
+        this.punkte = punkte;
+    }
+
+    public abstract angreifen(ziel: Monster): boolean;
+
+    public getName(): string {
+        throw new Error(\"Not implemented\");
+    }
+}
+"
+        );
+        // Waffe isn't in the model, so it isn't imported
+        assert!(files[1].starts_with(
+            "import { Held } from \"./Held\";
+import { Monster } from \"./Monster\";
+
+"
+        ));
+        assert!(files[1].contains(
+            "export class Krieger extends Held implements Monster {
+    private waffe: Waffe | null;
+    public static anzahl: number;
+
+    constructor(waffe: Waffe | null, name: string, punkte: number[]) {
+        super(name, punkte);
+        this.waffe = waffe;
+    }
+"
+        ));
+        assert!(files[1].contains("\n    public brüllen(): void {\n"));
+        assert_eq!(
+            files[2],
+            "export interface Monster {\n    brüllen(): void;\n}\n"
+        );
+        assert_eq!(
+            files[3],
+            "export enum Farbe {\n    ROT = \"ROT\",\n    DUNKEL_GRUEN = \"DUNKEL_GRUEN\",\n}\n"
+        );
+    }
+
+    #[test]
+    fn reserved_parameters_are_renamed() {
+        let input = "@startuml
+class Vertrag {
+ - in : int
+ + {static} anzahl : int
+ + delete(new : Vertrag, datum : java.time.Clock) : void
+}
+class Anhang
+interface Archiv {
+ + {static} leeren() : void
+ + ablegen(vertrag : Vertrag) : void
+}
+Vertrag <|-- Anhang
+@enduml";
+        let files: Vec<String> = fixture::files(input, &TypeScript, &Options::default())
+            .into_iter()
+            .map(|f| f.code)
+            .collect();
+
+        assert!(files[0].starts_with("export class Vertrag {\n    private in: number;\n"));
+        assert!(
+            files[0].contains("    constructor(in_: number) {\n        this.in = in_;\n    }\n")
+        );
+        assert!(files[0].contains("    public delete(new_: Vertrag, datum: Clock): void {\n"));
+        assert!(files[1].contains("    constructor(in_: number) {\n        super(in_);\n"));
+        // static members of interfaces are left out
+        assert_eq!(
+            files[2],
+            "import { Vertrag } from \"./Vertrag\";
+
+export interface Archiv {
+    ablegen(vertrag: Vertrag): void;
+}
+"
+        );
+    }
+
+    #[test]
+    fn java_types_are_replaced() {
+        assert_eq!(map_type("java.util.List<Integer>"), "number[]");
+        assert_eq!(
+            map_type("Map<String, List<Date?>>"),
+            "Map<string, (Date | null)[]>"
+        );
+        assert_eq!(map_type("java.time.LocalDate"), "Date");
+        assert_eq!(map_type("com.example.Held"), "Held");
+    }
+}
@@ -0,0 +1,544 @@
+// Copyright (c) 2023, Oskar Ohlenmacher
+// All rights reserved
+//
+// This source code is licensed under the BSD-style license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Terminal browser for the model of the diagrams. The classes borrow from the
+// tokens, so everything shown is copied into entries whenever the diagrams are
+// read again.
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::generate::{self, Options};
+use crate::model::{Class, ClassView, FieldView, MethodView};
+use crate::watch;
+
+/// Warnings and errors logged while the diagrams are read
+static DIAGNOSTICS: Mutex<Vec<(Level, String)>> = Mutex::new(Vec::new());
+
+struct DiagnosticsLogger;
+
+impl Log for DiagnosticsLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Warn
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            if let Ok(mut diagnostics) = DIAGNOSTICS.lock() {
+                diagnostics.push((record.level(), record.args().to_string()));
+            }
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Collects warnings and errors for the diagnostics pane instead of writing them to
+/// the terminal
+pub fn init_logger() -> Result<(), log::SetLoggerError> {
+    log::set_logger(&DiagnosticsLogger)?;
+    log::set_max_level(LevelFilter::Warn);
+    Ok(())
+}
+
+fn take_diagnostics() -> Vec<(Level, String)> {
+    DIAGNOSTICS
+        .lock()
+        .map(|mut d| std::mem::take(&mut *d))
+        .unwrap_or_default()
+}
+
+/// Everything shown of one class
+#[derive(Debug, Clone)]
+struct Entry {
+    name: String,
+    package: String,
+    kind: &'static str,
+    fields: Vec<String>,
+    methods: Vec<String>,
+    supertypes: Vec<String>,
+    subtypes: Vec<String>,
+    associations: Vec<String>,
+    path: PathBuf,
+    code: String,
+}
+
+/// Package of classes without one
+const DEFAULT_PACKAGE: &str = "(default package)";
+
+fn entries(classes: &[Class], options: &Options) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    for class in classes.iter() {
+        push_entries(class, &class.view(), None, options, &mut entries);
+    }
+
+    // subtypes and associations need every class
+    let names: Vec<String> = entries.iter().map(|e| e.name.clone()).collect();
+    for i in 0..entries.len() {
+        let name = &names[i];
+        entries[i].subtypes = entries
+            .iter()
+            .filter(|e| e.supertypes.contains(name))
+            .map(|e| e.name.clone())
+            .collect();
+    }
+    for entry in entries.iter_mut() {
+        entry.associations = names
+            .iter()
+            .filter(|n| **n != entry.name)
+            .filter(|n| {
+                let simple = n.rsplit('.').next().unwrap_or(n);
+                entry
+                    .fields
+                    .iter()
+                    .chain(entry.methods.iter())
+                    .any(|member| mentions(member, simple))
+            })
+            .cloned()
+            .collect();
+    }
+    entries
+}
+
+/// Adds the class and its inner classes
+fn push_entries(
+    class: &Class,
+    view: &ClassView,
+    outer: Option<&str>,
+    options: &Options,
+    entries: &mut Vec<Entry>,
+) {
+    let name = match outer {
+        Some(outer) => format!("{}.{}", outer, view.name),
+        None => view.name.clone(),
+    };
+    let package = match (&options.config.package, view.name.rsplit_once('.')) {
+        (Some(package), _) => package.clone(),
+        (None, Some((package, _))) if package.starts_with(char::is_lowercase) => {
+            package.to_string()
+        }
+        _ => DEFAULT_PACKAGE.to_string(),
+    };
+    let code = generate::class_file(class, options).unwrap_or_else(|e| e.to_string());
+    entries.push(Entry {
+        name: name.clone(),
+        package,
+        kind: view.kind,
+        fields: view.fields.iter().map(field).collect(),
+        methods: view.methods.iter().map(method).collect(),
+        supertypes: view
+            .extends
+            .iter()
+            .chain(view.implements.iter())
+            .cloned()
+            .collect(),
+        subtypes: Vec::new(),
+        associations: Vec::new(),
+        path: options.backend().path(class, &options.config),
+        code,
+    });
+    for (inner, inner_view) in class.inner().iter().zip(view.inner.iter()) {
+        push_entries(inner, inner_view, Some(&name), options, entries);
+    }
+}
+
+fn symbol(visibility: &str) -> &str {
+    match visibility {
+        "public" => "+",
+        "protected" => "#",
+        "private" => "-",
+        _ => "~",
+    }
+}
+
+fn field(f: &FieldView) -> String {
+    format!("{} {} : {}", symbol(f.visibility), f.name, f.vartype)
+}
+
+fn method(m: &MethodView) -> String {
+    let parameters: Vec<String> = m
+        .parameters
+        .iter()
+        .map(|p| format!("{} : {}", p.name, p.vartype))
+        .collect();
+    format!(
+        "{} {}({}) : {}",
+        symbol(m.visibility),
+        m.name,
+        parameters.join(", "),
+        m.return_type
+    )
+}
+
+/// Whether the member uses the type name as a whole word
+fn mentions(member: &str, name: &str) -> bool {
+    member
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .any(|w| w == name)
+}
+
+/// One line of the tree
+#[derive(Debug, Clone, PartialEq)]
+struct Row {
+    /// Unique path of the node, used to remember which nodes are expanded
+    key: String,
+    depth: usize,
+    label: String,
+    expandable: bool,
+    /// Index of the entry the row belongs to
+    entry: Option<usize>,
+}
+
+/// Visible rows of packages, classes, sections and members
+fn rows(entries: &[Entry], expanded: &HashSet<String>) -> Vec<Row> {
+    let mut packages: Vec<&str> = entries.iter().map(|e| e.package.as_str()).collect();
+    packages.sort();
+    packages.dedup();
+
+    let mut rows = Vec::new();
+    for package in packages {
+        let key = format!("p:{}", package);
+        let is_expanded = expanded.contains(&key);
+        rows.push(Row {
+            label: format!("{} {}", marker(is_expanded), package),
+            key,
+            depth: 0,
+            expandable: true,
+            entry: None,
+        });
+        if !is_expanded {
+            continue;
+        }
+        for (i, entry) in entries.iter().enumerate() {
+            if entry.package != package {
+                continue;
+            }
+            let key = format!("c:{}", entry.name);
+            let is_expanded = expanded.contains(&key);
+            rows.push(Row {
+                label: format!("{} {} {}", marker(is_expanded), entry.kind, entry.name),
+                key: key.clone(),
+                depth: 1,
+                expandable: true,
+                entry: Some(i),
+            });
+            if !is_expanded {
+                continue;
+            }
+            let sections = [
+                ("Fields", &entry.fields),
+                ("Methods", &entry.methods),
+                ("Supertypes", &entry.supertypes),
+                ("Subtypes", &entry.subtypes),
+                ("Associations", &entry.associations),
+            ];
+            for (title, items) in sections {
+                if items.is_empty() {
+                    continue;
+                }
+                let key = format!("{}/{}", key, title);
+                let is_expanded = expanded.contains(&key);
+                rows.push(Row {
+                    label: format!("{} {} ({})", marker(is_expanded), title, items.len()),
+                    key: key.clone(),
+                    depth: 2,
+                    expandable: true,
+                    entry: Some(i),
+                });
+                if !is_expanded {
+                    continue;
+                }
+                for (j, item) in items.iter().enumerate() {
+                    rows.push(Row {
+                        key: format!("{}/{}", key, j),
+                        depth: 3,
+                        label: item.clone(),
+                        expandable: false,
+                        entry: Some(i),
+                    });
+                }
+            }
+        }
+    }
+    rows
+}
+
+fn marker(is_expanded: bool) -> &'static str {
+    match is_expanded {
+        true => "▾",
+        false => "▸",
+    }
+}
+
+struct Browser<'a> {
+    inputs: &'a [String],
+    options: &'a Options,
+    entries: Vec<Entry>,
+    diagnostics: Vec<(Level, String)>,
+    /// Every file which was read, to notice changes
+    read: Vec<PathBuf>,
+    expanded: HashSet<String>,
+    list: ListState,
+    code_scroll: u16,
+}
+
+impl<'a> Browser<'a> {
+    fn new(inputs: &'a [String], options: &'a Options) -> Self {
+        let mut browser = Browser {
+            inputs,
+            options,
+            entries: Vec::new(),
+            diagnostics: Vec::new(),
+            read: Vec::new(),
+            expanded: HashSet::new(),
+            list: ListState::default().with_selected(Some(0)),
+            code_scroll: 0,
+        };
+        browser.reload();
+        // packages are open at the start
+        for entry in browser.entries.iter() {
+            browser.expanded.insert(format!("p:{}", entry.package));
+        }
+        browser
+    }
+
+    /// Reads the diagrams again, the last model is kept if they contain errors
+    fn reload(&mut self) {
+        let selected = self.selected_row().map(|r| r.key);
+        let result = (|| -> Result<Vec<Entry>, Box<dyn Error>> {
+            let (files, read) = generate::read_inputs(self.inputs)?;
+            // included files are watched even if the diagrams contain errors
+            self.read = read;
+            let idents = generate::merge_diagrams(&files, self.options)?;
+            let ast = generate::parse(&idents)?;
+            let classes = generate::build_classes(ast, self.options)?;
+            Ok(entries(&classes, self.options))
+        })();
+
+        self.diagnostics = take_diagnostics();
+        match result {
+            Ok(entries) => self.entries = entries,
+            Err(e) => {
+                let message = e.to_string();
+                if !self.diagnostics.iter().any(|(_, m)| *m == message) {
+                    self.diagnostics.push((Level::Error, message));
+                }
+            }
+        }
+
+        // keep the selection on the same node
+        let rows = rows(&self.entries, &self.expanded);
+        let index = selected
+            .and_then(|key| rows.iter().position(|r| r.key == key))
+            .unwrap_or(0);
+        self.list
+            .select(Some(index.min(rows.len().saturating_sub(1))));
+    }
+
+    fn selected_row(&self) -> Option<Row> {
+        let rows = rows(&self.entries, &self.expanded);
+        self.list.selected().and_then(|i| rows.get(i).cloned())
+    }
+
+    /// Returns false if the browser should be closed
+    fn handle_key(&mut self, code: KeyCode) -> bool {
+        let rows = rows(&self.entries, &self.expanded);
+        let selected = self.list.selected().unwrap_or(0);
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Down | KeyCode::Char('j') if selected + 1 < rows.len() => {
+                self.list.select(Some(selected + 1));
+                self.code_scroll = 0;
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.list.select(Some(selected.saturating_sub(1)));
+                self.code_scroll = 0;
+            }
+            KeyCode::Right | KeyCode::Char('l') | KeyCode::Enter => {
+                if let Some(row) = rows.get(selected).filter(|r| r.expandable) {
+                    if !self.expanded.remove(&row.key) {
+                        self.expanded.insert(row.key.clone());
+                    }
+                }
+            }
+            KeyCode::Left | KeyCode::Char('h') => {
+                if let Some(row) = rows.get(selected) {
+                    if !self.expanded.remove(&row.key) {
+                        // collapse the parent instead
+                        let parent = rows[..selected].iter().rposition(|r| r.depth < row.depth);
+                        if let Some(parent) = parent {
+                            self.expanded.remove(&rows[parent].key);
+                            self.list.select(Some(parent));
+                        }
+                    }
+                }
+            }
+            KeyCode::PageDown => self.code_scroll = self.code_scroll.saturating_add(10),
+            KeyCode::PageUp => self.code_scroll = self.code_scroll.saturating_sub(10),
+            KeyCode::Char('r') => self.reload(),
+            _ => (),
+        }
+        true
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let diagnostics_height = self.diagnostics.len().clamp(1, 6) as u16 + 2;
+        let [main, diagnostics, help] = Layout::vertical([
+            Constraint::Min(3),
+            Constraint::Length(diagnostics_height),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [tree, code] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(main);
+
+        let rows = rows(&self.entries, &self.expanded);
+        let items: Vec<ListItem> = rows
+            .iter()
+            .map(|r| ListItem::new(format!("{}{}", "  ".repeat(r.depth), r.label)))
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title(" Model "))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, tree, &mut self.list);
+
+        let entry = self
+            .list
+            .selected()
+            .and_then(|i| rows.get(i))
+            .and_then(|r| r.entry)
+            .and_then(|i| self.entries.get(i));
+        let (title, code_text) = match entry {
+            Some(entry) => (format!(" {} ", entry.path.display()), entry.code.as_str()),
+            None => (" Code ".to_string(), ""),
+        };
+        let code_pane = Paragraph::new(code_text)
+            .block(Block::bordered().title(title))
+            .scroll((self.code_scroll, 0));
+        frame.render_widget(code_pane, code);
+
+        let lines: Vec<Line> = match self.diagnostics.is_empty() {
+            true => vec![Line::styled(
+                "No problems",
+                Style::default().fg(Color::Green),
+            )],
+            false => self
+                .diagnostics
+                .iter()
+                .map(|(level, message)| {
+                    let color = match level {
+                        Level::Error => Color::Red,
+                        _ => Color::Yellow,
+                    };
+                    Line::styled(
+                        format!("{}: {}", level, message),
+                        Style::default().fg(color),
+                    )
+                })
+                .collect(),
+        };
+        let diagnostics_pane =
+            Paragraph::new(lines).block(Block::bordered().title(" Diagnostics "));
+        frame.render_widget(diagnostics_pane, diagnostics);
+
+        frame.render_widget(
+            Paragraph::new("↑↓ move  → open  ← close  PgUp/PgDn scroll code  r reload  q quit"),
+            help,
+        );
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), Box<dyn Error>> {
+        let mut before = watch::snapshot(self.inputs, &self.read);
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if event::poll(watch::POLL_INTERVAL)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press && !self.handle_key(key.code) {
+                        return Ok(());
+                    }
+                }
+            } else if watch::snapshot(self.inputs, &self.read) != before {
+                thread::sleep(watch::SETTLE_TIME);
+                self.reload();
+                before = watch::snapshot(self.inputs, &self.read);
+            }
+        }
+    }
+}
+
+/// Shows the model of the diagrams until the user quits, the model is read again
+/// whenever a diagram changes
+pub fn browse(inputs: &[String], options: &Options) -> Result<(), Box<dyn Error>> {
+    if inputs.iter().any(|i| i == generate::STDIN) {
+        return Err(Box::new(generate::CustomError::InputNotFound(
+            "stdin can't be browsed".to_string(),
+        )));
+    }
+    let mut browser = Browser::new(inputs, options);
+    let mut terminal = ratatui::init();
+    let result = browser.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tree_shows_members_and_relations() {
+        let input = "@startuml
+class Held {
+ - waffe : Waffe
+ + angreifen(ziel : Monster) : void
+}
+class Krieger
+class Monster
+class Waffe
+Held <|-- Krieger
+@enduml";
+        let tokens = crate::tokenize(input).unwrap();
+        let options = Options::default();
+        let classes = crate::parse(&tokens, &options).unwrap();
+        let entries = entries(&classes, &options);
+
+        let held = &entries[0];
+        assert_eq!(held.subtypes, vec!["Krieger"]);
+        assert_eq!(held.associations, vec!["Monster", "Waffe"]);
+        assert_eq!(entries[1].supertypes, vec!["Held"]);
+
+        let mut expanded = HashSet::from([format!("p:{}", DEFAULT_PACKAGE)]);
+        assert_eq!(rows(&entries, &expanded).len(), 5);
+        expanded.insert("c:Held".to_string());
+        expanded.insert("c:Held/Methods".to_string());
+        let labels: Vec<String> = rows(&entries, &expanded)
+            .into_iter()
+            .map(|r| r.label)
+            .collect();
+        assert_eq!(
+            labels[1..7],
+            [
+                "▾ class Held",
+                "▸ Fields (1)",
+                "▾ Methods (1)",
+                "+ angreifen(ziel : Monster) : void",
+                "▸ Subtypes (1)",
+                "▸ Associations (2)",
+            ]
+        );
+    }
+}
@@ -0,0 +1,247 @@
+// Copyright (c) 2023, Oskar Ohlenmacher
+// All rights reserved
+//
+// This source code is licensed under the BSD-style license found in the
+// LICENSE file in the root directory of this source tree.
+
+use crate::backend::Target;
+use crate::model::{Accessors, Type};
+use log::debug;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the configuration file which is searched from the input upward
+pub const CONFIG_FILE: &str = "plantuml2java.toml";
+
+/// Options changing how the generated files look
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// Language the classes are generated in
+    pub target: Target,
+    /// Spaces per indentation level
+    pub indent: usize,
+    /// Indent with tabs instead of spaces
+    pub tabs: bool,
+    /// Where opening braces are placed, the usual style of the target if not set
+    pub brace_style: Option<BraceStyle>,
+    /// Package of all generated classes
+    pub package: Option<String>,
+    /// Type used for attributes with a multiplicity like `String[*]`, the list
+    /// of the target if not set
+    pub collection: Option<String>,
+    pub accessors: Accessors,
+    pub overwrite: Overwrite,
+    /// Types of the diagram replaced by java types, only used for the JVM targets
+    pub types: BTreeMap<String, String>,
+    /// Directory with templates replacing the built-in ones
+    pub templates: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            target: Target::Java,
+            indent: 4,
+            tabs: false,
+            brace_style: None,
+            package: None,
+            collection: None,
+            accessors: Accessors::None,
+            overwrite: Overwrite::Always,
+            types: BTreeMap::new(),
+            templates: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum BraceStyle {
+    /// Opening braces end the line of the declaration
+    #[default]
+    SameLine,
+    /// Opening braces are on their own line
+    NextLine,
+}
+
+/// What to do with files which already exist
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Overwrite {
+    #[default]
+    Always,
+    Never,
+}
+
+impl Config {
+    /// Reads the configuration file
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| ConfigError::Read(path.to_path_buf(), e.to_string()))?;
+        let mut config: Config = toml::from_str(&content)
+            .map_err(|e| ConfigError::Parse(path.to_path_buf(), e.to_string()))?;
+        // paths are relative to the configuration file
+        if let (Some(templates), Some(dir)) = (&config.templates, path.parent()) {
+            config.templates = Some(dir.join(templates));
+        }
+        Ok(config)
+    }
+
+    /// Searches the configuration file from the directory of the input upward,
+    /// the default configuration is used if there is none
+    pub fn discover(input: &Path) -> Result<Self, ConfigError> {
+        let start = match input.is_dir() {
+            true => input.to_path_buf(),
+            false => input.parent().map(Path::to_path_buf).unwrap_or_default(),
+        };
+        let start = fs::canonicalize(&start).unwrap_or(start);
+        for dir in start.ancestors() {
+            let path = dir.join(CONFIG_FILE);
+            if path.is_file() {
+                debug!("Using configuration {}", path.display());
+                return Config::load(&path);
+            }
+        }
+        Ok(Config::default())
+    }
+
+    /// Type of attributes with a multiplicity, every backend knows `List`
+    pub fn collection(&self) -> &str {
+        match (&self.collection, self.target) {
+            (Some(collection), _) => collection,
+            (None, Target::Java) => "java.util.List",
+            (None, _) => "List",
+        }
+    }
+
+    /// Whether the types are replaced by the configured ones, they are java types
+    /// which only the JVM targets can import
+    pub fn maps_types(&self) -> bool {
+        matches!(self.target, Target::Java | Target::Kotlin)
+    }
+
+    /// Replaces types by their mapping and multiplicities by the collection type
+    pub fn map_type<'a>(&'a self, vartype: Type<'a>) -> Type<'a> {
+        let Type::Other(name) = vartype else {
+            return vartype;
+        };
+        let element = ["[*]", "[0..*]", "[1..*]"]
+            .iter()
+            .find_map(|suffix| name.strip_suffix(suffix));
+        let Some(element) = element else {
+            return Type::Other(self.map_name(name));
+        };
+        let element = self.map_name(element.trim());
+        // only java can't put primitive types into collections
+        let element = match self.target {
+            Target::Java => boxed(element),
+            _ => element,
+        };
+        Type::Collection(self.collection(), element)
+    }
+
+    fn map_name<'a>(&'a self, name: &'a str) -> &'a str {
+        match self.maps_types() {
+            true => self.types.get(name).map(String::as_str).unwrap_or(name),
+            false => name,
+        }
+    }
+}
+
+/// Collections can't contain primitive types
+fn boxed(name: &str) -> &str {
+    match name {
+        "int" => "Integer",
+        "char" => "Character",
+        "boolean" => "Boolean",
+        "byte" => "Byte",
+        "short" => "Short",
+        "long" => "Long",
+        "float" => "Float",
+        "double" => "Double",
+        _ => name,
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, String),
+    Parse(PathBuf, String),
+    InvalidTypeMapping(String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read(path, e) => write!(f, "Couldn't read {}: {}", path.display(), e),
+            Self::Parse(path, e) => write!(f, "Invalid configuration {}: {}", path.display(), e),
+            Self::InvalidTypeMapping(s) => {
+                write!(f, "Type mapping \"{}\" has to look like Name=java.Type", s)
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+/// Parses a type mapping given on the command line
+pub fn parse_type_mapping(s: &str) -> Result<(String, String), ConfigError> {
+    match s.split_once('=') {
+        Some((from, to)) if !from.trim().is_empty() && !to.trim().is_empty() => {
+            Ok((from.trim().to_string(), to.trim().to_string()))
+        }
+        _ => Err(ConfigError::InvalidTypeMapping(s.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configuration_is_read_from_toml() {
+        let config: Config = toml::from_str(
+            r#"
+            target = "java"
+            indent = 2
+            brace-style = "next-line"
+            package = "com.example"
+            accessors = "getters"
+
+            [types]
+            Date = "java.time.LocalDate"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.target, Target::Java);
+        assert_eq!(config.indent, 2);
+        assert_eq!(config.brace_style, Some(BraceStyle::NextLine));
+        assert_eq!(config.accessors, Accessors::Getters);
+        assert_eq!(config.collection(), "java.util.List");
+        assert_eq!(config.types["Date"], "java.time.LocalDate");
+        assert!(toml::from_str::<Config>("indentation = 2").is_err());
+    }
+
+    #[test]
+    fn java_types_are_only_used_for_java() {
+        let mut config = Config::default();
+        config
+            .types
+            .insert("Date".to_string(), "java.time.LocalDate".to_string());
+        let mapped = |config: &Config, name: &str| config.map_type(Type::Other(name)).to_string();
+        assert_eq!(mapped(&config, "int[*]"), "java.util.List<Integer>");
+        assert_eq!(mapped(&config, "Date"), "java.time.LocalDate");
+
+        config.target = Target::Rust;
+        assert_eq!(mapped(&config, "int[*]"), "List<int>");
+        assert_eq!(mapped(&config, "Date"), "Date");
+
+        config.collection = Some("Set".to_string());
+        assert_eq!(mapped(&config, "int[1..*]"), "Set<int>");
+    }
+}
@@ -0,0 +1,706 @@
+// Copyright (c) 2023, Oskar Ohlenmacher
+// All rights reserved
+//
+// This source code is licensed under the BSD-style license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Compares the classes of two versions of a diagram and reports the changes
+//! which break code using the old version.
+
+use std::error::Error;
+
+use serde::Serialize;
+
+use crate::generate::{self, Options};
+use crate::model::{Attribute, Class, ClassKind, Function, Sealing, Span, View};
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum DiffFormat {
+    /// One line for every change
+    Text,
+    /// The changes as a JSON array
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// A difference between the old and new version of a class or member
+#[derive(Debug, Serialize)]
+pub struct Change<'a> {
+    pub kind: ChangeKind,
+    /// Name of the class, inner classes are written as `Outer.Inner`
+    pub class: String,
+    /// Attribute or method like `area(double) : double`
+    pub member: Option<String>,
+    pub description: String,
+    /// Whether code written against the old version could break
+    pub breaking: bool,
+    /// Declaration of the new version, of the old one if it was removed
+    pub span: Option<Span<'a>>,
+}
+
+impl std::fmt::Display for Change<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self.kind {
+            ChangeKind::Added => '+',
+            ChangeKind::Removed => '-',
+            ChangeKind::Changed => '~',
+        };
+        write!(f, "{} {}", symbol, self.class)?;
+        if let Some(member) = &self.member {
+            write!(f, "::{}", member)?;
+        }
+        write!(f, " - {}", self.description)?;
+        if self.breaking {
+            write!(f, " [breaking]")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares the diagrams of both inputs, types are compared as they are written.
+/// Returns the report and whether a change is breaking
+pub fn diff_inputs(
+    old: &str,
+    new: &str,
+    format: DiffFormat,
+    options: &Options,
+) -> Result<(String, bool), Box<dyn Error>> {
+    let (old_files, _) = generate::read_inputs(&[old.to_string()])?;
+    let (new_files, _) = generate::read_inputs(&[new.to_string()])?;
+    let old_idents = generate::merge_diagrams(&old_files, options)?;
+    let new_idents = generate::merge_diagrams(&new_files, options)?;
+    let old_classes = generate::resolve(generate::parse(&old_idents)?, options.sealed)?;
+    let new_classes = generate::resolve(generate::parse(&new_idents)?, options.sealed)?;
+
+    let changes = diff(&old_classes, &new_classes);
+    let breaking = changes.iter().filter(|c| c.breaking).count();
+    let report = match format {
+        DiffFormat::Json => serde_json::to_string_pretty(&changes)? + "\n",
+        DiffFormat::Text => {
+            let mut report: String = changes.iter().map(|c| format!("{}\n", c)).collect();
+            report.push_str(&format!(
+                "{} changes, {} breaking\n",
+                changes.len(),
+                breaking
+            ));
+            report
+        }
+    };
+    Ok((report, breaking > 0))
+}
+
+/// Changes from the old to the new classes, classes are matched by name and
+/// methods by name and parameter types
+pub fn diff<'a>(old: &[Class<'a>], new: &[Class<'a>]) -> Vec<Change<'a>> {
+    let old = flatten(old, "");
+    let new = flatten(new, "");
+    let mut changes = Vec::new();
+    for (name, old_class) in old.iter() {
+        match new.iter().find(|(n, _)| n == name) {
+            Some((_, new_class)) => diff_class(name, old_class, new_class, &mut changes),
+            None => changes.push(Change {
+                kind: ChangeKind::Removed,
+                class: name.clone(),
+                member: None,
+                description: format!("{} removed", old_class.get_kind().as_str()),
+                breaking: is_visible(old_class.visibility()),
+                span: old_class.span(),
+            }),
+        }
+    }
+    for (name, new_class) in new.iter() {
+        if !old.iter().any(|(n, _)| n == name) {
+            changes.push(Change {
+                kind: ChangeKind::Added,
+                class: name.clone(),
+                member: None,
+                description: format!("{} added", new_class.get_kind().as_str()),
+                breaking: false,
+                span: new_class.span(),
+            });
+        }
+    }
+    changes
+}
+
+/// Every class with its name, inner classes follow the outer one
+fn flatten<'c, 'a>(classes: &'c [Class<'a>], outer: &str) -> Vec<(String, &'c Class<'a>)> {
+    let mut all = Vec::new();
+    for class in classes.iter() {
+        let name = match outer {
+            "" => class.name().to_string(),
+            outer => format!("{}.{}", outer, class.name()),
+        };
+        let inner = flatten(class.inner(), &name);
+        all.push((name, class));
+        all.extend(inner);
+    }
+    all
+}
+
+fn diff_class<'a>(name: &str, old: &Class<'a>, new: &Class<'a>, changes: &mut Vec<Change<'a>>) {
+    let mut change = |description: String, breaking: bool| {
+        changes.push(Change {
+            kind: ChangeKind::Changed,
+            class: name.to_string(),
+            member: None,
+            description,
+            breaking,
+            span: new.span(),
+        })
+    };
+    if old.get_kind() != new.get_kind() {
+        let description = format!(
+            "{} became {}",
+            old.get_kind().as_str(),
+            new.get_kind().as_str()
+        );
+        change(description, true);
+    }
+    if let Some(description) = visibility_change(old.visibility(), new.visibility()) {
+        change(description, is_narrowed(old.visibility(), new.visibility()));
+    }
+    if old.is_abstract() != new.is_abstract() && old.get_kind() == new.get_kind() {
+        match new.is_abstract() {
+            true => change("became abstract".to_string(), true),
+            false => change("is no longer abstract".to_string(), false),
+        }
+    }
+    if old.is_static() != new.is_static() {
+        match new.is_static() {
+            true => change("became static".to_string(), true),
+            false => change("is no longer static".to_string(), true),
+        }
+    }
+    if old.get_sealing() != new.get_sealing() {
+        let description = format!(
+            "became {}",
+            match new.get_sealing() {
+                Sealing::Open => "open",
+                sealing => sealing.as_str(),
+            }
+        );
+        // subclasses outside of the diagram can't extend it anymore
+        change(description, new.get_sealing() != Sealing::Open);
+    }
+
+    let old_master = old.extends().map(|m| m.name());
+    let new_master = new.extends().map(|m| m.name());
+    match (old_master, new_master) {
+        (Some(o), Some(n)) if o != n => change(format!("extends {} instead of {}", n, o), true),
+        (Some(o), None) => change(format!("no longer extends {}", o), true),
+        (None, Some(n)) => change(format!("extends {}", n), false),
+        _ => (),
+    }
+    for interface in old.implements().iter() {
+        if !new
+            .implements()
+            .iter()
+            .any(|i| i.name() == interface.name())
+        {
+            change(format!("no longer implements {}", interface.name()), true);
+        }
+    }
+    for interface in new.implements().iter() {
+        if !old
+            .implements()
+            .iter()
+            .any(|i| i.name() == interface.name())
+        {
+            change(format!("implements {}", interface.name()), false);
+        }
+    }
+    for constant in old.constants().iter() {
+        if !new.constants().contains(constant) {
+            change(format!("constant {} removed", constant), true);
+        }
+    }
+    for constant in new.constants().iter() {
+        if !old.constants().contains(constant) {
+            change(format!("constant {} added", constant), false);
+        }
+    }
+
+    diff_attributes(name, old, new, changes);
+    diff_methods(name, old, new, changes);
+}
+
+fn diff_attributes<'a>(
+    name: &str,
+    old: &Class<'a>,
+    new: &Class<'a>,
+    changes: &mut Vec<Change<'a>>,
+) {
+    let in_interface = old.get_kind() == ClassKind::Interface;
+    for a in old.attributes().iter() {
+        let visible = is_visible(a.visibility()) || in_interface;
+        let Some(b) = new.attributes().iter().find(|b| b.name() == a.name()) else {
+            changes.push(member_change(
+                name,
+                ChangeKind::Removed,
+                &attribute(a),
+                "removed".to_string(),
+                visible,
+                a.span(),
+            ));
+            continue;
+        };
+        let mut descriptions = Vec::new();
+        if a.vartype().to_string() != b.vartype().to_string() {
+            descriptions.push((
+                format!("type changed from {} to {}", a.vartype(), b.vartype()),
+                visible,
+            ));
+        }
+        if let Some(description) = visibility_change(a.visibility(), b.visibility()) {
+            descriptions.push((
+                description,
+                visible && is_narrowed(a.visibility(), b.visibility()),
+            ));
+        }
+        if a.is_static() != b.is_static() {
+            let description = match b.is_static() {
+                true => "became static",
+                false => "is no longer static",
+            };
+            descriptions.push((description.to_string(), visible));
+        }
+        for (description, breaking) in descriptions {
+            changes.push(member_change(
+                name,
+                ChangeKind::Changed,
+                &attribute(b),
+                description,
+                breaking,
+                b.span(),
+            ));
+        }
+    }
+    for b in new.attributes().iter() {
+        if !old.attributes().iter().any(|a| a.name() == b.name()) {
+            changes.push(member_change(
+                name,
+                ChangeKind::Added,
+                &attribute(b),
+                "added".to_string(),
+                false,
+                b.span(),
+            ));
+        }
+    }
+}
+
+fn diff_methods<'a>(name: &str, old: &Class<'a>, new: &Class<'a>, changes: &mut Vec<Change<'a>>) {
+    let old_interface = old.get_kind() == ClassKind::Interface;
+    let new_interface = new.get_kind() == ClassKind::Interface;
+    let mut removed: Vec<&Function> = Vec::new();
+    let mut added: Vec<&Function> = new
+        .methods()
+        .iter()
+        .filter(|b| !old.methods().iter().any(|a| a.overrides(b)))
+        .collect();
+    for a in old.methods().iter() {
+        match new.methods().iter().find(|b| a.overrides(b)) {
+            Some(b) => diff_method(name, a, b, old_interface, new_interface, changes),
+            None => removed.push(a),
+        }
+    }
+
+    for a in removed {
+        let visible = is_visible(a.visibility()) || old_interface;
+        // the only new overload of the same name is taken as the changed method
+        let same_name: Vec<usize> = added
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.name() == a.name())
+            .map(|(i, _)| i)
+            .collect();
+        let overloads = old
+            .methods()
+            .iter()
+            .filter(|m| m.name() == a.name())
+            .count();
+        if let ([i], 1) = (same_name.as_slice(), overloads) {
+            let b = added.remove(*i);
+            let description = format!(
+                "parameters changed from ({}) to ({})",
+                parameter_types(a),
+                parameter_types(b)
+            );
+            changes.push(member_change(
+                name,
+                ChangeKind::Changed,
+                &method(b),
+                description,
+                visible,
+                b.span(),
+            ));
+            diff_method(name, a, b, old_interface, new_interface, changes);
+            continue;
+        }
+        changes.push(member_change(
+            name,
+            ChangeKind::Removed,
+            &method(a),
+            "removed".to_string(),
+            visible,
+            a.span(),
+        ));
+    }
+    for b in added {
+        // classes using the old version don't implement it
+        let breaking = must_implement(b, new_interface);
+        let description = match breaking {
+            true => "abstract method added",
+            false => "added",
+        };
+        changes.push(member_change(
+            name,
+            ChangeKind::Added,
+            &method(b),
+            description.to_string(),
+            breaking,
+            b.span(),
+        ));
+    }
+}
+
+/// Compares two versions of a method with the same name
+fn diff_method<'a>(
+    name: &str,
+    a: &Function<'a>,
+    b: &Function<'a>,
+    old_interface: bool,
+    new_interface: bool,
+    changes: &mut Vec<Change<'a>>,
+) {
+    let visible = is_visible(a.visibility()) || old_interface;
+    let mut descriptions = Vec::new();
+    if a.return_type().to_string() != b.return_type().to_string() {
+        descriptions.push((
+            format!(
+                "return type changed from {} to {}",
+                type_name(&a.return_type().to_string()),
+                type_name(&b.return_type().to_string())
+            ),
+            visible,
+        ));
+    }
+    let (old_view, new_view) = match (old_interface, new_interface) {
+        (true, true) => (View::Public, View::Public),
+        _ => (a.visibility(), b.visibility()),
+    };
+    if let Some(description) = visibility_change(old_view, new_view) {
+        descriptions.push((description, visible && is_narrowed(old_view, new_view)));
+    }
+    if a.is_static() != b.is_static() {
+        let description = match b.is_static() {
+            true => "became static",
+            false => "is no longer static",
+        };
+        descriptions.push((description.to_string(), visible));
+    }
+    let (old_abstract, new_abstract) = (
+        must_implement(a, old_interface),
+        must_implement(b, new_interface),
+    );
+    if old_abstract != new_abstract {
+        match new_abstract {
+            true => descriptions.push(("became abstract".to_string(), true)),
+            false => descriptions.push(("is no longer abstract".to_string(), false)),
+        }
+    }
+    for (description, breaking) in descriptions {
+        changes.push(member_change(
+            name,
+            ChangeKind::Changed,
+            &method(b),
+            description,
+            breaking,
+            b.span(),
+        ));
+    }
+}
+
+fn member_change<'a>(
+    class: &str,
+    kind: ChangeKind,
+    member: &str,
+    description: String,
+    breaking: bool,
+    span: Option<Span<'a>>,
+) -> Change<'a> {
+    Change {
+        kind,
+        class: class.to_string(),
+        member: Some(member.to_string()),
+        description,
+        breaking,
+        span,
+    }
+}
+
+/// Interface methods have to be implemented unless they are static
+fn must_implement(f: &Function, in_interface: bool) -> bool {
+    f.is_abstract() || (in_interface && !f.is_static())
+}
+
+fn attribute(a: &Attribute) -> String {
+    format!("{} : {}", a.name(), a.vartype())
+}
+
+fn method(f: &Function) -> String {
+    format!(
+        "{}({}) : {}",
+        f.name(),
+        parameter_types(f),
+        type_name(&f.return_type().to_string())
+    )
+}
+
+fn parameter_types(f: &Function) -> String {
+    let types: Vec<String> = f
+        .parameters()
+        .iter()
+        .map(|p| p.vartype().to_string())
+        .collect();
+    types.join(", ")
+}
+
+/// Methods without a return type return nothing
+fn type_name(name: &str) -> &str {
+    match name {
+        "" => "void",
+        name => name,
+    }
+}
+
+/// Whether a member with the visibility can be used outside of its package
+fn is_visible(view: View) -> bool {
+    matches!(view, View::Public | View::Protected)
+}
+
+/// Private members are the least and public ones the most visible
+fn rank(view: View) -> u8 {
+    match view {
+        View::Private => 0,
+        View::Normal => 1,
+        View::Protected => 2,
+        View::Public => 3,
+    }
+}
+
+fn is_narrowed(old: View, new: View) -> bool {
+    rank(new) < rank(old)
+}
+
+fn visibility_change(old: View, new: View) -> Option<String> {
+    let name = |view: View| match view {
+        View::Normal => "package private",
+        view => view.as_str(),
+    };
+    (rank(old) != rank(new)).then(|| {
+        format!(
+            "visibility {} from {} to {}",
+            match is_narrowed(old, new) {
+                true => "narrowed",
+                false => "widened",
+            },
+            name(old),
+            name(new)
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer;
+
+    const OLD: &str = "@startuml
+abstract class Shape {
+ # id : long
+ + {abstract} area() : double
+}
+class Circle {
+ + r : double
+ - cache : double
+ + area() : double
+ + scale(by : double) : Circle
+ + move(p : Point) : void
+ + move(x : int, y : int) : void
+}
+interface Named
+class Point
+enum Color {
+ RED
+ GREEN
+}
+Shape <|-- Circle
+Named <|.. Circle
+@enduml";
+
+    const NEW: &str = "@startuml
+abstract class Shape {
+ + id : long
+ + {abstract} area() : double
+ + {abstract} perimeter() : double
+}
+class Circle {
+ - r : double
+ - cache : float
+ + area() : float
+ + scale(by : float) : Circle
+ + move(x : int, y : int) : void
+ + describe() : String
+}
+interface Named
+enum Color {
+ RED
+ BLUE
+}
+class Square
+Shape <|-- Circle
+@enduml";
+
+    #[test]
+    fn breaking_changes_are_flagged() {
+        assert_eq!(
+            changes(OLD, NEW),
+            [
+                "~ Shape::id : long - visibility widened from protected to public",
+                "+ Shape::perimeter() : double - abstract method added [breaking]",
+                "~ Circle - no longer implements Named [breaking]",
+                "~ Circle::r : double - visibility narrowed from public to private [breaking]",
+                "~ Circle::cache : float - type changed from double to float",
+                "~ Circle::area() : float - return type changed from double to float [breaking]",
+                "~ Circle::scale(float) : Circle - parameters changed from (double) to (float) [breaking]",
+                "- Circle::move(Point) : void - removed [breaking]",
+                "+ Circle::describe() : String - added",
+                "- Point - class removed [breaking]",
+                "~ Color - constant GREEN removed [breaking]",
+                "~ Color - constant BLUE added",
+                "+ Square - class added",
+            ]
+        );
+        assert!(changes(NEW, NEW).is_empty());
+    }
+
+    /// The changes between two diagrams as they are printed
+    fn changes(old: &str, new: &str) -> Vec<String> {
+        let old = tokenizer::get_identifiers_from_str(old.to_string()).unwrap();
+        let new = tokenizer::get_identifiers_from_str(new.to_string()).unwrap();
+        let old = generate::resolve(generate::parse(&old).unwrap(), false).unwrap();
+        let new = generate::resolve(generate::parse(&new).unwrap(), false).unwrap();
+        diff(&old, &new).iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn overloads_are_matched_by_parameter_types() {
+        let old = "@startuml
+class Circle {
+ + move(p : Point) : void
+ + move(x : int, y : int) : int
+ + scale(by : double) : void
+}
+@enduml";
+        let new = "@startuml
+class Circle {
+ + move(x : int, y : int) : long
+ + move(x : double, y : double) : void
+ + scale(by : float) : void
+ + scale(x : float, y : float) : void
+}
+@enduml";
+        // with several overloads a changed one is removed and added
+        assert_eq!(
+            changes(old, new),
+            [
+                "~ Circle::move(int, int) : long - return type changed from int to long [breaking]",
+                "- Circle::move(Point) : void - removed [breaking]",
+                "- Circle::scale(double) : void - removed [breaking]",
+                "+ Circle::move(double, double) : void - added",
+                "+ Circle::scale(float) : void - added",
+                "+ Circle::scale(float, float) : void - added",
+            ]
+        );
+    }
+
+    #[test]
+    fn inner_classes_are_named_after_the_outer_class() {
+        let old = "@startuml
+class Outer
+class Inner {
+ + value : int
+}
+class Gone
+Outer +-- Inner
+Outer +-- Gone
+@enduml";
+        let new = "@startuml
+class Outer
+class Inner {
+ + value : long
+ + {static} of(value : long) : Inner
+}
+class Gone
+class Added
+Outer +-- Inner
+Outer +-- Added
+@enduml";
+        assert_eq!(
+            changes(old, new),
+            [
+                "~ Outer.Inner::value : long - type changed from int to long [breaking]",
+                "+ Outer.Inner::of(long) : Inner - added",
+                "- Outer.Gone - class removed [breaking]",
+                "+ Outer.Added - class added",
+                "+ Gone - class added",
+            ]
+        );
+    }
+
+    #[test]
+    fn changes_are_written_as_json() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (old, new) = (tmp.path().join("old.puml"), tmp.path().join("new.puml"));
+        std::fs::write(&old, OLD).unwrap();
+        std::fs::write(&new, NEW).unwrap();
+        let (old, new) = (old.to_str().unwrap(), new.to_str().unwrap());
+        let options = Options::default();
+
+        let (report, breaking) = diff_inputs(old, new, DiffFormat::Json, &options).unwrap();
+        assert!(breaking);
+        let json: serde_json::Value = serde_json::from_str(&report).unwrap();
+        let changes = json.as_array().unwrap();
+        assert_eq!(changes.len(), 13);
+        assert_eq!(changes[0]["kind"], "changed");
+        assert_eq!(changes[0]["class"], "Shape");
+        assert_eq!(changes[0]["member"], "id : long");
+        assert_eq!(
+            changes[0]["description"],
+            "visibility widened from protected to public"
+        );
+        assert_eq!(changes[0]["breaking"], false);
+        assert_eq!(changes[12]["kind"], "added");
+        assert_eq!(changes[12]["member"], serde_json::Value::Null);
+
+        // nothing changed, so nothing breaks
+        let (report, breaking) = diff_inputs(old, old, DiffFormat::Json, &options).unwrap();
+        assert!(!breaking);
+        assert_eq!(report, "[]\n");
+        let (report, breaking) = diff_inputs(old, old, DiffFormat::Text, &options).unwrap();
+        assert!(!breaking);
+        assert_eq!(report, "0 changes, 0 breaking\n");
+        let (report, breaking) = diff_inputs(old, new, DiffFormat::Text, &options).unwrap();
+        assert!(breaking);
+        assert!(report.ends_with("13 changes, 8 breaking\n"));
+    }
+}
@@ -0,0 +1,674 @@
+// Copyright (c) 2023, Oskar Ohlenmacher
+// All rights reserved
+//
+// This source code is licensed under the BSD-style license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Rewrites PlantUML diagrams in one layout. Members and relations are written
+//! the same way everywhere, comments, notes and other directives stay as they are.
+
+use std::error::Error;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+
+use crate::backend::split_arguments;
+use crate::generate::{self, STDIN};
+use crate::{mermaid, plantuml, tokenizer, xmi};
+
+#[derive(Debug)]
+pub enum FormatError {
+    Io(PathBuf, io::Error),
+    /// File and why it can't be parsed
+    Parse(PathBuf, String),
+    /// The formatted file would describe other classes than the file itself
+    Changed(PathBuf),
+}
+
+impl std::fmt::Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(path, e) => write!(f, "Can't access {}: {}", path.display(), e),
+            Self::Parse(path, e) => write!(f, "Can't parse {}: {}", path.display(), e),
+            Self::Changed(path) => write!(
+                f,
+                "Formatting would change the classes of {}, it is left as it is",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl Error for FormatError {}
+
+/// Formats every PlantUML file of the inputs in place, with check the files are
+/// only compared. Returns the files which are or would be changed, stdin is
+/// written to stdout.
+pub fn format_files(inputs: &[String], check: bool) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut changed = Vec::new();
+    for file in generate::collect_inputs(inputs)? {
+        if mermaid::is_mermaid_file(&file) || xmi::is_xmi_file(&file) {
+            warn!(
+                "Only PlantUML diagrams are formatted, skipped {}",
+                file.display()
+            );
+            continue;
+        }
+        let is_stdin = file.as_os_str() == STDIN;
+        let mut text = String::new();
+        let read = match is_stdin {
+            true => io::stdin().read_to_string(&mut text).map(|_| ()),
+            false => fs::read_to_string(&file).map(|t| text = t),
+        };
+        read.map_err(|e| FormatError::Io(file.clone(), e))?;
+
+        let formatted = format(&text);
+        if formatted == text {
+            info!("{} is formatted", file.display());
+        } else {
+            verify(&file, &text, &formatted)?;
+            changed.push(file.clone());
+        }
+        if is_stdin && !check {
+            print!("{}", formatted);
+        } else if !is_stdin && !check && formatted != text {
+            fs::write(&file, &formatted).map_err(|e| FormatError::Io(file.clone(), e))?;
+            info!("Formatted {}", file.display());
+        }
+    }
+    Ok(changed)
+}
+
+/// Makes sure that the formatted text has the same classes as the original
+fn verify(file: &Path, original: &str, formatted: &str) -> Result<(), FormatError> {
+    let write = |text: &str| -> Result<String, Box<dyn Error>> {
+        let tokens = tokenizer::get_identifiers_from_str(text.to_string())?;
+        let classes = generate::resolve(generate::parse(&tokens)?, false)?;
+        Ok(plantuml::write(&classes))
+    };
+    let original =
+        write(original).map_err(|e| FormatError::Parse(file.to_path_buf(), e.to_string()))?;
+    match write(formatted) {
+        Ok(formatted) if formatted == original => Ok(()),
+        _ => Err(FormatError::Changed(file.to_path_buf())),
+    }
+}
+
+/// Keywords which declare a class
+const DECLARATIONS: [&str; 7] = [
+    "class",
+    "abstract",
+    "interface",
+    "enum",
+    "record",
+    "annotation",
+    "entity",
+];
+
+/// Keywords of blocks which contain other declarations
+const GROUPS: [&str; 7] = [
+    "package",
+    "namespace",
+    "together",
+    "rectangle",
+    "frame",
+    "folder",
+    "node",
+];
+
+/// Keywords which can start a text spanning several lines up to `end keyword`
+const TEXTS: [&str; 7] = [
+    "note", "rnote", "hnote", "legend", "header", "footer", "title",
+];
+
+/// Writes the text in the canonical layout:
+/// - members are indented by two spaces and their names line up
+/// - `:` and the visibility are followed by one space and `:` is preceded by one
+/// - `Child --|> Master` is written as `Master <|-- Child`
+/// - relations follow the declarations at the end of each diagram
+pub fn format(text: &str) -> String {
+    let mut formatter = Formatter::default();
+    for line in text.lines() {
+        formatter.line(line);
+    }
+    formatter.finish()
+}
+
+#[derive(Default)]
+struct Formatter {
+    lines: Vec<String>,
+    /// Relations of the current diagram which are written before its end
+    relations: Vec<String>,
+    /// Lines of the class whose body is read
+    body: Option<Vec<String>>,
+    /// End of a block which is kept as it is, like `'/` or `endnote`
+    verbatim: Option<String>,
+    /// Open groups like packages
+    depth: usize,
+}
+
+impl Formatter {
+    fn push(&mut self, line: String) {
+        // one empty line is enough
+        if line.is_empty()
+            && self
+                .lines
+                .last()
+                .is_none_or(|l| l.is_empty() || l.ends_with('{'))
+        {
+            return;
+        }
+        self.lines.push(line);
+    }
+
+    fn indented(&mut self, line: &str) {
+        let line = format!("{}{}", "  ".repeat(self.depth), line);
+        self.push(line);
+    }
+
+    fn line(&mut self, line: &str) {
+        let line = line.trim_end();
+        let trimmed = line.trim();
+
+        if let Some(end) = &self.verbatim {
+            if ends_block(trimmed, end) {
+                self.verbatim = None;
+            }
+            match &mut self.body {
+                Some(body) => body.push(line.to_string()),
+                None => self.lines.push(line.to_string()),
+            }
+            return;
+        }
+        if trimmed.starts_with("/'") && !trimmed.contains("'/") {
+            self.verbatim = Some("'/".to_string());
+        }
+        if let Some(mut body) = self.body.take() {
+            if trimmed != "}" {
+                body.push(line.to_string());
+                self.body = Some(body);
+                return;
+            }
+            for member in format_body(&body) {
+                match member.is_empty() {
+                    true => self.push(member),
+                    false => self.indented(&format!("  {}", member)),
+                }
+            }
+            self.indented("}");
+            return;
+        }
+        if self.verbatim.is_some() {
+            self.lines.push(line.to_string());
+            return;
+        }
+
+        let first = trimmed
+            .split(|c: char| c.is_whitespace() || c == '{')
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        if trimmed.is_empty() {
+            self.push(String::new());
+        } else if trimmed.starts_with('\'') || trimmed.starts_with("/'") {
+            self.indented(trimmed);
+        } else if trimmed.starts_with("@startuml") {
+            self.relations.clear();
+            self.push(trimmed.to_string());
+        } else if trimmed.starts_with("@enduml") {
+            self.flush_relations();
+            self.push(trimmed.to_string());
+        } else if DECLARATIONS.contains(&first.as_str()) {
+            let header = header(trimmed);
+            let opens = header.ends_with('{');
+            self.indented(&header);
+            if opens {
+                self.body = Some(Vec::new());
+            }
+        } else if GROUPS.contains(&first.as_str()) && trimmed.ends_with('{') {
+            self.indented(&header(trimmed));
+            self.depth += 1;
+        } else if trimmed == "}" && self.depth > 0 {
+            self.depth -= 1;
+            self.indented("}");
+        } else if let Some(relation) = Relation::parse(trimmed) {
+            // relations inside of groups could declare classes there
+            match self.depth {
+                0 => self.relations.push(relation.to_string()),
+                _ => self.indented(&relation.to_string()),
+            }
+        } else {
+            if TEXTS.contains(&first.as_str()) && is_multiline(&first, trimmed) {
+                self.verbatim = Some(format!("end{}", first));
+            }
+            self.indented(trimmed);
+        }
+    }
+
+    fn flush_relations(&mut self) {
+        while self.lines.last().is_some_and(|l| l.is_empty()) {
+            self.lines.pop();
+        }
+        self.lines.append(&mut self.relations);
+    }
+
+    fn finish(mut self) -> String {
+        // a class without the closing brace stays as it is
+        if let Some(body) = self.body.take() {
+            self.lines.extend(body);
+        }
+        self.flush_relations();
+        let mut str = self.lines.join("\n");
+        str.push('\n');
+        str
+    }
+}
+
+/// Whether the line ends a block which was started with the end in mind
+fn ends_block(line: &str, end: &str) -> bool {
+    match end {
+        "'/" => line.contains("'/"),
+        end => line.to_lowercase().replace(' ', "") == end,
+    }
+}
+
+/// Notes without a text on the same line and other texts which only name their
+/// position continue up to their end
+fn is_multiline(keyword: &str, line: &str) -> bool {
+    match keyword {
+        "note" | "rnote" | "hnote" => !line.contains(':') && !line.contains('"'),
+        _ => line.split_whitespace().skip(1).all(|w| {
+            matches!(
+                w.to_lowercase().as_str(),
+                "left" | "right" | "center" | "top" | "bottom"
+            )
+        }),
+    }
+}
+
+/// Declaration with single spaces and a space before the brace
+fn header(line: &str) -> String {
+    let (declaration, brace) = match line.strip_suffix('{') {
+        Some(declaration) => (declaration, " {"),
+        None => match line.replace(' ', "").ends_with("{}") {
+            true => (&line[..line.rfind('{').unwrap_or(line.len())], " {}"),
+            false => (line, ""),
+        },
+    };
+    let declaration: Vec<&str> = declaration.split_whitespace().collect();
+    format!("{}{}", declaration.join(" "), brace)
+}
+
+/// Formats the members of a class body, without their indentation
+fn format_body(body: &[String]) -> Vec<String> {
+    let mut in_comment = false;
+    let mut lines: Vec<(bool, Member)> = Vec::new();
+    for line in body.iter() {
+        let trimmed = line.trim();
+        if in_comment || trimmed.starts_with("/'") {
+            in_comment = !trimmed.contains("'/");
+            lines.push((false, Member::verbatim(trimmed)));
+        } else if trimmed.is_empty() {
+            // empty lines at the start and twice in a row are left out
+            if lines.last().is_some_and(|(_, m)| !m.text.is_empty()) {
+                lines.push((false, Member::verbatim("")));
+            }
+        } else {
+            lines.push((true, Member::parse(trimmed)));
+        }
+    }
+    while lines.last().is_some_and(|(_, m)| m.text.is_empty()) {
+        lines.pop();
+    }
+
+    // names line up if some members have a visibility
+    let aligned = lines.iter().any(|(_, m)| m.visibility.is_some());
+    lines
+        .into_iter()
+        .map(
+            |(is_member, m)| match (m.visibility, is_member && aligned && m.is_typed) {
+                (Some(v), _) => format!("{} {}", v, m.text),
+                (None, true) => format!("  {}", m.text),
+                (None, false) => m.text,
+            },
+        )
+        .collect()
+}
+
+/// A member, separator or comment in a class body
+struct Member {
+    visibility: Option<char>,
+    text: String,
+    /// Attributes and methods in contrast to enum constants and separators
+    is_typed: bool,
+}
+
+impl Member {
+    fn verbatim(text: &str) -> Self {
+        Self {
+            visibility: None,
+            text: text.to_string(),
+            is_typed: false,
+        }
+    }
+
+    fn parse(line: &str) -> Self {
+        let mut chars = line.chars();
+        let (first, second) = (chars.next(), chars.next());
+        // separators like `--` or `.. text ..`
+        let is_separator = first.is_some_and(|f| "-.=_".contains(f)) && first == second;
+        if line.starts_with('\'') || is_separator {
+            return Self::verbatim(line);
+        }
+        let visibility = first.filter(|c| "+-#~".contains(*c));
+        let mut rest = match visibility {
+            Some(_) => line[1..].trim_start(),
+            None => line,
+        };
+
+        let mut parts = Vec::new();
+        while rest.starts_with('{') {
+            let Some(end) = rest.find('}') else {
+                break;
+            };
+            let modifier: String = rest[1..end].split_whitespace().collect();
+            parts.push(format!("{{{}}}", modifier));
+            rest = rest[end + 1..].trim_start();
+        }
+
+        let is_typed = rest.starts_with(|c: char| c.is_alphanumeric() || c == '_')
+            && rest.contains(['(', ':']);
+        let member = match (rest.find('('), rest.rfind(')')) {
+            (Some(open), Some(close)) if open < close => {
+                let parameters: Vec<String> = split_arguments(&rest[open + 1..close])
+                    .into_iter()
+                    .map(str::trim)
+                    .filter(|p| !p.is_empty())
+                    .map(typed)
+                    .collect();
+                let mut method = format!("{}({})", rest[..open].trim(), parameters.join(", "));
+                let after = rest[close + 1..].trim();
+                match after.strip_prefix(':') {
+                    Some(returns) => method.push_str(&format!(" : {}", returns.trim())),
+                    None if !after.is_empty() => method.push_str(&format!(" {}", after)),
+                    None => (),
+                }
+                method
+            }
+            _ => typed(rest),
+        };
+        parts.push(member);
+        Self {
+            visibility,
+            text: parts.join(" "),
+            is_typed,
+        }
+    }
+}
+
+/// `name : Type` with single spaces, other declarations stay as they are
+fn typed(declaration: &str) -> String {
+    match declaration.split_once(':') {
+        Some((name, vartype)) if !name.trim().is_empty() => {
+            format!("{} : {}", name.trim(), vartype.trim())
+        }
+        _ => declaration.trim().to_string(),
+    }
+}
+
+/// A relation like `A "1" *-- "many" B : label`
+struct Relation<'l> {
+    left: &'l str,
+    left_cardinality: Option<&'l str>,
+    arrow: String,
+    right_cardinality: Option<&'l str>,
+    right: &'l str,
+    label: Option<&'l str>,
+}
+
+impl<'l> Relation<'l> {
+    fn parse(line: &'l str) -> Option<Self> {
+        let (from, to) = arrow(line)?;
+        let (left, left_cardinality) = match line[..from].trim().strip_suffix('"') {
+            Some(rest) => {
+                let start = rest.rfind('"')?;
+                (rest[..start].trim(), Some(&rest[start + 1..]))
+            }
+            None => (line[..from].trim(), None),
+        };
+        let mut rest = line[to..].trim();
+        let mut right_cardinality = None;
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"')?;
+            right_cardinality = Some(&quoted[..end]);
+            rest = quoted[end + 1..].trim_start();
+        }
+        let end = rest
+            .char_indices()
+            .find(|(i, c)| c.is_whitespace() || (*c == ':' && !is_path(rest, *i)))
+            .map_or(rest.len(), |(i, _)| i);
+        let right = &rest[..end];
+        let label = match rest[end..].trim() {
+            "" => None,
+            after => Some(after.strip_prefix(':')?.trim()),
+        };
+        if !is_name(left) || !is_name(right) {
+            return None;
+        }
+
+        let mut relation = Self {
+            left,
+            left_cardinality,
+            arrow: line[from..to].trim().to_string(),
+            right_cardinality,
+            right,
+            label,
+        };
+        // masters and outer classes are written first
+        let flipped = match relation.arrow.as_str() {
+            "--|>" => Some("<|--"),
+            "..|>" => Some("<|.."),
+            "--+" => Some("+--"),
+            "..+" => Some("+.."),
+            _ => None,
+        };
+        if let Some(flipped) = flipped {
+            relation.arrow = flipped.to_string();
+            std::mem::swap(&mut relation.left, &mut relation.right);
+            std::mem::swap(
+                &mut relation.left_cardinality,
+                &mut relation.right_cardinality,
+            );
+        }
+        Some(relation)
+    }
+}
+
+impl std::fmt::Display for Relation<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.left)?;
+        if let Some(cardinality) = self.left_cardinality {
+            write!(f, " \"{}\"", cardinality)?;
+        }
+        write!(f, " {} ", self.arrow)?;
+        if let Some(cardinality) = self.right_cardinality {
+            write!(f, "\"{}\" ", cardinality)?;
+        }
+        write!(f, "{}", self.right)?;
+        if let Some(label) = self.label {
+            write!(f, " : {}", label)?;
+        }
+        Ok(())
+    }
+}
+
+/// Start and end of the first arrow like `<|--`, `o..` or `-up->`, outside of
+/// quotes
+fn arrow(line: &str) -> Option<(usize, usize)> {
+    let bytes = line.as_bytes();
+    let mut quoted = false;
+    let mut start = None;
+    for (i, byte) in bytes.iter().enumerate() {
+        match byte {
+            b'"' => quoted = !quoted,
+            b'-' | b'.' if !quoted => {
+                let next = bytes.get(i + 1).copied();
+                if next == Some(*byte) || next == Some(b'[') || direction(line, i).is_some() {
+                    start = Some(i);
+                    break;
+                }
+            }
+            _ => (),
+        }
+    }
+    let start = start?;
+    let mut from = start;
+    while from > 0 && is_head(bytes, from - 1, true) {
+        from -= 1;
+    }
+    let mut to = start;
+    while to < bytes.len() {
+        match bytes[to] {
+            b'-' | b'.' => match direction(line, to) {
+                Some(len) => to += len,
+                None => to += 1,
+            },
+            b'[' => to += line[to..].find(']')? + 1,
+            _ => break,
+        }
+    }
+    while to < bytes.len() && is_head(bytes, to, false) {
+        to += 1;
+    }
+    Some((from, to))
+}
+
+/// Length of a direction like `-up` in `-up->` starting at the dash
+fn direction(line: &str, i: usize) -> Option<usize> {
+    let dash = &line[i..i + 1];
+    ["up", "down", "left", "right", "u", "d", "l", "r"]
+        .iter()
+        .find(|d| line[i + 1..].starts_with(&format!("{}{}", d, dash)))
+        .map(|d| d.len() + 1)
+}
+
+/// Heads of arrows, `o` and `x` only count if they are separated from the name
+fn is_head(bytes: &[u8], i: usize, left: bool) -> bool {
+    match bytes[i] {
+        b'<' | b'>' | b'|' | b'*' | b'+' | b'#' | b'^' | b'{' | b'}' => true,
+        b'o' | b'x' => {
+            let outside = match left {
+                true => i.checked_sub(1).map(|j| bytes[j]),
+                false => bytes.get(i + 1).copied(),
+            };
+            outside.is_none_or(|c| c.is_ascii_whitespace() || c == b'"')
+        }
+        _ => false,
+    }
+}
+
+/// Whether the `:` at i is part of a `::` like in `Class::member`
+fn is_path(text: &str, i: usize) -> bool {
+    text[i..].starts_with("::") || text[..i].ends_with(':')
+}
+
+fn is_name(name: &str) -> bool {
+    let quoted = name.len() > 1 && name.starts_with('"') && name.ends_with('"');
+    quoted
+        || (!name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || "_.$:".contains(c)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSY: &str = "@startuml
+skinparam classAttributeIconSize 0
+' heroes
+Zauberer --|> Held
+abstract class Held << (Q,red) >>{
+#name:String
+# lebenspunkte :int
+   +addLebenspunkte(faktor:int, ziel : Map<String,int>):boolean
+-{abstract}useweapon(w: Waffe) :void
+  ..
+zauber : int
+
+
+}
+enum Farbe {
+ROT
+GRUEN
+}
+class Zauberer
+class  Outer
+class Inner
+note top of Held
+  Held ist
+     abstrakt
+end note
+/' Monster
+class Monster
+'/
+Inner --+ Outer
+Held \"1\"-->\"*\" Farbe:hat
+
+
+@enduml
+";
+
+    #[test]
+    fn diagrams_are_written_canonically() {
+        let formatted = format(MESSY);
+        assert_eq!(
+            formatted,
+            "@startuml
+skinparam classAttributeIconSize 0
+' heroes
+abstract class Held << (Q,red) >> {
+  # name : String
+  # lebenspunkte : int
+  + addLebenspunkte(faktor : int, ziel : Map<String,int>) : boolean
+  - {abstract} useweapon(w : Waffe) : void
+  ..
+    zauber : int
+}
+enum Farbe {
+  ROT
+  GRUEN
+}
+class Zauberer
+class Outer
+class Inner
+note top of Held
+  Held ist
+     abstrakt
+end note
+/' Monster
+class Monster
+'/
+Held <|-- Zauberer
+Outer +-- Inner
+Held \"1\" --> \"*\" Farbe : hat
+@enduml
+"
+        );
+        assert_eq!(format(&formatted), formatted);
+        verify(Path::new("messy.puml"), MESSY, &formatted).unwrap();
+    }
+
+    #[test]
+    fn changed_classes_are_noticed() {
+        let err = verify(
+            Path::new("a.puml"),
+            "@startuml\nclass A {\n+ a : int\n}\n@enduml",
+            "@startuml\nclass A {\n+ a : long\n}\n@enduml",
+        );
+        assert!(matches!(err, Err(FormatError::Changed(_))));
+    }
+}
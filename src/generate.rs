@@ -5,67 +5,455 @@
 // LICENSE file in the root directory of this source tree.
 
 use crate::{
+    backend::{Backend, SourceFile},
+    config::{Config, Overwrite},
+    mermaid,
+    model::{Attribute, Class, ClassKind, Function, Sealing, Span, Type, View},
+    plantuml,
+    template::{TemplateError, Templates},
     tokenizer::{self, Token},
-    model::{Attribute, Class, Function, Type, View},
+    xmi,
 };
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
+use serde::Serialize;
 use std::fs::{self, File};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::{error::Error, io::prelude::*};
 
+/// Options changing which classes are generated and how
+#[derive(Debug, Default)]
+pub struct Options {
+    /// Seal every class with subclasses
+    pub sealed: bool,
+    /// Names or numbers (starting at 1) of the diagrams to generate, all if empty
+    pub diagrams: Vec<String>,
+    /// Only list which files would be created or modified
+    pub dry_run: bool,
+    /// Print a unified diff of every file which would change instead of writing it
+    pub diff: bool,
+    /// Target language and how the files look
+    pub config: Config,
+    /// Templates to write the classes with instead of the built-in generator
+    pub templates: Option<Templates>,
+    /// Print this stage of the conversion as JSON instead of generating classes
+    pub emit: Option<Emit>,
+}
+
+impl Options {
+    /// The backend of the configured target
+    pub fn backend(&self) -> &'static dyn Backend {
+        self.config.target.backend()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Emit {
+    /// The tokens of every input file
+    Tokens,
+    /// Classes, relations and notes as they are written in the diagrams
+    Ast,
+    /// The classes with resolved inheritance, nesting and types
+    Model,
+}
+
+/// Result of one generation run
+#[derive(Debug, Default)]
+pub struct Summary {
+    /// Number of files which were (or would be) created or modified
+    pub changed: usize,
+    /// Every diagram file which was read, including included ones
+    pub files: Vec<PathBuf>,
+}
+
+/// Generates the classes of all inputs, which can be files, directories, globs or "-" for
+/// stdin. Without an outputlocation all classes are printed to stdout.
 pub fn generate_files(
-    inputfile: &str,
-    outputlocation: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // check inputfile and outputlocation
-    let inputfile = Path::new(inputfile);
-    let outputlocation = Path::new(outputlocation);
-
-    if !outputlocation.exists() {
-        if outputlocation.is_dir() {
+    inputs: &[String],
+    outputlocation: Option<&str>,
+    options: &Options,
+) -> Result<Summary, Box<dyn std::error::Error>> {
+    // check inputs and outputlocation
+    let outputlocation = outputlocation.map(Path::new);
+
+    if let Some(outputlocation) = outputlocation {
+        if !outputlocation.exists() {
             fs::create_dir_all(outputlocation)?;
-        } else {
+        } else if !outputlocation.is_dir() {
             error!("Given output is not a directory");
             return Err(Box::new(CustomError::OutputNotDirectory));
         }
     }
 
-    if !inputfile.exists() {
-        error!("Input file does not exist");
-        return Err(Box::new(CustomError::InputNotFound));
-    } else {
-        if !inputfile.is_file() {
-            error!("Given input is not a file");
-            return Err(Box::new(CustomError::InputNotFile));
+    let (files, read) = read_inputs(inputs)?;
+    let mut summary = Summary {
+        files: read,
+        ..Summary::default()
+    };
+
+    if options.emit == Some(Emit::Tokens) {
+        let tokens: Vec<&Token> = files.iter().flat_map(|(_, idents)| idents).collect();
+        println!("{}", serde_json::to_string_pretty(&tokens)?);
+        return Ok(summary);
+    }
+
+    let idents = merge_diagrams(&files, options)?;
+    let ast = parse(&idents).map_err(|err| {
+        error!("{}", err);
+        Box::new(err)
+    })?;
+    if options.emit == Some(Emit::Ast) {
+        println!("{}", serde_json::to_string_pretty(&ast)?);
+        return Ok(summary);
+    }
+    let relations = ast.relations.clone();
+    let classes = build_classes(ast, options).map_err(|err| {
+        error!("{}", err);
+        Box::new(err)
+    })?;
+
+    if options.emit == Some(Emit::Model) {
+        let model = Model { classes, relations };
+        println!("{}", serde_json::to_string_pretty(&model)?);
+        return Ok(summary);
+    }
+
+    for file in options.backend().files(&classes, options)?.iter() {
+        match outputlocation {
+            Some(outputlocation) => {
+                if write_file(file, outputlocation, options)? != FileStatus::Unchanged {
+                    summary.changed += 1;
+                }
+            }
+            None => print_file(file),
         }
     }
 
-    let idents = tokenizer::get_identifiers(inputfile)?;
-    let classes = get_classes(&idents)
-        .or_else(|err| {
-            error!("{}", err);
-            return Err(Box::new(err));
-        })
-        .unwrap();
+    Ok(summary)
+}
 
-    for class in classes.iter() {
-        write_class(class, &outputlocation)?
+/// Notation of a diagram
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Notation {
+    #[value(name = "plantuml")]
+    PlantUml,
+    Mermaid,
+    Xmi,
+}
+
+/// Writes the diagrams of all inputs as one diagram in the notation, the types
+/// stay as they are written in the diagrams
+pub fn convert(
+    inputs: &[String],
+    notation: Notation,
+    options: &Options,
+) -> Result<String, Box<dyn Error>> {
+    let (files, _) = read_inputs(inputs)?;
+    let idents = merge_diagrams(&files, options)?;
+    let ast = parse(&idents).map_err(|err| {
+        error!("{}", err);
+        Box::new(err)
+    })?;
+    let classes = resolve(ast, options.sealed)?;
+    Ok(match notation {
+        Notation::PlantUml => plantuml::write(&classes),
+        Notation::Mermaid => mermaid::write(&classes),
+        Notation::Xmi => xmi::write(&classes, options.config.package.as_deref()),
+    })
+}
+
+/// Tokens of one input file
+pub type InputFile = (PathBuf, Vec<Token>);
+
+/// Tokens of every input, inputs which are included by another one are left out.
+/// Also returns every file which was read, including included ones.
+pub fn read_inputs(inputs: &[String]) -> Result<(Vec<InputFile>, Vec<PathBuf>), Box<dyn Error>> {
+    let inputfiles = collect_inputs(inputs)?;
+
+    // every file is one part of the model
+    let mut files = Vec::new();
+    let mut included = Vec::new();
+    let mut all_read = Vec::new();
+    for inputfile in inputfiles.into_iter() {
+        let (idents, read) = match inputfile.as_os_str() == STDIN {
+            true => tokenizer::get_identifiers_from_stdin()?,
+            false => tokenizer::get_identifiers(&inputfile)?,
+        };
+        included.extend(read.iter().skip(1).cloned());
+        all_read.extend(read);
+        files.push((inputfile, idents));
+    }
+    // files which are included by another input are already part of that one
+    files.retain(|(inputfile, _)| {
+        let is_included = fs::canonicalize(inputfile).is_ok_and(|p| included.contains(&p));
+        if is_included {
+            debug!("{} is included by another input", inputfile.display());
+        }
+        !is_included
+    });
+    Ok((files, all_read))
+}
+
+/// The selected diagrams of all files as one, a class may only be declared in one file
+pub fn merge_diagrams(files: &[InputFile], options: &Options) -> Result<Vec<Token>, CustomError> {
+    let files = files
+        .iter()
+        .map(|(path, idents)| (path.as_path(), idents.as_slice()));
+    Ok(select_unique_diagrams(files, options)?.concat())
+}
+
+/// The selected diagrams of all files, fails if a class is declared in more than one
+/// diagram
+pub fn select_unique_diagrams<'a>(
+    files: impl IntoIterator<Item = (&'a Path, &'a [Token])>,
+    options: &Options,
+) -> Result<Vec<&'a [Token]>, CustomError> {
+    let mut diagrams = Vec::new();
+    let mut declared: Vec<(&str, &Path)> = Vec::new();
+    for (inputfile, file_idents) in files {
+        for diagram in select_diagrams(file_idents, &options.diagrams)? {
+            for name in declared_classes(diagram) {
+                if let Some((_, other)) = declared.iter().find(|(n, _)| *n == name) {
+                    let err = CustomError::DuplicateClass(
+                        name.to_string(),
+                        other.to_path_buf(),
+                        inputfile.to_path_buf(),
+                    );
+                    error!("{}", err);
+                    return Err(err);
+                }
+                declared.push((name, inputfile));
+            }
+            diagrams.push(diagram);
+        }
+    }
+    Ok(diagrams)
+}
+
+/// Resolves the classes and applies the configured types and accessors
+pub fn build_classes<'a>(
+    ast: Ast<'a>,
+    options: &'a Options,
+) -> Result<Vec<Class<'a>>, GeneratorError> {
+    let mut classes = resolve(ast, options.sealed)?;
+    let config = &options.config;
+    for class in classes.iter_mut() {
+        class.map_types(&|t| config.map_type(t));
+        class.set_accessors(config.accessors);
     }
+    Ok(classes)
+}
+
+/// Input name to read the diagram from stdin
+pub const STDIN: &str = "-";
+
+/// Expands directories and globs to the diagram files they contain
+pub fn collect_inputs(inputs: &[String]) -> Result<Vec<PathBuf>, CustomError> {
+    let mut files = Vec::new();
+    for input in inputs.iter() {
+        if input == STDIN {
+            files.push(PathBuf::from(STDIN));
+            continue;
+        }
+        let paths: Vec<PathBuf> = if input.contains(['*', '?', '[']) {
+            glob::glob(input)
+                .map_err(|_| CustomError::InputNotFound(input.clone()))?
+                .filter_map(|p| p.ok())
+                .collect()
+        } else {
+            vec![PathBuf::from(input)]
+        };
 
+        if paths.is_empty() || !paths.iter().all(|p| p.exists()) {
+            error!("Input {} does not exist", input);
+            return Err(CustomError::InputNotFound(input.clone()));
+        }
+        for path in paths {
+            match path.is_dir() {
+                true => collect_directory(&path, &mut files)?,
+                false => files.push(path),
+            }
+        }
+    }
+    // the same file could be given directly and by its directory
+    let mut unique: Vec<PathBuf> = Vec::new();
+    for file in files {
+        if !unique.iter().any(|u| is_same_file(u, &file)) {
+            unique.push(file);
+        }
+    }
+    Ok(unique)
+}
+
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Adds every diagram in the directory and its subdirectories
+fn collect_directory(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), CustomError> {
+    let read_err = |_| CustomError::InputNotFound(dir.display().to_string());
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(read_err)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .collect();
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            collect_directory(&path, files)?;
+        } else if path.extension().is_some_and(|e| {
+            // markdown files without a diagram would be errors
+            e == "puml"
+                || e == "plantuml"
+                || e == "pu"
+                || e == "mmd"
+                || e == "mermaid"
+                || e == "xmi"
+        }) {
+            files.push(path);
+        }
+    }
     Ok(())
 }
 
-fn write_class<'a>(class: &Class<'a>, location: &Path) -> Result<(), std::io::Error> {
-    let classpath = Path::new(class.name).with_extension("java");
+/// Names of all classes which are declared in the identifiers
+fn declared_classes(idents: &[Token]) -> impl Iterator<Item = &str> {
+    idents.windows(2).filter_map(|w| match w {
+        [Token::Class
+        | Token::AbstractClass
+        | Token::Interface
+        | Token::Enum
+        | Token::Record, Token::Name(name)] => Some(name.as_str()),
+        _ => None,
+    })
+}
+
+/// Splits the identifiers at every @startuml and returns the selected diagrams
+pub fn select_diagrams<'a>(
+    idents: &'a [Token],
+    selection: &[String],
+) -> Result<Vec<&'a [Token]>, CustomError> {
+    let mut diagrams = Vec::new();
+    for (i, start) in idents.iter().enumerate() {
+        if let Token::Startuml(name) = start {
+            let end = idents[i..]
+                .iter()
+                .position(|t| matches!(t, Token::Enduml))
+                .map_or(idents.len(), |e| i + e);
+            diagrams.push((name, &idents[i + 1..end]));
+        }
+    }
+
+    // a diagram is selected by its name or its number starting at 1
+    let is_selected = |i: usize, name: &Option<String>, s: &String| {
+        name.as_ref() == Some(s) || (i + 1).to_string() == *s
+    };
+    for s in selection.iter() {
+        if !diagrams
+            .iter()
+            .enumerate()
+            .any(|(i, (name, _))| is_selected(i, name, s))
+        {
+            error!("There is no diagram {}", s);
+            return Err(CustomError::DiagramNotFound(s.clone()));
+        }
+    }
+
+    Ok(diagrams
+        .into_iter()
+        .enumerate()
+        .filter(|(i, (name, _))| {
+            selection.is_empty() || selection.iter().any(|s| is_selected(*i, name, s))
+        })
+        .map(|(i, (name, diagram))| {
+            debug!("Selected diagram {} {:?}", i + 1, name);
+            diagram
+        })
+        .collect())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FileStatus {
+    Created,
+    Modified,
+    Unchanged,
+}
+
+impl std::fmt::Display for FileStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Created => f.pad("created"),
+            Self::Modified => f.pad("modified"),
+            Self::Unchanged => f.pad("unchanged"),
+        }
+    }
+}
+
+/// Writes the file if it changed, with dry_run or diff nothing is written
+fn write_file(
+    file: &SourceFile,
+    location: &Path,
+    options: &Options,
+) -> Result<FileStatus, Box<dyn Error>> {
+    let classpath = &file.path;
     let path = Path::join(location, classpath);
+    let code = file.code.as_str();
+    let existing = fs::read_to_string(&path).ok();
+    let status = match &existing {
+        None => FileStatus::Created,
+        Some(_) if options.config.overwrite == Overwrite::Never => FileStatus::Unchanged,
+        Some(existing) if *existing == code => FileStatus::Unchanged,
+        Some(_) => FileStatus::Modified,
+    };
+
+    if options.dry_run {
+        println!("{:<9} {}", status, path.display());
+    }
+    if options.diff && status != FileStatus::Unchanged {
+        let old_header = match existing {
+            Some(_) => format!("a/{}", classpath.display()),
+            None => "/dev/null".to_string(),
+        };
+        let existing = existing.unwrap_or_default();
+        let diff = similar::TextDiff::from_lines(existing.as_str(), code);
+        print!(
+            "{}",
+            diff.unified_diff()
+                .header(&old_header, &format!("b/{}", classpath.display()))
+        );
+    }
+    if options.dry_run || options.diff {
+        return Ok(status);
+    }
+
+    if status == FileStatus::Unchanged {
+        info!("{} is unchanged", path.display());
+        return Ok(status);
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
     let mut file = File::create(&path)?;
-    file.write_all(class.to_java().as_bytes())?;
+    file.write_all(code.as_bytes())?;
     info!("successfully wrote to {}", path.display());
-    Ok(())
+    Ok(status)
+}
+
+/// Prints the file with its name as a header
+fn print_file(file: &SourceFile) {
+    println!("// ==== {} ====", file.path.display());
+    println!("{}", file.code);
+}
+
+/// The formatted file of the class in the target language
+pub fn class_file(class: &Class, options: &Options) -> Result<String, TemplateError> {
+    options.backend().file(class, options)
 }
 
 #[derive(Debug)]
-enum GeneratorError {
+pub enum GeneratorError {
     UnexpectedIdentifier(String),
 }
 
@@ -77,22 +465,30 @@ impl std::fmt::Display for GeneratorError {
     }
 }
 
+impl Error for GeneratorError {}
+
 #[derive(Debug)]
-enum CustomError {
-    Utf8ParseError,
+pub enum CustomError {
     OutputNotDirectory,
-    InputNotFile,
-    InputNotFound,
+    InputNotFound(String),
+    DiagramNotFound(String),
+    DuplicateClass(String, PathBuf, PathBuf),
     // InputWrongExtension
 }
 
 impl std::fmt::Display for CustomError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Utf8ParseError => write!(f, "There is a utf8 error"),
-            Self::InputNotFile => write!(f, "Input file is a directory"),
-            Self::InputNotFound => write!(f, "Input file not present"),
+            Self::InputNotFound(input) => write!(f, "Input {} not present", input),
             Self::OutputNotDirectory => write!(f, "Output directory is a file"),
+            Self::DiagramNotFound(name) => write!(f, "Diagram {} not found", name),
+            Self::DuplicateClass(name, a, b) => write!(
+                f,
+                "Class {} is declared in {} and {}",
+                name,
+                a.display(),
+                b.display()
+            ),
             // _ => write!(f, "Some Error"),
         }
     }
@@ -100,131 +496,348 @@ impl std::fmt::Display for CustomError {
 
 impl Error for CustomError {}
 
+/// Classes, relations and notes as they are written in the diagrams, before
+/// inheritance, sealing and nesting are resolved
+#[derive(Debug, Default, Serialize)]
+pub struct Ast<'a> {
+    pub classes: Vec<Class<'a>>,
+    pub relations: Vec<Relation<'a>>,
+    pub notes: Vec<Note<'a>>,
+}
+
+/// The resolved classes with the relations they were resolved from
+#[derive(Debug, Serialize)]
+pub struct Model<'a> {
+    pub classes: Vec<Class<'a>>,
+    pub relations: Vec<Relation<'a>>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Relation<'a> {
+    pub kind: RelationKind,
+    /// The master of an inheritance or the outer class of a nesting
+    pub from: &'a str,
+    pub to: &'a str,
+    pub span: Option<Span<'a>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RelationKind {
+    Inherits,
+    Nests,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Note<'a> {
+    pub class: &'a str,
+    pub member: Option<&'a str>,
+    pub text: &'a str,
+    pub span: Option<Span<'a>>,
+}
+
 // TODO:
 // wait for start/enduml
-/* fn get_classes<'a>(idents: &'a [Token]) -> Result<Vec<Class<'a>>, GeneratorError> {
+/// Builds the classes and collects relations and notes
+pub fn parse<'a>(idents: &'a [Token]) -> Result<Ast<'a>, GeneratorError> {
     debug!("Converting identifiers: {:?}", idents);
-    let mut classes = Vec::new();
-    let mut is_abstract = false;
+    let mut ast = Ast::default();
+    let mut span = None;
     let mut i = 0;
-    // let mut iditer = idents.iter().peekable();
 
     while i < idents.len() {
         match &idents[i] {
-            Token::Abstract => is_abstract = true,
-            Token::Class => match &idents[i + 1] {
-                Token::Name(name) => {
-                    match &idents[i + 2] {
-                        Token::StartObject => {
-                            let (skip, mut class) = gen_class(idents, i + 3, name)?;
-                            class = class.with_abstract(is_abstract);
-                            // let mut class = Class::build(name, View::Public, false);
-                            i += skip + 3;
-                            classes.push(class);
-                            is_abstract = false;
-                        }
-                        _ => {
-                            let s =
-                                format!("Expected Start of object after the class name {{name}}");
-                            return Err(GeneratorError::UnexpectedIdentifier(s));
-                        }
-                    };
-                }
-                _ => {
-                    let s = format!("Expected name after class statement");
-                    return Err(GeneratorError::UnexpectedIdentifier(s));
-                }
-            },
-            Token::InheritesLeft => {
-                let mastername = match &idents[i - 1] {
-                    Token::Name(name) => name,
-                    _ => {
-                        return Err(GeneratorError::UnexpectedIdentifier(
-                            "Expected Name to be iherited from".to_string(),
-                        ))
-                    }
+            Token::Line(file, line) => span = Some(Span { file, line: *line }),
+            Token::Class
+            | Token::AbstractClass
+            | Token::Interface
+            | Token::Enum
+            | Token::Record => {
+                let kind = match &idents[i] {
+                    Token::Interface => ClassKind::Interface,
+                    Token::Enum => ClassKind::Enum,
+                    Token::Record => ClassKind::Record,
+                    _ => ClassKind::Class,
                 };
-                let childname = match &idents[i + 1] {
-                    Token::Name(name) => name,
+                let is_abstract = matches!(idents[i], Token::AbstractClass);
+                let name = match idents.get(i + 1) {
+                    Some(Token::Name(name)) => name,
                     _ => {
-                        let s = format!("Expected name to inherit {}", mastername);
-                        return Err(GeneratorError::UnexpectedIdentifier(s));
-                    }
-                };
-
-                debug!("{}<|--{}", mastername, childname);
-                let master = match classes.iter().find(|c| c.name == mastername) {
-                    Some(c) => c.clone(),
-                    None => {
-                        let s = format!("class {mastername} doesn't exist to be inherited");
-                        return Err(GeneratorError::UnexpectedIdentifier(s));
-                    }
-                };
-                let child = match classes.iter_mut().find(|c| c.name == childname) {
-                    Some(c) => c,
-                    None => {
-                        let s = format!("class {childname} doesn't exist to inherit {mastername}");
+                        let s = "Expected name after class statement".to_string();
                         return Err(GeneratorError::UnexpectedIdentifier(s));
                     }
                 };
-                child.set_inherits(master);
+                if ast.classes.iter().any(|c: &Class| c.name == name) {
+                    let s = format!("class {name} is declared more than once");
+                    return Err(GeneratorError::UnexpectedIdentifier(s));
+                }
+                let mut class = Class::build(name, View::Public, is_abstract)
+                    .with_kind(kind)
+                    .with_span(span);
+                i += 1;
+                if let Some(Token::Static) = idents.get(i + 1) {
+                    class = class.with_static(true);
+                    i += 1;
+                }
+                if let Some(Token::Sealed) = idents.get(i + 1) {
+                    class = class.with_sealing(Sealing::Sealed);
+                    i += 1;
+                }
+                // classes without a body are allowed
+                if let Some(Token::StartObject) = idents.get(i + 1) {
+                    let (skip, c) = gen_class(idents, i + 2, class)?;
+                    class = c;
+                    i += skip + 1;
+                }
+                ast.classes.push(class);
             }
-            Token::InheritesRight => {
-                let mastername = match &idents[i + 1] {
-                    Token::Name(name) => name,
-                    _ => {
-                        return Err(GeneratorError::UnexpectedIdentifier(
-                            "Expected Name to be iherited from".to_string(),
-                        ))
-                    }
+            Token::InheritesLeft | Token::InheritesRight | Token::NestsLeft | Token::NestsRight => {
+                let (left, right) = get_relation_names(idents, i)?;
+                let (kind, from, to) = match &idents[i] {
+                    Token::InheritesLeft => (RelationKind::Inherits, left, right),
+                    Token::InheritesRight => (RelationKind::Inherits, right, left),
+                    Token::NestsLeft => (RelationKind::Nests, left, right),
+                    _ => (RelationKind::Nests, right, left),
                 };
-                let childname = match &idents[i - 1] {
-                    Token::Name(name) => name,
-                    _ => {
-                        let s = format!("Expected name to inherit {}", mastername);
-                        return Err(GeneratorError::UnexpectedIdentifier(s));
-                    }
-                };
-
-                debug!("{}<|--{}", mastername, childname);
-                let master = match classes.iter().find(|c| c.name == mastername) {
-                    Some(c) => c.clone(),
-                    None => {
-                        let s = format!("class {mastername} doesn't exist to be inherited");
-                        return Err(GeneratorError::UnexpectedIdentifier(s));
-                    }
-                };
-                let child = match classes.iter_mut().find(|c| c.name == childname) {
-                    Some(c) => c,
-                    None => {
-                        let s = format!("class {childname} doesn't exist to inherit {mastername}");
-                        return Err(GeneratorError::UnexpectedIdentifier(s));
-                    }
-                };
-                child.set_inherits(master);
+                ast.relations.push(Relation {
+                    kind,
+                    from,
+                    to,
+                    span,
+                });
+            }
+            Token::Note(class, member, text) => {
+                ast.notes.push(Note {
+                    class,
+                    member: member.as_deref(),
+                    text,
+                    span,
+                });
             }
             _ => (),
         }
         i += 1;
     }
+    Ok(ast)
+}
+
+/// Attaches notes, inherits, seals and nests the classes. If sealed is set every class
+/// with subclasses is sealed, not only the ones marked `<<sealed>>`
+pub fn resolve<'a>(ast: Ast<'a>, sealed: bool) -> Result<Vec<Class<'a>>, GeneratorError> {
+    let mut classes = ast.classes;
+    // (master, child) pairs, resolved after all classes are known
+    let mut inherits: Vec<(&str, &str)> = Vec::new();
+    // (outer, inner) pairs of nested classes
+    let mut nests: Vec<(&str, &str)> = Vec::new();
+    for relation in ast.relations.iter() {
+        match relation.kind {
+            RelationKind::Inherits => inherits.push((relation.from, relation.to)),
+            RelationKind::Nests => nests.push((relation.from, relation.to)),
+        }
+    }
+
+    // before inheriting, so implemented methods keep the documentation
+    for note in ast.notes.iter() {
+        let documented = classes
+            .iter_mut()
+            .find(|c| c.name == note.class)
+            .is_some_and(|c| c.add_doc(note.member, note.text));
+        if !documented {
+            let target = match note.member {
+                Some(member) => format!("{}::{}", note.class, member),
+                None => note.class.to_string(),
+            };
+            warn!("note of {} doesn't belong to any class or member", target);
+        }
+    }
+
+    // masters inherit first so their children get the complete master
+    while !inherits.is_empty() {
+        let pos = inherits
+            .iter()
+            .position(|(master, _)| !inherits.iter().any(|(_, child)| child == master))
+            .ok_or_else(|| {
+                let s = "classes can't inherit from each other".to_string();
+                GeneratorError::UnexpectedIdentifier(s)
+            })?;
+        let (mastername, childname) = inherits.remove(pos);
+        inherit(&mut classes, mastername, childname)?;
+    }
+
+    seal_classes(&mut classes, sealed);
+
+    // "Outer.Inner" is nested into Outer if Outer is a declared class
+    for class in classes.iter() {
+        if let Some((outername, _)) = class.name.rsplit_once('.') {
+            if classes.iter().any(|c| c.name == outername) {
+                nests.push((outername, class.name));
+            }
+        }
+    }
+    nest_classes(&mut classes, nests)?;
 
     Ok(classes)
 }
 
+/// Returns the names left and right of the relation token at index
+fn get_relation_names(idents: &[Token], index: usize) -> Result<(&str, &str), GeneratorError> {
+    let left = match index.checked_sub(1).and_then(|i| idents.get(i)) {
+        Some(Token::Name(name)) => name,
+        _ => {
+            let s = "Expected Name on the left side of a relation".to_string();
+            return Err(GeneratorError::UnexpectedIdentifier(s));
+        }
+    };
+    let right = match idents.get(index + 1) {
+        Some(Token::Name(name)) => name,
+        _ => {
+            let s = format!(
+                "Expected Name on the right side of a relation with {}",
+                left
+            );
+            return Err(GeneratorError::UnexpectedIdentifier(s));
+        }
+    };
+    Ok((left, right))
+}
+
+fn inherit<'a>(
+    classes: &mut [Class<'a>],
+    mastername: &str,
+    childname: &str,
+) -> Result<(), GeneratorError> {
+    debug!("{}<|--{}", mastername, childname);
+    let master = match classes.iter().find(|c| c.name == mastername) {
+        Some(c) => c.clone(),
+        None => {
+            let s = format!("class {mastername} doesn't exist to be inherited");
+            return Err(GeneratorError::UnexpectedIdentifier(s));
+        }
+    };
+    let child = match classes.iter_mut().find(|c| c.name == childname) {
+        Some(c) => c,
+        None => {
+            let s = format!("class {childname} doesn't exist to inherit {mastername}");
+            return Err(GeneratorError::UnexpectedIdentifier(s));
+        }
+    };
+    match (master.get_kind(), child.get_kind()) {
+        (ClassKind::Interface, _) => child.add_implements(master),
+        (_, ClassKind::Record) | (_, ClassKind::Enum) => {
+            let s = format!("{childname} can only implement interfaces, not {mastername}");
+            return Err(GeneratorError::UnexpectedIdentifier(s));
+        }
+        _ => child.set_inherits(master),
+    }
+    Ok(())
+}
+
+/// Adds the permitted subtypes to sealed classes and marks their children as final or non-sealed
+fn seal_classes(classes: &mut [Class], all: bool) {
+    let subtypes: Vec<Vec<&str>> = classes
+        .iter()
+        .map(|master| {
+            classes
+                .iter()
+                .filter(|c| c.is_subtype_of(master.name))
+                .map(|c| c.name)
+                .collect()
+        })
+        .collect();
+
+    let sealed: Vec<bool> = classes
+        .iter()
+        .zip(subtypes.iter())
+        .map(|(c, s)| {
+            let sealed = all || c.get_sealing() == Sealing::Sealed;
+            if sealed && s.is_empty() && !all {
+                warn!("{} can't be sealed without any subclasses", c.name);
+            }
+            sealed && !s.is_empty()
+        })
+        .collect();
+
+    for i in 0..classes.len() {
+        let has_sealed_master = classes
+            .iter()
+            .zip(sealed.iter())
+            .any(|(master, sealed)| *sealed && classes[i].is_subtype_of(master.name));
+
+        let sealing = if sealed[i] {
+            Sealing::Sealed
+        } else if !has_sealed_master {
+            Sealing::Open
+        } else if !subtypes[i].is_empty() || classes[i].is_abstract() {
+            Sealing::NonSealed
+        } else {
+            // records are always final and interfaces can't be
+            match classes[i].get_kind() {
+                ClassKind::Class => Sealing::Final,
+                ClassKind::Interface => Sealing::NonSealed,
+                _ => Sealing::Open,
+            }
+        };
+        classes[i].set_sealing(sealing, &subtypes[i]);
+    }
+}
+
+/// Moves nested classes into their outer class, innermost classes first
+fn nest_classes<'a>(
+    classes: &mut Vec<Class<'a>>,
+    mut nests: Vec<(&'a str, &'a str)>,
+) -> Result<(), GeneratorError> {
+    while !nests.is_empty() {
+        let pos = match nests
+            .iter()
+            .position(|(_, inner)| !nests.iter().any(|(outer, _)| outer == inner))
+        {
+            Some(pos) => pos,
+            None => {
+                let s = "classes can't be nested into each other".to_string();
+                return Err(GeneratorError::UnexpectedIdentifier(s));
+            }
+        };
+        let (outername, innername) = nests.remove(pos);
+        debug!("{}+--{}", outername, innername);
+
+        let mut inner = match classes.iter().position(|c| c.name == innername) {
+            Some(index) => classes.remove(index),
+            None => {
+                let s = format!("class {innername} doesn't exist to be nested in {outername}");
+                return Err(GeneratorError::UnexpectedIdentifier(s));
+            }
+        };
+        if let Some((prefix, name)) = innername.rsplit_once('.') {
+            if prefix == outername {
+                inner.set_name(name);
+            }
+        }
+        match classes.iter_mut().find(|c| c.name == outername) {
+            Some(outer) => outer.add_inner(inner),
+            None => {
+                let s = format!("class {outername} doesn't exist to nest {innername}");
+                return Err(GeneratorError::UnexpectedIdentifier(s));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the number of identifiers used including the closing EndObject
 fn gen_class<'a>(
     idents: &'a [Token],
     index: usize,
-    classname: &'a str,
+    mut class: Class<'a>,
 ) -> Result<(usize, Class<'a>), GeneratorError> {
-    let mut class = Class::build(classname, View::Public, false);
-    let mut skip = 0;
     let mut is_abstract = false;
     let mut is_static = false;
     let mut i = index;
     let mut view = View::Normal;
+    let mut span = None;
 
     while i < idents.len() {
         match &idents[i] {
+            Token::Line(file, line) => span = Some(Span { file, line: *line }),
             Token::Public => view = View::Public,
             Token::Private => view = View::Private,
             Token::Protected => view = View::Protected,
@@ -233,15 +846,15 @@ fn gen_class<'a>(
                 class = class.with_abstract(true)
             }
             Token::Static => is_static = true,
+            Token::Constant(name) => class = class.with_constant(name),
             Token::Variable(varname) => {
-                match &idents[i + 1] {
-                    Token::Type(vartype) => {
-                        class = class.with_attribute(Attribute::new(
-                            view,
-                            varname,
-                            Type::Other(vartype),
-                            false,
-                        ));
+                match idents.get(i + 1) {
+                    Some(Token::Type(vartype)) => {
+                        class = class.with_attribute(
+                            Attribute::new(view, varname, Type::Other(vartype), false)
+                                .with_static(is_static)
+                                .with_span(span),
+                        );
                     }
                     _ => {
                         let s = format!(
@@ -255,21 +868,19 @@ fn gen_class<'a>(
                 is_static = false;
                 is_abstract = false;
                 i += 1;
-                skip += 1;
             }
             Token::StartMethod => match &idents[i - 1] {
                 Token::Name(methodname) => {
                     let (mskip, method) =
                         gen_method(idents, i + 1, methodname, view, is_abstract, is_static)?;
                     i += mskip;
-                    skip += mskip;
-                    class = class.with_method(method);
+                    class = class.with_method(method.with_span(span));
                     view = View::Normal;
                     is_static = false;
                     is_abstract = false;
                 }
                 _ => {
-                    let s = format!("Expected a method name");
+                    let s = "Expected a method name".to_string();
                     return Err(GeneratorError::UnexpectedIdentifier(s));
                 }
             },
@@ -279,9 +890,10 @@ fn gen_class<'a>(
         i += 1;
     }
 
-    Ok((skip, class))
+    Ok((i - index + 1, class))
 }
 
+/// Returns the number of identifiers used including the return type
 fn gen_method<'a>(
     idents: &'a [Token],
     index: usize,
@@ -291,14 +903,13 @@ fn gen_method<'a>(
     is_static: bool,
 ) -> Result<(usize, Function<'a>), GeneratorError> {
     let mut paremeters = Vec::new();
-    let mut skip = 0;
     let mut i = index;
     let mut returntype = Type::Other("");
 
     while i < idents.len() {
         match &idents[i] {
-            Token::Variable(varname) => match &idents[i + 1] {
-                Token::Type(typename) => {
+            Token::Variable(varname) => match idents.get(i + 1) {
+                Some(Token::Type(typename)) => {
                     paremeters.push(Attribute::new(view, varname, Type::Other(typename), false))
                 }
                 _ => {
@@ -309,22 +920,20 @@ fn gen_method<'a>(
                     return Err(GeneratorError::UnexpectedIdentifier(s));
                 }
             },
-            Token::EndMethod => match &idents[i + 1] {
-                Token::Type(returnname) => {
+            Token::EndMethod => {
+                if let Some(Token::Type(returnname)) = idents.get(i + 1) {
                     returntype = Type::Other(returnname);
                     i += 1;
-                    break;
                 }
-                _ => (),
-            },
+                break;
+            }
             _ => (),
         }
         i += 1;
     }
-    skip += i - index;
 
     Ok((
-        skip,
+        i - index + 1,
         Function::new(
             methodname,
             view,
@@ -334,5 +943,146 @@ fn gen_method<'a>(
             is_static,
         ),
     ))
-} */
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{Backend, Java};
+
+    fn get_classes(idents: &[Token], sealed: bool) -> Result<Vec<Class<'_>>, GeneratorError> {
+        resolve(parse(idents)?, sealed)
+    }
+
+    fn name(s: &str) -> Token {
+        Token::Name(s.to_string())
+    }
+
+    #[test]
+    fn classes_are_merged_across_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("input")).unwrap();
+        fs::write(
+            dir.join("input/a.puml"),
+            "@startuml\nclass Dog\nAnimal <|-- Dog\n@enduml",
+        )
+        .unwrap();
+        fs::write(dir.join("input/b.puml"), "@startuml\nclass Animal\n@enduml").unwrap();
+        let input = [dir.join("input").display().to_string()];
+        let output = dir.join("output").display().to_string();
+
+        generate_files(&input, Some(&output), &Options::default()).unwrap();
+        let dog = fs::read_to_string(dir.join("output/Dog.java")).unwrap();
+        assert!(dog.starts_with("public class Dog extends Animal {"));
+
+        let input = [
+            dir.join("input/b.puml").display().to_string(),
+            dir.join("input/*.puml").display().to_string(),
+        ];
+        generate_files(&input, Some(&output), &Options::default()).unwrap();
+        let dog = fs::read_to_string(dir.join("output/Dog.java")).unwrap();
+        assert!(dog.starts_with("public class Dog extends Animal {"));
+
+        fs::write(dir.join("input/c.puml"), "@startuml\nclass Dog\n@enduml").unwrap();
+        let err = generate_files(&input, Some(&output), &Options::default()).unwrap_err();
+        assert!(err.to_string().starts_with("Class Dog is declared in"));
+    }
+
+    #[test]
+    fn dry_run_reports_drift_without_writing() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::write(dir.join("a.puml"), "@startuml\nclass A\nclass B\n@enduml").unwrap();
+        let input = [dir.join("a.puml").display().to_string()];
+        let output = dir.join("output").display().to_string();
+        let write = Options::default();
+        let dry_run = Options {
+            dry_run: true,
+            ..Default::default()
+        };
 
+        let changed = |options| {
+            generate_files(&input, Some(&output), options)
+                .unwrap()
+                .changed
+        };
+        assert_eq!(changed(&dry_run), 2);
+        assert!(!dir.join("output/A.java").exists());
+
+        assert_eq!(changed(&write), 2);
+        assert_eq!(changed(&dry_run), 0);
+
+        fs::write(dir.join("output/B.java"), "class B {}").unwrap();
+        assert_eq!(changed(&dry_run), 1);
+    }
+
+    #[test]
+    fn diagrams_are_selected_by_name_or_number() {
+        let idents = vec![
+            Token::Startuml(Some("first".to_string())),
+            Token::Class,
+            name("A"),
+            Token::Enduml,
+            Token::Startuml(None),
+            Token::Class,
+            name("B"),
+            Token::Enduml,
+        ];
+
+        assert_eq!(select_diagrams(&idents, &[]).unwrap().len(), 2);
+        let selection = ["2".to_string()];
+        let diagrams = select_diagrams(&idents, &selection).unwrap();
+        assert!(matches!(diagrams[..], [[Token::Class, Token::Name(ref n)]] if n == "B"));
+        let selection = ["first".to_string()];
+        let diagrams = select_diagrams(&idents, &selection).unwrap();
+        assert!(matches!(diagrams[..], [[Token::Class, Token::Name(ref n)]] if n == "A"));
+        assert!(select_diagrams(&idents, &["third".to_string()]).is_err());
+    }
+
+    #[test]
+    fn sealed_classes_permit_their_subclasses() {
+        let idents = vec![
+            Token::AbstractClass,
+            name("Shape"),
+            Token::Sealed,
+            Token::Class,
+            name("Circle"),
+            Token::Class,
+            name("Square"),
+            Token::Class,
+            name("Rect"),
+            name("Shape"),
+            Token::InheritesLeft,
+            name("Circle"),
+            name("Shape"),
+            Token::InheritesLeft,
+            name("Rect"),
+            name("Square"),
+            Token::InheritesRight,
+            name("Rect"),
+        ];
+        let classes = get_classes(&idents, false).unwrap();
+        let java: Vec<String> = classes.iter().map(|c| Java.class(c)).collect();
+
+        assert!(java[0].starts_with("public abstract sealed class Shape permits Circle, Rect {"));
+        assert!(java[1].starts_with("public final class Circle extends Shape {"));
+        assert!(java[2].starts_with("public class Square extends Rect {"));
+        assert!(java[3].starts_with("public non-sealed class Rect extends Shape {"));
+    }
+
+    #[test]
+    fn parsed_diagram_keeps_lines() {
+        let input = "@startuml\n\nclass Held {\n  ' comment\n  - leben : int\n}\nKrieger --|> Held\n@enduml";
+        let idents = tokenizer::get_identifiers_from_str(input.to_string()).unwrap();
+        let ast = parse(&idents).unwrap();
+
+        let held = ast.classes[0].view();
+        assert_eq!(held.span.map(|s| s.line), Some(3));
+        assert_eq!(held.fields[0].span.map(|s| s.line), Some(5));
+        let relation = ast.relations[0];
+        assert_eq!(relation.kind, RelationKind::Inherits);
+        assert_eq!((relation.from, relation.to), ("Held", "Krieger"));
+        assert_eq!(relation.span.map(|s| s.line), Some(7));
+    }
+}
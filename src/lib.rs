@@ -0,0 +1,198 @@
+// Copyright (c) 2023, Oskar Ohlenmacher
+// All rights reserved
+//
+// This source code is licensed under the BSD-style license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Converts PlantUML class diagrams to Java classes, or the classes of another
+//! target language.
+//!
+//! The functions of the crate root work on text in memory and are the stable
+//! API together with the `model`, `config` and `backend` modules. The terminal
+//! browser of the command line tool needs the `cli` feature, which is on by default.
+//!
+//! ```
+//! let diagram = "@startuml\nclass Held {\n - leben : int\n}\n@enduml";
+//! let files = plantuml2java::generate(diagram, &plantuml2java::Options::default()).unwrap();
+//!
+//! assert_eq!(files[0].path, std::path::Path::new("Held.java"));
+//! assert!(files[0].code.contains("private int leben;"));
+//! ```
+
+use std::env;
+use std::error::Error as StdError;
+use std::path::Path;
+
+pub mod backend;
+pub mod config;
+pub mod model;
+
+// what the command line tool is built from, not part of the stable API
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod browse;
+#[doc(hidden)]
+pub mod diff;
+#[doc(hidden)]
+pub mod format;
+#[doc(hidden)]
+pub mod generate;
+#[doc(hidden)]
+pub mod template;
+#[doc(hidden)]
+pub mod watch;
+
+pub(crate) mod mermaid;
+pub(crate) mod plantuml;
+pub(crate) mod preprocessor;
+pub(crate) mod tokenizer;
+pub(crate) mod xmi;
+
+pub use backend::SourceFile;
+pub use config::Config;
+pub use generate::Options;
+pub use model::Class;
+pub use tokenizer::Token;
+
+// the errors wrapped by `Error`, so callers can match on them
+pub use generate::{CustomError, GeneratorError};
+pub use template::TemplateError;
+pub use tokenizer::SearchError;
+
+/// Preprocesses and tokenizes all diagrams of source, includes are relative to
+/// the current directory
+pub fn tokenize(source: &str) -> Result<Vec<Token>, Error> {
+    let dir = env::current_dir().map_err(|e| SearchError::Error(Box::new(e)))?;
+    let (tokens, _) = tokenizer::get_identifiers_from_source(source, &dir)?;
+    Ok(tokens)
+}
+
+/// Builds the classes of all diagrams with resolved notes, inheritance, sealing
+/// and nesting, a class may only be declared in one of the diagrams
+pub fn parse<'a>(tokens: &'a [Token], options: &'a Options) -> Result<Vec<Class<'a>>, Error> {
+    // the classes borrow from the tokens, so the diagrams are parsed one by one
+    let mut ast = generate::Ast::default();
+    let source = [(Path::new(generate::STDIN), tokens)];
+    for diagram in generate::select_unique_diagrams(source, options)? {
+        let diagram = generate::parse(diagram)?;
+        ast.classes.extend(diagram.classes);
+        ast.relations.extend(diagram.relations);
+        ast.notes.extend(diagram.notes);
+    }
+    Ok(generate::build_classes(ast, options)?)
+}
+
+/// The file of the class on its own, languages which put several classes into
+/// one file are only written like that by `generate`
+pub fn render(class: &Class, options: &Options) -> Result<SourceFile, Error> {
+    Ok(SourceFile {
+        path: options.backend().path(class, &options.config),
+        code: generate::class_file(class, options)?,
+    })
+}
+
+/// Converts every class of the diagrams in source to a file of the target language
+pub fn generate(source: &str, options: &Options) -> Result<Vec<SourceFile>, Error> {
+    let tokens = tokenize(source)?;
+    let classes = parse(&tokens, options)?;
+    Ok(options.backend().files(&classes, options)?)
+}
+
+/// Why a diagram couldn't be converted, more kinds of errors may be added
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    Tokenize(SearchError),
+    Diagram(CustomError),
+    Generate(GeneratorError),
+    Template(TemplateError),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tokenize(e) => write!(f, "{}", e),
+            Self::Diagram(e) => write!(f, "{}", e),
+            Self::Generate(e) => write!(f, "{}", e),
+            Self::Template(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Tokenize(e) => Some(e),
+            Self::Diagram(e) => Some(e),
+            Self::Generate(e) => Some(e),
+            Self::Template(e) => Some(e),
+        }
+    }
+}
+
+impl From<SearchError> for Error {
+    fn from(e: SearchError) -> Self {
+        Self::Tokenize(e)
+    }
+}
+
+impl From<CustomError> for Error {
+    fn from(e: CustomError) -> Self {
+        Self::Diagram(e)
+    }
+}
+
+impl From<GeneratorError> for Error {
+    fn from(e: GeneratorError) -> Self {
+        Self::Generate(e)
+    }
+}
+
+impl From<TemplateError> for Error {
+    fn from(e: TemplateError) -> Self {
+        Self::Template(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classes_are_declared_once_across_diagrams() {
+        let source = "@startuml first\nclass A\n@enduml\n@startuml second\nclass A\n@enduml";
+        let tokens = tokenize(source).unwrap();
+        let options = Options::default();
+        assert!(matches!(
+            parse(&tokens, &options),
+            Err(Error::Diagram(CustomError::DuplicateClass(name, _, _))) if name == "A"
+        ));
+
+        let options = Options {
+            diagrams: vec!["second".to_string()],
+            ..Options::default()
+        };
+        assert_eq!(parse(&tokens, &options).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn diagrams_are_tokenized_and_rendered() {
+        let tokens = tokenize("@startuml\nclass Held {\n - leben : int\n}\n@enduml").unwrap();
+        assert!(matches!(
+            &tokens[..3],
+            [Token::Startuml(None), Token::Line(_, 2), Token::Class]
+        ));
+        assert!(matches!(
+            tokenize("class Held"),
+            Err(Error::Tokenize(SearchError::NoStartYaml))
+        ));
+
+        let mut options = Options::default();
+        options.config.package = Some("com.example".to_string());
+        let classes = parse(&tokens, &options).unwrap();
+        let file = render(&classes[0], &options).unwrap();
+        assert_eq!(file.path, Path::new("com/example/Held.java"));
+        assert!(file.code.starts_with("package com.example;\n"));
+        assert!(file.code.contains("    private int leben;\n"));
+    }
+}
@@ -4,38 +4,341 @@
 // This source code is licensed under the BSD-style license found in the
 // LICENSE file in the root directory of this source tree.
 
-#![feature(iter_intersperse)]
-#![feature(pattern)]
-use clap::Parser;
-use log::error;
-
-mod generate;
-mod tokenizer;
-mod model;
+use clap::{Parser, Subcommand};
+use log::{error, warn};
+use plantuml2java::backend::Target;
+use plantuml2java::config::{self, BraceStyle, Config, Overwrite};
+use plantuml2java::diff::{self, DiffFormat};
+use plantuml2java::model::Accessors;
+use plantuml2java::{browse, format, generate, template, watch};
+use std::fs;
+use std::path::Path;
+use std::process;
 
 // TODO
 // impl interfaces / enums -> evt. trait object
 // auto inflict interface methods
 // fix lexer -> fix comments
-// finish file checks aka concat files for env::current_dir()
 
 /// Convert .puml files to java classes / interfaces not jet implemented
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, subcommand_negates_reqs = true)]
 struct Args {
-    /// The input file (.puml)
-    #[arg(short, long)]
-    input: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// The input files (.puml, .mmd, .xmi), directories or globs, "-" reads from stdin
+    #[arg(short, long, required = true, num_args = 1..)]
+    input: Vec<String>,
 
     /// The output folder - is created if non existend
+    #[arg(short, long, required_unless_present_any = ["stdout", "emit"])]
+    output: Option<String>,
+
+    /// Print all classes to stdout instead of writing files
+    #[arg(long, conflicts_with = "output")]
+    stdout: bool,
+
+    /// Seal every class with subclasses, not only the ones marked <<sealed>>
+    #[arg(long)]
+    sealed: bool,
+
+    /// Only generate the diagram with this name or number, can be given multiple times
     #[arg(short, long)]
-    output: String,
+    diagram: Vec<String>,
+
+    /// List the files which would be created, modified or left unchanged without writing them
+    #[arg(long, requires = "output")]
+    dry_run: bool,
+
+    /// Print a unified diff of the files which would change without writing them
+    #[arg(long, requires = "output")]
+    diff: bool,
+
+    /// Generate again whenever an input or included file changes
+    #[arg(short, long, conflicts_with_all = ["dry_run", "diff"])]
+    watch: bool,
+
+    /// The configuration file, by default plantuml2java.toml is searched from the input upward
+    #[arg(short, long)]
+    config: Option<String>,
+
+    /// Language of the generated classes
+    #[arg(long)]
+    target: Option<Target>,
+
+    /// Package of the generated classes
+    #[arg(long)]
+    package: Option<String>,
+
+    /// Spaces per indentation level
+    #[arg(long)]
+    indent: Option<usize>,
+
+    /// Indent with tabs, --tabs=false overrides the configuration file
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    tabs: Option<bool>,
+
+    /// Where opening braces are placed
+    #[arg(long)]
+    brace_style: Option<BraceStyle>,
+
+    /// Type of attributes with a multiplicity like String[*]
+    #[arg(long)]
+    collection: Option<String>,
+
+    /// Generate getters and setters for attributes which aren't public
+    #[arg(long)]
+    accessors: Option<Accessors>,
+
+    /// Whether existing files are overwritten
+    #[arg(long)]
+    overwrite: Option<Overwrite>,
+
+    /// Replace a type of the diagram, like Date=java.time.LocalDate, can be given multiple times
+    #[arg(short, long = "type", value_parser = config::parse_type_mapping)]
+    types: Vec<(String, String)>,
+
+    /// Print the tokens, the parsed diagram or the resolved model as JSON instead of generating classes
+    #[arg(long, conflicts_with_all = ["output", "stdout"])]
+    emit: Option<generate::Emit>,
+
+    /// Directory with class, interface, enum, record, field and method templates (*.jinja)
+    #[arg(long)]
+    templates: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Explore the classes of the diagrams in the terminal, reloads when a diagram changes
+    Browse {
+        /// The input files (.puml), directories or globs
+        #[arg(short, long, required = true, num_args = 1..)]
+        input: Vec<String>,
+    },
+    /// Write the diagrams as one PlantUML or Mermaid class diagram or XMI model
+    Convert {
+        /// The input files (.puml, .mmd, .md, .xmi), directories or globs, "-" reads from stdin
+        #[arg(short, long, required = true, num_args = 1..)]
+        input: Vec<String>,
+
+        /// Notation of the written diagram
+        #[arg(long)]
+        to: generate::Notation,
+
+        /// File to write the diagram to instead of stdout
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Package of the classes in XMI models
+        #[arg(long)]
+        package: Option<String>,
+    },
+    /// Compare the classes of two versions of the diagrams, fails if a change is breaking
+    Diff {
+        /// The old version, a file, directory or glob
+        old: String,
+
+        /// The new version, a file, directory or glob
+        new: String,
+
+        /// How the changes are written
+        #[arg(long, value_enum, default_value_t = DiffFormat::Text)]
+        format: DiffFormat,
+    },
+    /// Rewrite PlantUML diagrams in one layout, comments and directives are kept
+    Fmt {
+        /// The input files (.puml), directories or globs, "-" reads from stdin and writes to stdout
+        #[arg(short, long, required = true, num_args = 1..)]
+        input: Vec<String>,
+
+        /// Only list the files which aren't formatted, fails if there are some
+        #[arg(long)]
+        check: bool,
+    },
+}
+
+/// Reads the configuration file and applies the command line flags to it
+fn load_config(args: &Args, inputs: &[String]) -> Result<Config, config::ConfigError> {
+    let mut config = match &args.config {
+        Some(path) => Config::load(Path::new(path))?,
+        None => {
+            let input = inputs
+                .iter()
+                .find(|i| *i != generate::STDIN)
+                .map(String::as_str)
+                .unwrap_or(".");
+            // globs are searched from the directory they start in
+            let input = input.split(['*', '?', '[']).next().unwrap_or_default();
+            Config::discover(Path::new(input))?
+        }
+    };
+    if let Some(target) = args.target {
+        config.target = target;
+    }
+    if let Some(package) = &args.package {
+        config.package = Some(package.clone());
+    }
+    if let Some(Command::Convert {
+        package: Some(package),
+        ..
+    }) = &args.command
+    {
+        config.package = Some(package.clone());
+    }
+    if let Some(indent) = args.indent {
+        config.indent = indent;
+    }
+    if let Some(tabs) = args.tabs {
+        config.tabs = tabs;
+    }
+    if let Some(brace_style) = args.brace_style {
+        config.brace_style = Some(brace_style);
+    }
+    if let Some(collection) = &args.collection {
+        config.collection = Some(collection.clone());
+    }
+    if let Some(accessors) = args.accessors {
+        config.accessors = accessors;
+    }
+    if let Some(overwrite) = args.overwrite {
+        config.overwrite = overwrite;
+    }
+    config.types.extend(args.types.iter().cloned());
+    if let Some(templates) = &args.templates {
+        config.templates = Some(templates.into());
+    }
+    Ok(config)
 }
 
 fn main() {
-    env_logger::builder().filter_level(log::LevelFilter::Trace).init();
     let args = Args::parse();
-    if let Err(e) = generate::generate_files(&args.input, &args.output) {
-        error!("{}", e);
+    let versions;
+    let inputs = match &args.command {
+        Some(Command::Browse { input }) => {
+            // log messages would break the screen, they are shown in the browser
+            browse::init_logger().expect("logger is only set once");
+            input
+        }
+        Some(Command::Convert { input, .. }) => {
+            env_logger::init();
+            input
+        }
+        Some(Command::Diff { old, new, .. }) => {
+            env_logger::init();
+            versions = vec![old.clone(), new.clone()];
+            &versions
+        }
+        Some(Command::Fmt { input, check }) => {
+            env_logger::init();
+            match format::format_files(input, *check) {
+                Ok(changed) if *check && !changed.is_empty() => {
+                    for file in changed.iter() {
+                        println!("{}", file.display());
+                    }
+                    process::exit(1);
+                }
+                Ok(_) => return,
+                Err(e) => {
+                    error!("{}", e);
+                    process::exit(2);
+                }
+            }
+        }
+        None => {
+            env_logger::builder()
+                .filter_level(log::LevelFilter::Trace)
+                .init();
+            &args.input
+        }
+    };
+    let config = match load_config(&args, inputs) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("{}", e);
+            process::exit(2);
+        }
+    };
+    if !config.types.is_empty() && !config.maps_types() {
+        warn!("Type mappings are only used for java and kotlin, ignoring them");
+    }
+    let templates = match &config.templates {
+        // the templates write java, other targets have their own generator
+        Some(_) if config.target != Target::Java => {
+            warn!("Templates are only used for java, ignoring them");
+            None
+        }
+        Some(dir) => match template::Templates::load(Some(dir)) {
+            Ok(templates) => Some(templates),
+            Err(e) => {
+                error!("{}", e);
+                process::exit(2);
+            }
+        },
+        None => None,
+    };
+    let options = generate::Options {
+        sealed: args.sealed,
+        diagrams: args.diagram,
+        dry_run: args.dry_run,
+        diff: args.diff,
+        config,
+        templates,
+        emit: args.emit,
+    };
+    if let Some(Command::Browse { input }) = &args.command {
+        if let Err(e) = browse::browse(input, &options) {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+        return;
+    }
+    if let Some(Command::Convert {
+        input, to, output, ..
+    }) = &args.command
+    {
+        let written = generate::convert(input, *to, &options).and_then(|diagram| {
+            match output {
+                Some(output) => fs::write(output, diagram)?,
+                None => print!("{}", diagram),
+            }
+            Ok(())
+        });
+        if let Err(e) = written {
+            error!("{}", e);
+            process::exit(2);
+        }
+        return;
+    }
+    if let Some(Command::Diff { old, new, format }) = &args.command {
+        match diff::diff_inputs(old, new, *format, &options) {
+            Ok((report, breaking)) => {
+                print!("{}", report);
+                if breaking {
+                    process::exit(1);
+                }
+            }
+            Err(e) => {
+                error!("{}", e);
+                process::exit(2);
+            }
+        }
+        return;
+    }
+    if args.watch {
+        if let Err(e) = watch::watch(&args.input, args.output.as_deref(), &options) {
+            error!("{}", e);
+            process::exit(2);
+        }
+        return;
+    }
+    match generate::generate_files(&args.input, args.output.as_deref(), &options) {
+        // the diagram and the code are out of sync
+        Ok(summary) if summary.changed > 0 && (options.dry_run || options.diff) => process::exit(1),
+        Ok(_) => (),
+        Err(e) => {
+            error!("{}", e);
+            process::exit(2);
+        }
     }
 }
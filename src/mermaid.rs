@@ -0,0 +1,901 @@
+// Copyright (c) 2023, Oskar Ohlenmacher
+// All rights reserved
+//
+// This source code is licensed under the BSD-style license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Mermaid `classDiagram`s, read into the same tokens as PlantUML diagrams and
+//! written from the model.
+
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+
+use crate::model::{Class, ClassKind, Function, Sealing, View};
+use crate::tokenizer::Token;
+
+/// Extensions of files read as Mermaid, markdown files are searched for
+/// fenced `mermaid` blocks
+pub const EXTENSIONS: [&str; 3] = ["mmd", "mermaid", "md"];
+
+/// Label of the composition which declares a class inside of another
+const NESTED: &str = "nested";
+
+/// Lines which only change how the diagram looks
+const STYLE_STATEMENTS: [&str; 7] = [
+    "direction",
+    "classDef",
+    "cssClass",
+    "style",
+    "click",
+    "callback",
+    "link",
+];
+
+#[derive(Debug)]
+pub enum MermaidError {
+    Io(PathBuf, std::io::Error),
+    NoClassDiagram,
+    UnclosedClass(usize, String),
+    Member(usize, String),
+}
+
+impl std::fmt::Display for MermaidError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(path, e) => write!(f, "Can't read {}: {}", path.display(), e),
+            Self::NoClassDiagram => write!(f, "No classDiagram found"),
+            Self::UnclosedClass(line, name) => {
+                write!(f, "Body of class {} on line {} is not closed", name, line)
+            }
+            Self::Member(line, member) => {
+                write!(f, "Can't read member \"{}\" on line {}", member, line)
+            }
+        }
+    }
+}
+
+impl Error for MermaidError {}
+
+/// Whether the file is read as Mermaid instead of PlantUML
+pub fn is_mermaid_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| EXTENSIONS.iter().any(|m| e == *m))
+}
+
+/// Whether text which isn't read from a file is a Mermaid diagram
+pub fn is_mermaid(text: &str) -> bool {
+    !text.contains("@startuml")
+        && text.lines().any(|l| {
+            let l = l.trim();
+            l.starts_with("classDiagram") || l.starts_with("```mermaid")
+        })
+}
+
+/// Returns the tokens of the file and the file itself as the only one read
+pub fn get_identifiers(filepath: &Path) -> Result<(Vec<Token>, Vec<PathBuf>), MermaidError> {
+    let path =
+        fs::canonicalize(filepath).map_err(|e| MermaidError::Io(filepath.to_path_buf(), e))?;
+    let text = fs::read_to_string(&path).map_err(|e| MermaidError::Io(path.clone(), e))?;
+    info!("Opened {:?} to parse from", filepath);
+    let current_dir = env::current_dir().unwrap_or_default();
+    let file = path.strip_prefix(&current_dir).unwrap_or(&path);
+    Ok((tokenize(&text, &file.display().to_string())?, vec![path]))
+}
+
+/// A class with everything the diagram says about it, members can be added after
+/// the declaration with `Name : member`
+#[derive(Default)]
+struct Declaration {
+    name: String,
+    line: usize,
+    annotations: Vec<String>,
+    members: Vec<(usize, String)>,
+}
+
+#[derive(Default)]
+struct Diagram {
+    name: Option<String>,
+    classes: Vec<Declaration>,
+    /// Line, relation token and the classes on its left and right
+    relations: Vec<(usize, Token, String, String)>,
+    notes: Vec<(usize, String, String)>,
+}
+
+impl Diagram {
+    fn class(&mut self, name: &str, line: usize) -> &mut Declaration {
+        let i = match self.classes.iter().position(|c| c.name == name) {
+            Some(i) => i,
+            None => {
+                self.classes.push(Declaration {
+                    name: name.to_string(),
+                    line,
+                    ..Default::default()
+                });
+                self.classes.len() - 1
+            }
+        };
+        &mut self.classes[i]
+    }
+}
+
+/// Converts every `classDiagram` of the text to tokens, in markdown only the
+/// fenced `mermaid` blocks are read
+pub fn tokenize(text: &str, file: &str) -> Result<Vec<Token>, MermaidError> {
+    let is_markdown = text.lines().any(|l| l.trim().starts_with("```mermaid"));
+    let mut diagrams: Vec<Diagram> = Vec::new();
+    let mut in_fence = false;
+    let mut in_diagram = false;
+    let mut title = None;
+    // class whose body is read and the depth of namespaces
+    let mut body: Option<String> = None;
+    let mut namespaces = 0;
+
+    for (i, line) in text.lines().enumerate() {
+        let number = i + 1;
+        let line = line.trim();
+        if is_markdown {
+            if line.starts_with("```mermaid") {
+                in_fence = true;
+                in_diagram = false;
+                continue;
+            }
+            if !in_fence {
+                continue;
+            }
+            if line.starts_with("```") {
+                in_fence = false;
+                in_diagram = false;
+                continue;
+            }
+        }
+        if line.is_empty() || line.starts_with("%%") {
+            continue;
+        }
+        if !in_diagram {
+            if let Some(name) = line.strip_prefix("title:") {
+                title = Some(name.trim().trim_matches('"').to_string());
+            } else if line.starts_with("classDiagram") {
+                in_diagram = true;
+                diagrams.push(Diagram {
+                    name: title.take(),
+                    ..Default::default()
+                });
+            }
+            continue;
+        }
+        let Some(diagram) = diagrams.last_mut() else {
+            continue;
+        };
+        // a new diagram starts without an end of the last one
+        if line.starts_with("classDiagram") {
+            diagrams.push(Diagram::default());
+            continue;
+        }
+
+        if let Some(class) = &body {
+            if line == "}" {
+                body = None;
+            } else if let Some(annotation) = annotation(line) {
+                diagram.class(class, number).annotations.push(annotation);
+            } else {
+                let class = class.clone();
+                diagram
+                    .class(&class, number)
+                    .members
+                    .push((number, line.to_string()));
+            }
+            continue;
+        }
+
+        let first = line.split_whitespace().next().unwrap_or_default();
+        if STYLE_STATEMENTS.contains(&first) {
+            continue;
+        } else if line == "}" {
+            namespaces -= 1;
+        } else if first == "namespace" {
+            namespaces += 1;
+        } else if first == "note" {
+            if let Some((class, text)) = note(line) {
+                diagram.notes.push((number, class, text));
+            }
+        } else if first == "class" {
+            let rest = &line["class".len()..];
+            let name = class_name(rest);
+            let class = diagram.class(&name, number);
+            class.line = number;
+            // the model has no type parameters, they are part of the name in PlantUML
+            if let Some(parameters) = type_parameters(rest) {
+                warn!(
+                    "Line {} of {}: the type parameters {} of {} are left out",
+                    number, file, parameters, name
+                );
+            }
+            if let Some(annotation) = annotation(rest) {
+                class.annotations.push(annotation);
+            }
+            let open = rest.trim_end().ends_with('{');
+            if open {
+                body = Some(name);
+            }
+        } else if let Some(annotation) = annotation(line).filter(|_| line.starts_with("<<")) {
+            let name = class_name(&line[line.find(">>").unwrap_or_default() + 2..]);
+            diagram.class(&name, number).annotations.push(annotation);
+        } else if let Some(relation) = relation(line) {
+            let (left, arrow, right) = (&relation.left, relation.arrow.as_str(), &relation.right);
+            diagram.class(left, number);
+            diagram.class(right, number);
+            // Mermaid can't nest classes, "Outer *-- Inner : nested" stands in for it
+            let label = line.split_once(" : ").map(|(_, label)| label.trim());
+            let nested = label == Some(NESTED);
+            let token = match arrow {
+                a if a.starts_with("<|") && !a.ends_with("|>") => Some(Token::InheritesLeft),
+                a if a.ends_with("|>") && !a.starts_with("<|") => Some(Token::InheritesRight),
+                "*--" if nested => Some(Token::NestsLeft),
+                "--*" if nested => Some(Token::NestsRight),
+                _ => None,
+            };
+            if let Some(token) = token {
+                diagram
+                    .relations
+                    .push((number, token, left.clone(), right.clone()));
+                continue;
+            }
+            // the class an association can be navigated from has the other end
+            // as its attribute
+            let solid = arrow.contains("--");
+            let mut ends = Vec::new();
+            if (solid && arrow.ends_with('>')) || arrow.starts_with(['*', 'o']) {
+                ends.push((left, right, &relation.right_cardinality));
+            }
+            if (solid && arrow.starts_with('<')) || arrow.ends_with(['*', 'o']) {
+                ends.push((right, left, &relation.left_cardinality));
+            }
+            if ends.is_empty() {
+                warn!(
+                    "Line {} of {}: \"{}\" has no direction and isn't part of the model",
+                    number, file, line
+                );
+            }
+            for (owner, other, cardinality) in ends {
+                let member = association_end(other, cardinality, label);
+                diagram.class(owner, number).members.push((number, member));
+            }
+        } else if let Some((name, member)) = line.split_once(':') {
+            let name = class_name(name);
+            diagram
+                .class(&name, number)
+                .members
+                .push((number, member.trim().to_string()));
+        } else {
+            // a class can be declared by its name only
+            let name = class_name(line);
+            diagram.class(&name, number);
+        }
+    }
+    if let Some(class) = body {
+        let line = diagrams
+            .last()
+            .and_then(|d| d.classes.iter().find(|c| c.name == class))
+            .map(|c| c.line)
+            .unwrap_or_default();
+        return Err(MermaidError::UnclosedClass(line, class));
+    }
+    if namespaces != 0 {
+        warn!("A namespace of {} is not closed", file);
+    }
+    if diagrams.is_empty() {
+        return Err(MermaidError::NoClassDiagram);
+    }
+
+    let mut tokens = Vec::new();
+    for diagram in diagrams.iter() {
+        push_diagram(diagram, file, &mut tokens)?;
+    }
+    Ok(tokens)
+}
+
+fn push_diagram(
+    diagram: &Diagram,
+    file: &str,
+    tokens: &mut Vec<Token>,
+) -> Result<(), MermaidError> {
+    let line = |number: usize| Token::Line(file.to_string(), number);
+    tokens.push(Token::Startuml(diagram.name.clone()));
+    for class in diagram.classes.iter() {
+        let has = |a: &str| class.annotations.iter().any(|b| b.eq_ignore_ascii_case(a));
+        let is_enum = has("enumeration") || has("enum");
+        tokens.push(line(class.line));
+        tokens.push(if has("interface") {
+            Token::Interface
+        } else if is_enum {
+            Token::Enum
+        } else if has("record") {
+            Token::Record
+        } else if has("abstract") {
+            Token::AbstractClass
+        } else {
+            Token::Class
+        });
+        tokens.push(Token::Name(class.name.clone()));
+        if has("static") {
+            tokens.push(Token::Static);
+        }
+        if has("sealed") {
+            tokens.push(Token::Sealed);
+        }
+        if class.members.is_empty() {
+            continue;
+        }
+        tokens.push(Token::StartObject);
+        for (number, member) in class.members.iter() {
+            tokens.push(line(*number));
+            let member_tokens = member_tokens(member, is_enum)
+                .ok_or_else(|| MermaidError::Member(*number, member.clone()))?;
+            tokens.extend(member_tokens);
+        }
+        tokens.push(Token::EndObject);
+    }
+    for (number, token, left, right) in diagram.relations.iter() {
+        tokens.push(line(*number));
+        tokens.push(Token::Name(left.clone()));
+        tokens.push(token.clone());
+        tokens.push(Token::Name(right.clone()));
+    }
+    for (number, class, text) in diagram.notes.iter() {
+        tokens.push(line(*number));
+        tokens.push(Token::Note(class.clone(), None, text.clone()));
+    }
+    tokens.push(Token::Enduml);
+    Ok(())
+}
+
+/// Name of the class at the start of the text, without generics, label and style
+fn class_name(text: &str) -> String {
+    let text = text.trim();
+    let end = text
+        .find(|c: char| c.is_whitespace() || "~[{<:".contains(c))
+        .unwrap_or(text.len());
+    text[..end].trim_matches('`').to_string()
+}
+
+/// Type parameters after the name of a declared class, `Box~K, V~` has `<K, V>`
+fn type_parameters(text: &str) -> Option<String> {
+    let text = text.trim();
+    let start = text.find(|c: char| c.is_whitespace() || "~[{<:".contains(c))?;
+    let rest = text[start..].strip_prefix('~')?;
+    let head = rest.split(['{', '[', ':']).next().unwrap_or_default();
+    let head = head.split("<<").next().unwrap_or_default();
+    let end = head.rfind('~')?;
+    Some(generics(&format!("~{}", &head[..=end])))
+}
+
+/// Attribute for the end of an association, it is named by the label or else
+/// after the class and many classes are a multiplicity
+fn association_end(class: &str, cardinality: &str, label: Option<&str>) -> String {
+    let name = match label {
+        Some(label) if is_identifier(label) => label.to_string(),
+        _ => {
+            let mut chars = class.chars();
+            let first = chars.next().map(|c| c.to_lowercase().to_string());
+            first.unwrap_or_default() + chars.as_str()
+        }
+    };
+    let cardinality = cardinality.trim();
+    let is_many = cardinality.ends_with(['*', 'n']) || cardinality == "many";
+    let multiplicity = if is_many { "[*]" } else { "" };
+    format!("-{}{} {}", class, multiplicity, name)
+}
+
+fn is_identifier(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Text of an annotation like `<<interface>>`
+fn annotation(text: &str) -> Option<String> {
+    let start = text.find("<<")? + 2;
+    let end = start + text[start..].find(">>")?;
+    Some(text[start..end].trim().to_string())
+}
+
+/// Class and text of `note for Name "text"`, other notes aren't attached to a class
+fn note(line: &str) -> Option<(String, String)> {
+    let rest = line
+        .strip_prefix("note")?
+        .trim_start()
+        .strip_prefix("for ")?;
+    let (name, text) = rest.trim_start().split_once(char::is_whitespace)?;
+    let text = text.trim().strip_prefix('"')?.strip_suffix('"')?;
+    Some((
+        name.to_string(),
+        text.replace("\\n", "\n").replace("<br>", "\n"),
+    ))
+}
+
+/// Classes, arrow and cardinalities of a relation like `Left "1" --> "*" Right`
+struct Relation {
+    left: String,
+    arrow: String,
+    right: String,
+    left_cardinality: String,
+    right_cardinality: String,
+}
+
+/// Relation of the line, the label is left out
+fn relation(line: &str) -> Option<Relation> {
+    // the label could contain anything
+    let line = match line.find(" : ") {
+        Some(i) => &line[..i],
+        None => line,
+    };
+    let start = line.find("--").or_else(|| line.find(".."))?;
+    let bytes = line.as_bytes();
+    let mut from = start;
+    while from > 0 && is_arrow_end(bytes, from - 1, true) {
+        from -= 1;
+    }
+    let mut to = start + 2;
+    while to < bytes.len() && (bytes[to] == b'-' || bytes[to] == b'.') {
+        to += 1;
+    }
+    while to < bytes.len() && is_arrow_end(bytes, to, false) {
+        to += 1;
+    }
+    let (left, left_cardinality) = without_cardinality(&line[..from]);
+    let (right, right_cardinality) = without_cardinality(&line[to..]);
+    if left.is_empty() || right.is_empty() {
+        return None;
+    }
+    Some(Relation {
+        left: class_name(&left),
+        arrow: line[from..to].to_string(),
+        right: class_name(&right),
+        left_cardinality,
+        right_cardinality,
+    })
+}
+
+/// Heads of arrows, `o` only counts if it is separated from the class name
+fn is_arrow_end(bytes: &[u8], i: usize, left: bool) -> bool {
+    match bytes[i] {
+        b'<' | b'>' | b'|' | b'*' => true,
+        b'o' => {
+            let outside = match left {
+                true => i.checked_sub(1).map(|j| bytes[j]),
+                false => bytes.get(i + 1).copied(),
+            };
+            outside.is_none_or(|c| c.is_ascii_whitespace() || c == b'"')
+        }
+        _ => false,
+    }
+}
+
+/// Class name and the quoted cardinality next to it
+fn without_cardinality(text: &str) -> (String, String) {
+    let mut rest = text.trim().to_string();
+    let mut cardinality = String::new();
+    // "Left "1" -- "*" Right"
+    while let Some(start) = rest.find('"') {
+        let end = rest[start + 1..]
+            .find('"')
+            .map(|e| start + e + 2)
+            .unwrap_or(rest.len());
+        cardinality = rest[start..end].trim_matches('"').to_string();
+        rest.replace_range(start..end, "");
+    }
+    (rest.trim().to_string(), cardinality)
+}
+
+/// Tokens of an attribute like `+String name`, `name: String` or a method like
+/// `+area(int scale) double`, `$` marks static and `*` abstract members
+fn member_tokens(member: &str, is_enum: bool) -> Option<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut text = member.trim();
+    match text.chars().next()? {
+        '+' => tokens.push(Token::Public),
+        '-' => tokens.push(Token::Private),
+        '#' => tokens.push(Token::Protected),
+        '~' => tokens.push(Token::PackagePrivate),
+        _ => (),
+    }
+    if !tokens.is_empty() {
+        text = text[1..].trim_start();
+    }
+    let mut classifiers = Vec::new();
+    while let Some(c) = text.chars().last().filter(|c| *c == '$' || *c == '*') {
+        classifiers.push(c);
+        text = text[..text.len() - 1].trim_end();
+    }
+
+    let Some(open) = text.find('(') else {
+        push_classifiers(&classifiers, &mut tokens);
+        let text = generics(text);
+        let (name, vartype) = match text.split_once(':') {
+            Some((name, vartype)) => (name.trim().to_string(), vartype.trim().to_string()),
+            None => match text.trim().rsplit_once(char::is_whitespace) {
+                Some((vartype, name)) => (name.to_string(), vartype.trim().to_string()),
+                // enums list their constants
+                None if is_enum => {
+                    tokens.push(Token::Constant(text.trim().to_string()));
+                    return Some(tokens);
+                }
+                None => (text.trim().to_string(), "Object".to_string()),
+            },
+        };
+        tokens.push(Token::Variable(name));
+        tokens.push(Token::Type(vartype));
+        return Some(tokens);
+    };
+    let close = text.rfind(')')?;
+    let mut rest = text[close + 1..].trim();
+    while let Some(c) = rest.chars().next().filter(|c| *c == '$' || *c == '*') {
+        classifiers.push(c);
+        rest = rest[1..].trim_start();
+    }
+    push_classifiers(&classifiers, &mut tokens);
+    tokens.push(Token::Name(text[..open].trim().to_string()));
+    tokens.push(Token::StartMethod);
+    let parameters = generics(&text[open + 1..close]);
+    for parameter in split_parameters(&parameters) {
+        let parameter = parameter.trim();
+        if parameter.is_empty() {
+            continue;
+        }
+        let (name, vartype) = match parameter.split_once(':') {
+            Some((name, vartype)) => (name.trim(), vartype.trim()),
+            None => match parameter.rsplit_once(char::is_whitespace) {
+                Some((vartype, name)) => (name, vartype.trim()),
+                None => (parameter, "Object"),
+            },
+        };
+        tokens.push(Token::Variable(name.to_string()));
+        tokens.push(Token::Type(vartype.to_string()));
+    }
+    tokens.push(Token::EndMethod);
+    let return_type = generics(rest.strip_prefix(':').unwrap_or(rest).trim());
+    if !return_type.is_empty() {
+        tokens.push(Token::Type(return_type));
+    }
+    Some(tokens)
+}
+
+fn push_classifiers(classifiers: &[char], tokens: &mut Vec<Token>) {
+    if classifiers.contains(&'$') {
+        tokens.push(Token::Static);
+    }
+    if classifiers.contains(&'*') {
+        tokens.push(Token::Abstract);
+    }
+}
+
+/// Splits parameters at the commas which aren't inside of generics
+fn split_parameters(parameters: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in parameters.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&parameters[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    parts.push(&parameters[start..]);
+    parts
+}
+
+/// Replaces the `~` around generic arguments, `List~List~int~~` becomes
+/// `List<List<int>>`
+fn generics(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut str = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        match c {
+            '~' => {
+                let opens = chars
+                    .get(i + 1)
+                    .is_some_and(|n| n.is_alphanumeric() || *n == '_');
+                str.push(if opens { '<' } else { '>' });
+            }
+            c => str.push(*c),
+        }
+    }
+    str
+}
+
+/// Writes the classes as one Mermaid class diagram. Mermaid has no nested
+/// classes, they are written as compositions
+pub fn write(classes: &[Class]) -> String {
+    let mut str = String::from("classDiagram\n");
+    let mut relations = Vec::new();
+    let mut notes = Vec::new();
+    for class in classes.iter() {
+        push_class(class, &mut str, &mut relations, &mut notes);
+    }
+    for line in relations.iter().chain(notes.iter()) {
+        str.push_str("    ");
+        str.push_str(line);
+        str.push('\n');
+    }
+    str
+}
+
+fn push_class(
+    class: &Class,
+    str: &mut String,
+    relations: &mut Vec<String>,
+    notes: &mut Vec<String>,
+) {
+    let mut body = Vec::new();
+    match class.get_kind() {
+        ClassKind::Interface => body.push("<<interface>>".to_string()),
+        ClassKind::Enum => body.push("<<enumeration>>".to_string()),
+        ClassKind::Record => body.push("<<record>>".to_string()),
+        ClassKind::Class if class.is_abstract() => body.push("<<abstract>>".to_string()),
+        ClassKind::Class => (),
+    }
+    if class.get_sealing() == Sealing::Sealed {
+        body.push("<<sealed>>".to_string());
+    }
+    body.extend(class.constants().iter().map(|c| c.to_string()));
+    for a in class.attributes().iter() {
+        let classifier = if a.is_static() { "$" } else { "" };
+        body.push(format!(
+            "{}{} {}{}",
+            visibility(a.visibility()),
+            types(&a.vartype().to_string()),
+            a.name(),
+            classifier
+        ));
+    }
+    body.extend(class.methods().iter().map(method));
+
+    match body.is_empty() {
+        true => str.push_str(&format!("    class {}\n", types(class.name()))),
+        false => {
+            str.push_str(&format!("    class {} {{\n", types(class.name())));
+            for line in body.iter() {
+                str.push_str(&format!("        {}\n", line));
+            }
+            str.push_str("    }\n");
+        }
+    }
+
+    // relations only name the class, without the type parameters
+    let name = plain_name(class.name());
+    if let Some(master) = class.extends() {
+        relations.push(format!("{} <|-- {}", plain_name(master.name()), name));
+    }
+    for interface in class.implements().iter() {
+        let arrow = match class.get_kind() {
+            ClassKind::Interface => "<|--",
+            _ => "<|..",
+        };
+        relations.push(format!(
+            "{} {} {}",
+            plain_name(interface.name()),
+            arrow,
+            name
+        ));
+    }
+    if let Some(doc) = class.doc() {
+        notes.push(format!(
+            "note for {} \"{}\"",
+            name,
+            doc.replace('"', "'").replace('\n', "\\n")
+        ));
+    }
+    for inner in class.inner().iter() {
+        relations.push(format!(
+            "{} *-- {} : {}",
+            name,
+            plain_name(inner.name()),
+            NESTED
+        ));
+        push_class(inner, str, relations, notes);
+    }
+}
+
+fn method(f: &Function) -> String {
+    let parameters: Vec<String> = f
+        .parameters()
+        .iter()
+        .map(|p| format!("{} {}", types(&p.vartype().to_string()), p.name()))
+        .collect();
+    let mut str = format!(
+        "{}{}({})",
+        visibility(f.visibility()),
+        f.name(),
+        parameters.join(", ")
+    );
+    let return_type = f.return_type().to_string();
+    if !return_type.is_empty() {
+        str.push(' ');
+        str.push_str(&types(&return_type));
+    }
+    if f.is_static() {
+        str.push('$');
+    }
+    if f.is_abstract() {
+        str.push('*');
+    }
+    str
+}
+
+fn visibility(view: View) -> &'static str {
+    match view {
+        View::Public => "+",
+        View::Private => "-",
+        View::Protected => "#",
+        View::Normal => "",
+    }
+}
+
+/// Generic arguments are written between `~`
+fn types(name: &str) -> String {
+    name.replace(['<', '>'], "~")
+}
+
+fn plain_name(name: &str) -> &str {
+    name.split('<').next().unwrap_or(name).trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{Backend, Java};
+    use crate::generate;
+    use crate::tokenizer;
+
+    const ANIMALS: &str = "# Animals
+
+```mermaid
+---
+title: Zoo
+---
+classDiagram
+    %% every animal has a name
+    class Animal {
+        <<abstract>>
+        #String name
+        +List~String~ tags
+        +makeSound()* void
+        +create(String name)$ Animal
+    }
+    class Duck {
+        -int feathers
+        +swim(int meters, List~List~int~~ depths) boolean
+    }
+    Animal <|-- Duck
+    Duck ..|> Swimmer
+    Duck \"1\" --> \"*\" Egg : lays
+    Egg *-- Shell : nested
+    <<interface>> Swimmer
+    Swimmer : +swim(int meters, List~List~int~~ depths) boolean
+    class Color {
+        <<enumeration>>
+        RED
+        GREEN
+    }
+    note for Duck \"Quacks\\nloudly\"
+```
+";
+
+    fn java(tokens: &[Token]) -> Vec<String> {
+        let ast = generate::parse(tokens).unwrap();
+        let classes = generate::resolve(ast, false).unwrap();
+        classes.iter().map(|c| Java.class(c)).collect()
+    }
+
+    #[test]
+    fn mermaid_is_read_like_plantuml() {
+        let tokens = tokenize(ANIMALS, "animals.md").unwrap();
+        assert!(matches!(&tokens[0], Token::Startuml(Some(name)) if name == "Zoo"));
+        assert!(tokens
+            .iter()
+            .any(|t| matches!(t, Token::Line(file, 12) if file == "animals.md")));
+
+        let plantuml = "@startuml
+abstract class Animal {
+ # name : String
+ + tags : List<String>
+ + {abstract} makeSound() : void
+ + {static} create(name : String) : Animal
+}
+class Duck {
+ - feathers : int
+ + swim(meters : int, depths : List<List<int>>) : boolean
+ - lays : Egg[*]
+}
+class Egg
+class Shell
+interface Swimmer {
+ + swim(meters : int, depths : List<List<int>>) : boolean
+}
+enum Color {
+ RED
+ GREEN
+}
+Animal <|-- Duck
+Duck ..|> Swimmer
+Egg +-- Shell
+note top of Duck : Quacks\\nloudly
+@enduml";
+        let expected = tokenizer::get_identifiers_from_str(plantuml.to_string()).unwrap();
+        let mut got = java(&tokens);
+        let mut want = java(&expected);
+        got.sort();
+        want.sort();
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn written_diagrams_are_read_back() {
+        let tokens = tokenize(ANIMALS, "animals.md").unwrap();
+        let ast = generate::parse(&tokens).unwrap();
+        let classes = generate::resolve(ast, false).unwrap();
+        let written = write(&classes);
+        assert!(written.starts_with("classDiagram\n    class Animal {\n        <<abstract>>\n        #String name\n        +List~String~ tags\n        +makeSound() void*\n        +create(String name) Animal$\n    }\n"));
+        assert!(written.contains("    Animal <|-- Duck\n    Swimmer <|.. Duck\n"));
+        assert!(written.contains("    Egg *-- Shell : nested\n"));
+        assert!(written.contains("    note for Duck \"Quacks\\nloudly\"\n"));
+
+        assert_eq!(generics("Map~String, List~int~~"), "Map<String, List<int>>");
+        assert!(written.contains("        -Egg[*] lays\n"));
+
+        let again = tokenize(&written, "-").unwrap();
+        let mut first = java(&tokens);
+        let mut second = java(&again);
+        first.sort();
+        second.sort();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn associations_become_attributes() {
+        let diagram = "classDiagram
+    class Box~K, V~ {
+        +get(K key) V
+    }
+    Box <|-- Cache
+    Cache \"1\" *-- \"many\" Entry
+    Cache o-- Clock : clock
+    Cache <-- Owner : cache
+    Cache .. Log
+    Cache ..> Metrics
+";
+        let tokens = tokenize(diagram, "-").unwrap();
+        let plantuml = "@startuml
+class Box {
+ + get(key : K) : V
+}
+class Cache {
+ - entry : Entry[*]
+ - clock : Clock
+}
+class Entry
+class Clock
+class Owner {
+ - cache : Cache
+}
+class Log
+class Metrics
+Box <|-- Cache
+@enduml";
+        let expected = tokenizer::get_identifiers_from_str(plantuml.to_string()).unwrap();
+        assert_eq!(java(&tokens), java(&expected));
+
+        let ast = generate::parse(&tokens).unwrap();
+        let written = write(&generate::resolve(ast, false).unwrap());
+        assert!(written.contains("    class Cache {\n        -Entry[*] entry\n"));
+        assert_eq!(type_parameters(" Box~K, V~ {"), Some("<K, V>".to_string()));
+    }
+}
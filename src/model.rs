@@ -4,6 +4,8 @@
 // This source code is licensed under the BSD-style license found in the
 // LICENSE file in the root directory of this source tree.
 
+use serde::{Deserialize, Serialize};
+
 #[derive(Debug, Clone)]
 pub struct Class<'a> {
     pub(crate) name: &'a str,
@@ -12,6 +14,17 @@ pub struct Class<'a> {
     view: View,
     is_abstract: bool,
     inherits: Option<Box<Class<'a>>>,
+    kind: ClassKind,
+    is_static: bool,
+    constants: Vec<&'a str>,
+    inner: Vec<Class<'a>>,
+    implements: Vec<Class<'a>>,
+    sealing: Sealing,
+    /// Direct subtypes, the permitted ones if the class is sealed
+    permits: Vec<&'a str>,
+    accessors: Accessors,
+    doc: Option<&'a str>,
+    span: Option<Span<'a>>,
 }
 
 impl<'a> Class<'a> {
@@ -30,6 +43,16 @@ impl<'a> Class<'a> {
             view,
             is_abstract,
             inherits,
+            kind: ClassKind::Class,
+            is_static: false,
+            constants: Vec::new(),
+            inner: Vec::new(),
+            implements: Vec::new(),
+            sealing: Sealing::Open,
+            permits: Vec::new(),
+            accessors: Accessors::None,
+            doc: None,
+            span: None,
         }
     }
 
@@ -47,6 +70,7 @@ impl<'a> Class<'a> {
         self
     }
 
+    #[allow(dead_code)]
     pub fn inherits(mut self, class: Class<'a>) -> Self {
         self.inherits = Some(Box::new(class));
         self
@@ -57,105 +81,172 @@ impl<'a> Class<'a> {
         self
     }
 
-    pub fn to_java(&self) -> String {
-        let mut str = String::new();
+    pub fn with_kind(mut self, kind: ClassKind) -> Self {
+        self.kind = kind;
+        self
+    }
 
-        // top name and class dfinition
-        str.push_str(self.view.to_java());
-        str.push(' ');
-        if self.is_abstract {
-            str.push_str("abstract ");
+    pub fn with_static(mut self, is_static: bool) -> Self {
+        self.is_static = is_static;
+        self
+    }
+
+    pub fn with_constant(mut self, constant: &'a str) -> Self {
+        self.constants.push(constant);
+        self
+    }
+
+    pub fn with_span(mut self, span: Option<Span<'a>>) -> Self {
+        self.span = span;
+        self
+    }
+
+    pub fn with_sealing(mut self, sealing: Sealing) -> Self {
+        self.sealing = sealing;
+        self
+    }
+
+    pub fn add_inner(&mut self, class: Class<'a>) {
+        self.inner.push(class);
+    }
+
+    pub fn set_name(&mut self, name: &'a str) {
+        self.name = name;
+    }
+
+    /// Adds the text of a note to the class or all of its members with that name,
+    /// returns false if there is no such member
+    pub fn add_doc(&mut self, member: Option<&str>, doc: &'a str) -> bool {
+        let Some(member) = member else {
+            self.doc = Some(doc);
+            return true;
+        };
+        let mut found = false;
+        for attr in self.attributes.iter_mut().filter(|a| a.name == member) {
+            attr.doc = Some(doc);
+            found = true;
+        }
+        for method in self.methods.iter_mut().filter(|f| f.name == member) {
+            method.doc = Some(doc);
+            found = true;
         }
-        str.push_str("class ");
-        str.push_str(self.name);
-        str.push(' ');
-        if let Some(s) = &self.inherits {
-            str.push_str("extends ");
-            str.push_str(s.name);
-            str.push(' ');
+        found
+    }
+
+    pub fn set_accessors(&mut self, accessors: Accessors) {
+        self.accessors = accessors;
+        for class in self.inner.iter_mut() {
+            class.set_accessors(accessors);
         }
-        str.push('{');
-        str.push_str("\n");
+    }
 
-        // attrbutes
-        for p in self.attributes.iter() {
-            str.push_str("    ");
-            str.push_str(&p.to_java_as_attribute());
-            str.push_str("\n");
+    /// Replaces every type of the class, its members and inner classes
+    pub fn map_types<F>(&mut self, f: &F)
+    where
+        F: Fn(Type<'a>) -> Type<'a>,
+    {
+        for attr in self.attributes.iter_mut() {
+            attr.vartype = f(attr.vartype);
+        }
+        for method in self.methods.iter_mut() {
+            method.map_types(f);
+        }
+        if let Some(class) = self.inherits.as_mut() {
+            class.map_types(f);
         }
+        for class in self.implements.iter_mut().chain(self.inner.iter_mut()) {
+            class.map_types(f);
+        }
+    }
 
-        // constructor func
-        // top row
-        str.push('\n');
-        str.push_str("    ");
-        str.push_str(&self.get_constructor_func().to_java());
-        str.pop();
-        str.push('\n');
-        // super func if inherits
-        if let Some(class) = &self.inherits {
-            str.push_str("        super(");
-            for attr in class.attributes.iter() {
-                str.push_str(&attr.name);
-                str.push(',');
-                str.push(' ');
+    /// The accessors which have to be generated for the attributes
+    pub fn accessors(&self) -> Vec<Accessor<'a>> {
+        let mut accessors = Vec::new();
+        if self.accessors == Accessors::None {
+            return accessors;
+        }
+        for p in self.attributes.iter() {
+            if matches!(p.view, View::Public) || p.is_static {
+                continue;
             }
-            if class.attributes.len() > 0 {
-                str.pop();
-                str.pop();
+            let mut property = p.name.to_string();
+            if let Some(first) = property.get(..1) {
+                property.replace_range(..1, &first.to_uppercase());
+            }
+            let prefix = match p.vartype {
+                Type::Other("boolean") => "is",
+                _ => "get",
+            };
+
+            let getter = format!("{}{}", prefix, property);
+            // skip accessors which are already declared in the diagram
+            if !self.methods.iter().any(|f| f.name == getter) {
+                accessors.push(Accessor {
+                    name: getter,
+                    is_getter: true,
+                    field: *p,
+                });
+            }
+            let setter = format!("set{}", property);
+            if self.accessors == Accessors::GettersAndSetters
+                && !p.is_final
+                && !self.methods.iter().any(|f| f.name == setter)
+            {
+                accessors.push(Accessor {
+                    name: setter,
+                    is_getter: false,
+                    field: *p,
+                });
             }
-            str.push(')');
-            str.push(';');
         }
-        str.push('\n');
+        accessors
+    }
 
-        // normal this. thisngs
-        for p in self.attributes.iter() {
-            str.push_str("        ");
-            str.push_str(&format!("this.{} = {};", p.name, p.name));
-            str.push_str("\n");
+    /// Abstract methods of the super class and methods of all interfaces which
+    /// aren't declared in the diagram
+    pub fn inherited_methods(&self) -> Vec<Function<'a>> {
+        let mut methods = Vec::new();
+        if self.is_abstract {
+            return methods;
         }
-        str.push_str("    ");
-        str.push('}');
-        str.push_str("\n\n");
-
-        // methods
-        for f in self.methods.iter() {
-            str.push_str("    ");
-            str.push_str(&f.to_java());
-            str.push_str("\n");
+        let mut inherited = Vec::new();
+        if let Some(class) = &self.inherits {
+            inherited.extend(class.methods.iter().filter(|f| f.is_abstract));
+        }
+        for class in self.implements.iter() {
+            inherited.extend(class.methods.iter().filter(|f| !f.is_static));
         }
 
-        str.push_str("\n");
-
-        // inherited methods
-        if let Some(class) = &self.inherits {
-            for af in class.methods.iter() {
-                if af.is_abstract {
-                    let mut afp = af.clone();
-                    afp.is_abstract = false;
-                    str.push_str("    ");
-                    str.push_str(&afp.to_java());
-                    str.push_str("\n");
-                }
+        for af in inherited {
+            if self.methods.iter().any(|f| f.overrides(af)) {
+                continue;
             }
+            let mut afp = af.clone();
+            afp.is_abstract = false;
+            afp.view = View::Public;
+            methods.push(afp);
         }
+        methods
+    }
 
-        str.push('}');
-        str.push('\n');
-
-        str
+    /// Attributes which belong to the objects, not to the class
+    pub fn instance_attributes(&self) -> Vec<Attribute<'a>> {
+        self.attributes
+            .iter()
+            .filter(|a| !a.is_static)
+            .copied()
+            .collect()
     }
 
+    /// Constructor taking all instance attributes, followed by the ones of the super class
     pub fn get_constructor_func(&self) -> Function<'a> {
-        let mut attributes = self.attributes.clone();
+        let mut attributes = self.instance_attributes();
         if let Some(class) = &self.inherits {
-            for attr in class.attributes.iter() {
-                attributes.push(*attr);
-            }
+            attributes.extend(class.instance_attributes());
         }
         Function::new(
             self.name,
-            self.view.clone(),
+            self.view,
             Type::Other(""),
             attributes,
             false,
@@ -167,9 +258,273 @@ impl<'a> Class<'a> {
         self.inherits = Some(Box::new(master));
     }
 
-    pub fn get_inherits(&self) -> Option<Box<Class>> {
+    pub fn add_implements(&mut self, interface: Class<'a>) {
+        self.implements.push(interface);
+    }
+
+    pub fn inner(&self) -> &[Class<'a>] {
+        &self.inner
+    }
+
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    pub fn attributes(&self) -> &[Attribute<'a>] {
+        &self.attributes
+    }
+
+    pub fn methods(&self) -> &[Function<'a>] {
+        &self.methods
+    }
+
+    pub fn visibility(&self) -> View {
+        self.view
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.is_static
+    }
+
+    /// Constants of an enum
+    pub fn constants(&self) -> &[&'a str] {
+        &self.constants
+    }
+
+    /// The super class
+    pub fn extends(&self) -> Option<&Class<'a>> {
+        self.inherits.as_deref()
+    }
+
+    /// Interfaces, for an interface the ones it extends
+    pub fn implements(&self) -> &[Class<'a>] {
+        &self.implements
+    }
+
+    /// Classes which may extend a sealed class
+    pub fn permits(&self) -> &[&'a str] {
+        match self.sealing {
+            Sealing::Sealed => &self.permits,
+            _ => &[],
+        }
+    }
+
+    /// Classes which directly extend or implement this one
+    pub fn subtypes(&self) -> &[&'a str] {
+        &self.permits
+    }
+
+    pub fn doc(&self) -> Option<&'a str> {
+        self.doc
+    }
+
+    pub fn span(&self) -> Option<Span<'a>> {
+        self.span
+    }
+
+    pub fn get_kind(&self) -> ClassKind {
+        self.kind
+    }
+
+    pub fn is_abstract(&self) -> bool {
+        self.is_abstract
+    }
+
+    /// Whether this class directly extends or implements the class named master
+    pub fn is_subtype_of(&self, master: &str) -> bool {
+        self.inherits.as_ref().is_some_and(|c| c.name == master)
+            || self.implements.iter().any(|c| c.name == master)
+    }
+
+    pub fn get_sealing(&self) -> Sealing {
+        self.sealing
+    }
+
+    pub fn set_sealing(&mut self, sealing: Sealing, permits: &[&'a str]) {
+        self.sealing = sealing;
+        self.permits = permits.to_vec();
+    }
+
+    #[allow(dead_code)]
+    pub fn get_inherits(&self) -> Option<Box<Class<'a>>> {
         self.inherits.clone()
     }
+
+    /// Serialisable representation of the class as it is generated
+    pub fn view(&self) -> ClassView<'a> {
+        let mut constructor = self.get_constructor_func().view(false);
+        if self.kind == ClassKind::Enum {
+            // enum constructors are always private
+            constructor.visibility = View::Private.as_str();
+        }
+        let in_interface = self.kind == ClassKind::Interface;
+        ClassView {
+            name: self.name.to_string(),
+            span: self.span,
+            doc: lines(self.doc),
+            kind: self.kind.as_str(),
+            visibility: self.view.as_str(),
+            is_abstract: self.is_abstract && self.kind == ClassKind::Class,
+            is_static: self.is_static && self.kind == ClassKind::Class,
+            sealing: self.sealing.as_str(),
+            extends: self.inherits.as_ref().map(|c| c.name.to_string()),
+            super_args: self.inherits.as_ref().map(|c| {
+                c.instance_attributes()
+                    .iter()
+                    .map(|a| a.name.to_string())
+                    .collect()
+            }),
+            implements: self.implements.iter().map(|c| c.name.to_string()).collect(),
+            permits: self.permits().iter().map(|p| p.to_string()).collect(),
+            constants: self.constants.iter().map(|c| c.to_string()).collect(),
+            fields: self.attributes.iter().map(|a| a.view()).collect(),
+            constructor,
+            methods: self.methods.iter().map(|f| f.view(in_interface)).collect(),
+            accessors: self
+                .accessors()
+                .into_iter()
+                .map(|a| AccessorView {
+                    name: a.name,
+                    kind: if a.is_getter { "getter" } else { "setter" },
+                    field: a.field.view(),
+                })
+                .collect(),
+            inherited_methods: self
+                .inherited_methods()
+                .iter()
+                .map(|f| f.view(false))
+                .collect(),
+            inner: self.inner.iter().map(|c| c.view()).collect(),
+        }
+    }
+}
+
+/// Lines of the documentation, the templates write them into Javadoc comments
+fn lines(doc: Option<&str>) -> Vec<String> {
+    doc.iter()
+        .flat_map(|d| d.lines())
+        .map(crate::backend::escape_comment_end)
+        .collect()
+}
+
+impl Serialize for Class<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.view().serialize(serializer)
+    }
+}
+
+/// Getter or setter of an attribute
+#[derive(Debug, Clone)]
+pub struct Accessor<'a> {
+    pub name: String,
+    pub is_getter: bool,
+    pub field: Attribute<'a>,
+}
+
+/// A class with everything needed to write it, used for templates
+#[derive(Debug, Serialize)]
+pub struct ClassView<'a> {
+    pub name: String,
+    pub span: Option<Span<'a>>,
+    /// Lines of the notes attached to the class
+    pub doc: Vec<String>,
+    pub kind: &'static str,
+    pub visibility: &'static str,
+    pub is_abstract: bool,
+    pub is_static: bool,
+    /// "sealed", "non-sealed", "final" or empty
+    pub sealing: &'static str,
+    pub extends: Option<String>,
+    /// Names of the attributes passed to the super constructor
+    pub super_args: Option<Vec<String>>,
+    pub implements: Vec<String>,
+    pub permits: Vec<String>,
+    pub constants: Vec<String>,
+    pub fields: Vec<FieldView<'a>>,
+    pub constructor: MethodView<'a>,
+    pub methods: Vec<MethodView<'a>>,
+    pub accessors: Vec<AccessorView<'a>>,
+    /// Methods of the super class and interfaces which have to be implemented
+    pub inherited_methods: Vec<MethodView<'a>>,
+    pub inner: Vec<ClassView<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FieldView<'a> {
+    pub name: String,
+    pub span: Option<Span<'a>>,
+    pub doc: Vec<String>,
+    #[serde(rename = "type")]
+    pub vartype: String,
+    pub visibility: &'static str,
+    pub is_final: bool,
+    pub is_static: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MethodView<'a> {
+    pub name: String,
+    pub span: Option<Span<'a>>,
+    pub doc: Vec<String>,
+    pub return_type: String,
+    pub visibility: &'static str,
+    pub is_abstract: bool,
+    pub is_static: bool,
+    pub has_body: bool,
+    pub parameters: Vec<FieldView<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccessorView<'a> {
+    pub name: String,
+    /// "getter" or "setter"
+    pub kind: &'static str,
+    pub field: FieldView<'a>,
+}
+
+/// File and line (starting at 1) something is declared in
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Span<'a> {
+    pub file: &'a str,
+    pub line: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClassKind {
+    Class,
+    Interface,
+    Enum,
+    Record,
+}
+
+impl ClassKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Class => "class",
+            Self::Interface => "interface",
+            Self::Enum => "enum",
+            Self::Record => "record",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sealing {
+    Open,
+    Sealed,
+    NonSealed,
+    Final,
+}
+
+impl Sealing {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Open => "",
+            Self::Sealed => "sealed",
+            Self::NonSealed => "non-sealed",
+            Self::Final => "final",
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -178,6 +533,9 @@ pub struct Attribute<'a> {
     name: &'a str,
     vartype: Type<'a>,
     is_final: bool,
+    is_static: bool,
+    doc: Option<&'a str>,
+    span: Option<Span<'a>>,
 }
 
 impl<'a> Attribute<'a> {
@@ -187,34 +545,60 @@ impl<'a> Attribute<'a> {
             name,
             vartype,
             is_final,
+            is_static: false,
+            doc: None,
+            span: None,
         }
     }
 
-    fn to_java_as_parameter(&self) -> String {
-        let mut str = String::new();
-        if self.is_final {
-            str.push_str("final ");
-        }
-        str.push_str(self.vartype.to_java());
-        str.push(' ');
-        str.push_str(self.name);
-        str.push_str(", ");
-        str
+    pub fn with_span(mut self, span: Option<Span<'a>>) -> Self {
+        self.span = span;
+        self
     }
 
-    fn to_java_as_attribute(&self) -> String {
-        let mut str = String::new();
-        if self.is_final {
-            str.push_str("final ");
-        }
-        str.push_str(self.view.to_java());
-        str.push(' ');
-        str.push_str(self.vartype.to_java());
-        str.push(' ');
-        str.push_str(self.name);
-        str.push(';');
+    pub fn with_static(mut self, is_static: bool) -> Self {
+        self.is_static = is_static;
+        self
+    }
+
+    pub fn name(self) -> &'a str {
+        self.name
+    }
+
+    pub fn visibility(self) -> View {
+        self.view
+    }
 
-        str
+    pub fn vartype(self) -> Type<'a> {
+        self.vartype
+    }
+
+    pub fn is_final(self) -> bool {
+        self.is_final
+    }
+
+    pub fn is_static(self) -> bool {
+        self.is_static
+    }
+
+    pub fn doc(self) -> Option<&'a str> {
+        self.doc
+    }
+
+    pub fn span(self) -> Option<Span<'a>> {
+        self.span
+    }
+
+    fn view(self) -> FieldView<'a> {
+        FieldView {
+            name: self.name.to_string(),
+            span: self.span,
+            doc: lines(self.doc),
+            vartype: self.vartype.to_string(),
+            visibility: self.view.as_str(),
+            is_final: self.is_final,
+            is_static: self.is_static,
+        }
     }
 }
 
@@ -226,6 +610,8 @@ pub struct Function<'a> {
     parameters: Vec<Attribute<'a>>,
     is_abstract: bool,
     is_static: bool,
+    doc: Option<&'a str>,
+    span: Option<Span<'a>>,
 }
 
 impl<'a> Function<'a> {
@@ -244,54 +630,113 @@ impl<'a> Function<'a> {
             parameters: paremeters,
             is_abstract,
             is_static,
+            doc: None,
+            span: None,
         }
     }
 
-    pub fn to_java(&self) -> String {
-        let mut str = String::new();
-        str.push_str(self.view.to_java());
-        str.push(' ');
-        if self.is_abstract {
-            str.push_str("abstract");
-            str.push(' ');
-        }
-        if self.is_static {
-            str.push_str("static");
-            str.push(' ');
-        }
-        str.push_str(self.returntype.to_java());
-        str.push(' ');
-        str.push_str(self.name);
-        str.push('(');
-        for p in self.parameters.iter() {
-            str.push_str(&p.to_java_as_parameter());
-        }
-        if self.parameters.len() > 0 {
-            str.pop();
-            str.pop();
+    pub fn with_span(mut self, span: Option<Span<'a>>) -> Self {
+        self.span = span;
+        self
+    }
+
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    pub fn visibility(&self) -> View {
+        self.view
+    }
+
+    pub fn return_type(&self) -> Type<'a> {
+        self.returntype
+    }
+
+    pub fn parameters(&self) -> &[Attribute<'a>] {
+        &self.parameters
+    }
+
+    pub fn is_abstract(&self) -> bool {
+        self.is_abstract
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.is_static
+    }
+
+    pub fn doc(&self) -> Option<&'a str> {
+        self.doc
+    }
+
+    pub fn span(&self) -> Option<Span<'a>> {
+        self.span
+    }
+
+    /// Interface methods are only marked static and have no body unless they are static
+    fn view(&self, in_interface: bool) -> MethodView<'a> {
+        MethodView {
+            name: self.name.to_string(),
+            span: self.span,
+            doc: lines(self.doc),
+            return_type: self.returntype.to_string(),
+            visibility: self.view.as_str(),
+            is_abstract: self.is_abstract && !in_interface,
+            is_static: self.is_static,
+            has_body: match in_interface {
+                true => self.is_static,
+                false => !self.is_abstract,
+            },
+            parameters: self.parameters.iter().map(|p| p.view()).collect(),
         }
-        match self.is_abstract {
-            true => str.push_str(");"),
-            false => str.push_str(") {\n    }"),
+    }
+
+    fn map_types<F>(&mut self, f: &F)
+    where
+        F: Fn(Type<'a>) -> Type<'a>,
+    {
+        self.returntype = f(self.returntype);
+        for p in self.parameters.iter_mut() {
+            p.vartype = f(p.vartype);
         }
+    }
 
-        str
+    /// Whether both methods have the same name and parameter types
+    pub fn overrides(&self, other: &Function) -> bool {
+        self.name == other.name
+            && self.parameters.len() == other.parameters.len()
+            && self
+                .parameters
+                .iter()
+                .zip(other.parameters.iter())
+                .all(|(a, b)| a.vartype.to_string() == b.vartype.to_string())
     }
 }
 
 #[derive(Copy, Clone, Debug)]
 pub enum Type<'a> {
     Other(&'a str),
+    /// Collection type and the type of its elements
+    Collection(&'a str, &'a str),
 }
 
-impl<'a> Type<'a> {
-    pub fn to_java(&self) -> &'a str {
+impl std::fmt::Display for Type<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Other(s) => s,
+            Self::Other(s) => write!(f, "{}", s),
+            Self::Collection(c, e) => write!(f, "{}<{}>", c, e),
         }
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Accessors {
+    #[default]
+    None,
+    Getters,
+    GettersAndSetters,
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum View {
     Normal,
@@ -301,7 +746,7 @@ pub enum View {
 }
 
 impl View {
-    pub fn to_java<'a>(&self) -> &'a str {
+    pub fn as_str(self) -> &'static str {
         match self {
             Self::Normal => "",
             Self::Public => "public",
@@ -310,14 +755,3 @@ impl View {
         }
     }
 }
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
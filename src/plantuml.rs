@@ -0,0 +1,209 @@
+// Copyright (c) 2023, Oskar Ohlenmacher
+// All rights reserved
+//
+// This source code is licensed under the BSD-style license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Writes the model as a PlantUML class diagram.
+
+use crate::model::{Attribute, Class, ClassKind, Function, Sealing, View};
+
+/// Writes the classes as one PlantUML diagram, declarations come first followed
+/// by the relations and notes
+pub fn write(classes: &[Class]) -> String {
+    let mut str = String::from("@startuml\n");
+    let mut relations = Vec::new();
+    let mut notes = Vec::new();
+    for class in classes.iter() {
+        push_class(class, &mut str, &mut relations, &mut notes);
+    }
+    for line in relations.iter().chain(notes.iter()) {
+        str.push_str(line);
+        str.push('\n');
+    }
+    str.push_str("@enduml\n");
+    str
+}
+
+fn push_class(
+    class: &Class,
+    str: &mut String,
+    relations: &mut Vec<String>,
+    notes: &mut Vec<String>,
+) {
+    let kind = match class.get_kind() {
+        ClassKind::Class if class.is_abstract() => "abstract class",
+        kind => kind.as_str(),
+    };
+    str.push_str(&format!("{} {}", kind, class.name()));
+    if class.is_static() {
+        str.push_str(" <<static>>");
+    }
+    if class.get_sealing() == Sealing::Sealed {
+        str.push_str(" <<sealed>>");
+    }
+
+    let mut members: Vec<String> = class.constants().iter().map(|c| c.to_string()).collect();
+    members.extend(class.attributes().iter().map(|a| attribute(*a)));
+    members.extend(class.methods().iter().map(method));
+    match members.is_empty() {
+        true => str.push('\n'),
+        false => {
+            str.push_str(" {\n");
+            for member in members.iter() {
+                str.push_str(&format!("  {}\n", member));
+            }
+            str.push_str("}\n");
+        }
+    }
+
+    if let Some(master) = class.extends() {
+        relations.push(format!("{} <|-- {}", master.name(), class.name()));
+    }
+    for interface in class.implements().iter() {
+        let arrow = match class.get_kind() {
+            ClassKind::Interface => "<|--",
+            _ => "<|..",
+        };
+        relations.push(format!("{} {} {}", interface.name(), arrow, class.name()));
+    }
+    if let Some(doc) = class.doc() {
+        notes.push(format!("note top of {} : {}", class.name(), note_text(doc)));
+    }
+    for a in class.attributes().iter() {
+        if let Some(doc) = a.doc() {
+            notes.push(member_note(class, a.name(), doc));
+        }
+    }
+    for f in class.methods().iter() {
+        if let Some(doc) = f.doc() {
+            notes.push(member_note(class, f.name(), doc));
+        }
+    }
+    for inner in class.inner().iter() {
+        relations.push(format!("{} +-- {}", class.name(), inner.name()));
+        push_class(inner, str, relations, notes);
+    }
+}
+
+fn attribute(a: Attribute) -> String {
+    let mut str = visibility(a.visibility()).to_string();
+    if a.is_static() {
+        str.push_str("{static} ");
+    }
+    str.push_str(&format!("{} : {}", a.name(), a.vartype()));
+    str
+}
+
+fn method(f: &Function) -> String {
+    let mut str = visibility(f.visibility()).to_string();
+    if f.is_static() {
+        str.push_str("{static} ");
+    }
+    if f.is_abstract() {
+        str.push_str("{abstract} ");
+    }
+    let parameters: Vec<String> = f
+        .parameters()
+        .iter()
+        .map(|p| format!("{} : {}", p.name(), p.vartype()))
+        .collect();
+    str.push_str(&format!("{}({})", f.name(), parameters.join(", ")));
+    let return_type = f.return_type().to_string();
+    if !return_type.is_empty() {
+        str.push_str(" : ");
+        str.push_str(&return_type);
+    }
+    str
+}
+
+fn member_note(class: &Class, member: &str, doc: &str) -> String {
+    format!(
+        "note right of {}::{} : {}",
+        class.name(),
+        member,
+        note_text(doc)
+    )
+}
+
+/// Text of a single line note, a `'` would start a comment
+fn note_text(doc: &str) -> String {
+    doc.replace('\'', "’").replace('\n', "\\n")
+}
+
+fn visibility(view: View) -> &'static str {
+    match view {
+        View::Public => "+ ",
+        View::Private => "- ",
+        View::Protected => "# ",
+        View::Normal => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{Backend, Java};
+    use crate::generate;
+    use crate::tokenizer;
+
+    #[test]
+    fn written_diagrams_are_read_back() {
+        let input = "@startuml
+abstract class Shape <<sealed>> {
+ # id : long
+ + {abstract} area() : double
+}
+class Circle {
+ - r : double
+ + {static} unit : Circle
+ + area() : double
+ + scale(by : double, around : Point) : Circle
+}
+interface Named
+enum Color {
+ RED
+ GREEN
+}
+class Outer
+class Inner <<static>>
+Shape <|-- Circle
+Named <|.. Circle
+Outer +-- Inner
+note top of Circle : A circle\\nround
+note right of Circle::area : Computes
+@enduml";
+        let tokens = tokenizer::get_identifiers_from_str(input.to_string()).unwrap();
+        let classes = generate::resolve(generate::parse(&tokens).unwrap(), false).unwrap();
+        let written = write(&classes);
+        assert!(written.starts_with(
+            "@startuml
+abstract class Shape <<sealed>> {
+  # id : long
+  + {abstract} area() : double
+}
+class Circle {
+  - r : double
+  + {static} unit : Circle
+  + area() : double
+  + scale(by : double, around : Point) : Circle
+}
+"
+        ));
+        assert!(written.ends_with(
+            "Shape <|-- Circle
+Named <|.. Circle
+Outer +-- Inner
+note top of Circle : A circle\\nround
+note right of Circle::area : Computes
+@enduml
+"
+        ));
+
+        let again = tokenizer::get_identifiers_from_str(written).unwrap();
+        let again = generate::resolve(generate::parse(&again).unwrap(), false).unwrap();
+        let java =
+            |classes: &[Class]| -> Vec<String> { classes.iter().map(|c| Java.class(c)).collect() };
+        assert_eq!(java(&classes), java(&again));
+    }
+}
@@ -0,0 +1,647 @@
+// Copyright (c) 2023, Oskar Ohlenmacher
+// All rights reserved
+//
+// This source code is licensed under the BSD-style license found in the
+// LICENSE file in the root directory of this source tree.
+
+// see: https://plantuml.com/preprocessing
+//
+// Supported:
+//      !include, !include_many, !include_once for local files
+//      !define, !undef, !ifdef, !ifndef
+//      !$var = value, !$var ?= value
+//      !if, !elseif, !else, !endif
+//      !procedure, !endprocedure
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::{debug, info, warn};
+
+#[derive(Debug, Clone)]
+struct Define {
+    parameters: Option<Vec<String>>,
+    body: String,
+}
+
+#[derive(Debug, Clone)]
+struct Procedure {
+    parameters: Vec<String>,
+    body: Vec<String>,
+}
+
+/// State of one !if block
+struct Condition {
+    active: bool,
+    /// if any branch of the block was taken already
+    taken: bool,
+}
+
+#[derive(Default)]
+pub struct Preprocessor {
+    /// in the order they are defined, so expanding them is deterministic
+    defines: Vec<(String, Define)>,
+    variables: HashMap<String, String>,
+    procedures: HashMap<String, Procedure>,
+
+    /// files which are currently being included, to detect cycles
+    stack: Vec<PathBuf>,
+    /// every file which was read
+    files: Vec<PathBuf>,
+    /// index of the file and line every output line comes from
+    sources: Vec<(Option<usize>, usize)>,
+    /// line which is currently processed
+    line: usize,
+    /// line of the procedure call which is currently expanded
+    call_site: Option<usize>,
+}
+
+impl Preprocessor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every file which was read, including the ones pulled in by !include
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// File and line (starting at 1) of every output line, there is no file for
+    /// text which wasn't read from one
+    pub fn sources(&self) -> impl Iterator<Item = (Option<&Path>, usize)> {
+        self.sources
+            .iter()
+            .map(|(file, line)| (file.map(|i| self.files[i].as_path()), *line))
+    }
+
+    pub fn process_file(&mut self, filepath: &Path) -> Result<String, PreprocessError> {
+        let mut out = String::new();
+        self.include(filepath, &mut out)?;
+        Ok(out)
+    }
+
+    /// Processes text which isn't read from a file, includes are relative to dir
+    pub fn process_str(&mut self, text: &str, dir: &Path) -> Result<String, PreprocessError> {
+        let mut out = String::new();
+        let lines: Vec<String> = text.lines().map(|l| l.to_string()).collect();
+        self.process_lines(&lines, dir, &mut out)?;
+        Ok(out)
+    }
+
+    fn include(&mut self, filepath: &Path, out: &mut String) -> Result<(), PreprocessError> {
+        let path = fs::canonicalize(filepath)
+            .map_err(|e| PreprocessError::Io(filepath.to_path_buf(), e))?;
+        if self.stack.contains(&path) {
+            let mut chain: Vec<PathBuf> = self.stack.clone();
+            chain.push(path);
+            return Err(PreprocessError::IncludeCycle(chain));
+        }
+
+        let text =
+            fs::read_to_string(&path).map_err(|e| PreprocessError::Io(path.to_path_buf(), e))?;
+        info!("Preprocessing {}", path.display());
+        let dir = path.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+
+        self.files.push(path.clone());
+        self.stack.push(path);
+        let lines: Vec<String> = text.lines().map(|l| l.to_string()).collect();
+        let result = self.process_lines(&lines, &dir, out);
+        self.stack.pop();
+        result
+    }
+
+    fn process_lines(
+        &mut self,
+        lines: &[String],
+        dir: &Path,
+        out: &mut String,
+    ) -> Result<(), PreprocessError> {
+        let mut conditions: Vec<Condition> = Vec::new();
+        // name and procedure which is currently being defined
+        let mut recording: Option<(String, Procedure)> = None;
+
+        for (line_number, line) in lines.iter().enumerate() {
+            let trimmed = line.trim();
+            // expanded procedures keep the line they are called from
+            if self.call_site.is_none() {
+                self.line = line_number + 1;
+            }
+
+            if let Some((name, procedure)) = recording.as_mut() {
+                if trimmed.starts_with("!endprocedure") {
+                    debug!("defined procedure {}", name);
+                    let (name, procedure) = recording.take().unwrap();
+                    self.procedures.insert(name, procedure);
+                } else {
+                    procedure.body.push(line.clone());
+                }
+                continue;
+            }
+
+            let active = conditions.iter().all(|c| c.active);
+            let (directive, rest) = match trimmed.strip_prefix('!') {
+                Some(d) => d.split_once(char::is_whitespace).unwrap_or((d, "")),
+                None => {
+                    if active {
+                        self.push_line(line, dir, out)?;
+                    }
+                    continue;
+                }
+            };
+            let rest = rest.trim();
+
+            match directive {
+                "ifdef" | "ifndef" => {
+                    let defined = self.is_defined(rest) || self.variables.contains_key(rest);
+                    let result = defined == (directive == "ifdef");
+                    conditions.push(Condition {
+                        active: result,
+                        taken: result,
+                    });
+                }
+                "if" => {
+                    let result = self.evaluate(rest);
+                    conditions.push(Condition {
+                        active: result,
+                        taken: result,
+                    });
+                }
+                "elseif" => {
+                    let result = self.evaluate(rest);
+                    let condition = conditions.last_mut().ok_or_else(|| {
+                        PreprocessError::Unexpected("!elseif".to_string(), line.clone())
+                    })?;
+                    condition.active = !condition.taken && result;
+                    condition.taken |= result;
+                }
+                "else" => {
+                    let condition = conditions.last_mut().ok_or_else(|| {
+                        PreprocessError::Unexpected("!else".to_string(), line.clone())
+                    })?;
+                    condition.active = !condition.taken;
+                    condition.taken = true;
+                }
+                "endif" => {
+                    if conditions.pop().is_none() {
+                        return Err(PreprocessError::Unexpected(
+                            "!endif".to_string(),
+                            line.clone(),
+                        ));
+                    }
+                }
+                _ if !active => (),
+                "include" | "include_many" | "include_once" => {
+                    let filename = rest.trim_matches('"');
+                    if filename.starts_with('<') || filename.contains("://") {
+                        return Err(PreprocessError::UnsupportedInclude(filename.to_string()));
+                    }
+                    let path = dir.join(filename);
+                    let canonical = fs::canonicalize(&path).ok();
+                    let in_cycle = canonical.as_ref().is_some_and(|p| self.stack.contains(p));
+                    let included = canonical.as_ref().is_some_and(|p| self.files.contains(p));
+                    match (directive, included) {
+                        // include reports the cycle
+                        _ if in_cycle => self.include(&path, out)?,
+                        ("include_once", true) => {
+                            return Err(PreprocessError::IncludedTwice(path));
+                        }
+                        ("include", true) => {
+                            debug!("{} is already included", path.display());
+                        }
+                        _ => self.include(&path, out)?,
+                    }
+                }
+                "define" => {
+                    let (name, define) = parse_define(rest).ok_or_else(|| unclosed(trimmed))?;
+                    debug!("define {} = {:?}", name, define);
+                    match self.defines.iter_mut().find(|(n, _)| *n == name) {
+                        Some((_, existing)) => *existing = define,
+                        None => self.defines.push((name, define)),
+                    }
+                }
+                "undef" => {
+                    self.defines.retain(|(name, _)| name != rest);
+                }
+                "procedure" | "unquoted" => {
+                    let signature = rest.strip_prefix("procedure").unwrap_or(rest).trim();
+                    let (name, parameters) =
+                        parse_signature(signature).ok_or_else(|| unclosed(trimmed))?;
+                    let procedure = Procedure {
+                        parameters: parameters.unwrap_or_default(),
+                        body: Vec::new(),
+                    };
+                    recording = Some((name, procedure));
+                }
+                d if d.starts_with('$') => {
+                    let assignment = trimmed[1..].to_string();
+                    self.assign(&assignment);
+                }
+                d => warn!("Ignoring unsupported preprocessor directive !{}", d),
+            }
+        }
+
+        if let Some((name, _)) = recording {
+            return Err(PreprocessError::Unclosed(format!("!procedure {}", name)));
+        }
+        if !conditions.is_empty() {
+            return Err(PreprocessError::Unclosed("!if".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Writes a line with all variables and defines replaced, procedure calls are expanded
+    fn push_line(
+        &mut self,
+        line: &str,
+        dir: &Path,
+        out: &mut String,
+    ) -> Result<(), PreprocessError> {
+        let line = self.substitute(line);
+
+        let trimmed = line.trim();
+        if trimmed.starts_with('$') {
+            match parse_signature(trimmed) {
+                Some((name, arguments)) => {
+                    if let Some(procedure) = self.procedures.get(&name).cloned() {
+                        let arguments = arguments.unwrap_or_default();
+                        // arguments are variables only visible inside of the procedure
+                        let saved = self.variables.clone();
+                        for (p, a) in procedure.parameters.iter().zip(arguments.iter()) {
+                            self.variables.insert(p.clone(), unquote(a).to_string());
+                        }
+                        let call_site = self.call_site;
+                        self.call_site = call_site.or(Some(self.line));
+                        let result = self.process_lines(&procedure.body, dir, out);
+                        self.call_site = call_site;
+                        self.variables = saved;
+                        return result;
+                    }
+                }
+                None => warn!(
+                    "Line {}: the parenthesis of \"{}\" is never closed, it is not expanded",
+                    self.line, trimmed
+                ),
+            }
+        }
+        self.push_text(&line, out);
+        Ok(())
+    }
+
+    /// Writes the lines of text and remembers where they come from
+    fn push_text(&mut self, line: &str, out: &mut String) {
+        let file = self
+            .stack
+            .last()
+            .and_then(|path| self.files.iter().position(|f| f == path));
+        let source_line = self.call_site.unwrap_or(self.line);
+        // defines can span several lines
+        for _ in line.split('\n') {
+            self.sources.push((file, source_line));
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+
+    /// Handles "$name = value" and "$name ?= value"
+    fn assign(&mut self, assignment: &str) {
+        let (name, value, only_if_unset) = match assignment.split_once("?=") {
+            Some((name, value)) => (name, value, true),
+            None => match assignment.split_once('=') {
+                Some((name, value)) => (name, value, false),
+                None => (assignment, "", false),
+            },
+        };
+        let name = name.trim().to_string();
+        if only_if_unset && self.variables.contains_key(&name) {
+            return;
+        }
+        let value = unquote(&self.substitute(value.trim())).to_string();
+        debug!("{} = {:?}", name, value);
+        self.variables.insert(name, value);
+    }
+
+    /// Replaces variables and defines, the expansion of a define is expanded
+    /// again except for the define itself
+    fn substitute(&self, line: &str) -> String {
+        let mut line = line.to_string();
+
+        // longest names first so $ab is not replaced by $a
+        let mut variables: Vec<(&String, &String)> = self.variables.iter().collect();
+        variables.sort_by_key(|v| std::cmp::Reverse(v.0.len()));
+        for (name, value) in variables {
+            line = replace_word(&line, name, |_| Some(value.clone()));
+        }
+        self.expand_defines(&line, &mut Vec::new())
+    }
+
+    /// `expanding` are the defines whose expansion is being expanded, they stay
+    /// as they are like in cpp
+    fn expand_defines<'d>(&'d self, line: &str, expanding: &mut Vec<&'d str>) -> String {
+        let is_ident = |c: char| c.is_alphanumeric() || c == '_' || c == '$';
+        let mut str = String::new();
+        let mut rest = line;
+        while let Some(start) = rest.find(is_ident) {
+            str.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find(|c: char| !is_ident(c))
+                .map_or(rest.len(), |e| start + e);
+            let word = &rest[start..end];
+            rest = &rest[end..];
+
+            let define = self
+                .defines
+                .iter()
+                .find(|(name, _)| name == word && !expanding.contains(&name.as_str()));
+            let Some((name, define)) = define else {
+                str.push_str(word);
+                continue;
+            };
+            let body = match &define.parameters {
+                None => define.body.clone(),
+                // without a parenthesis right after it the name is just a word
+                Some(_) if !rest.starts_with('(') => {
+                    str.push_str(word);
+                    continue;
+                }
+                Some(parameters) => {
+                    let (Some(after), Some((_, Some(arguments)))) = (
+                        skip_parenthesis(rest),
+                        parse_signature(&format!("{}{}", name, rest)),
+                    ) else {
+                        warn!(
+                            "Line {}: the parenthesis of {} is never closed, it is not expanded",
+                            self.line, name
+                        );
+                        str.push_str(word);
+                        continue;
+                    };
+                    rest = after;
+                    let mut body = define.body.clone();
+                    for (p, a) in parameters.iter().zip(arguments.iter()) {
+                        body = replace_word(&body, p, |_| Some(unquote(a).to_string()));
+                    }
+                    body
+                }
+            };
+            expanding.push(name);
+            str.push_str(&self.expand_defines(&body, expanding));
+            expanding.pop();
+        }
+        str.push_str(rest);
+        str
+    }
+
+    /// Evaluates the condition of an !if, supports ==, !=, &&, || and !
+    fn evaluate(&self, expression: &str) -> bool {
+        let expression = self.substitute(expression);
+        expression.split("||").any(|part| {
+            part.split("&&").all(|term| {
+                let term = term.trim();
+                if let Some((a, b)) = term.split_once("!=") {
+                    unquote(a.trim()) != unquote(b.trim())
+                } else if let Some((a, b)) = term.split_once("==") {
+                    unquote(a.trim()) == unquote(b.trim())
+                } else if let Some(term) = term.strip_prefix('!') {
+                    !self.is_true(term.trim())
+                } else {
+                    self.is_true(term)
+                }
+            })
+        })
+    }
+
+    fn is_true(&self, term: &str) -> bool {
+        if let Some(name) = term
+            .strip_prefix("%defined(")
+            .or_else(|| term.strip_prefix("%variable_exists("))
+            .and_then(|t| t.strip_suffix(')'))
+        {
+            let name = unquote(name.trim());
+            return self.is_defined(name) || self.variables.contains_key(name);
+        }
+        !matches!(unquote(term), "" | "0" | "false" | "%false()")
+    }
+
+    fn is_defined(&self, name: &str) -> bool {
+        self.defines.iter().any(|(n, _)| n == name)
+    }
+}
+
+/// Replaces every whole word, replacement gets the text after the word and
+/// returns None if the word should stay
+fn replace_word<F>(line: &str, word: &str, replacement: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_' || c == '$';
+    let mut str = String::new();
+    let mut rest = line;
+
+    while let Some(pos) = rest.find(word) {
+        let before = rest[..pos].chars().last();
+        let after = rest[pos + word.len()..].chars().next();
+        let starts_word = !before.is_some_and(is_ident) || word.starts_with('$');
+        let ends_word = !after.is_some_and(is_ident);
+
+        str.push_str(&rest[..pos]);
+        let following = &rest[pos + word.len()..];
+        match (starts_word && ends_word)
+            .then(|| replacement(following))
+            .flatten()
+        {
+            Some(r) => {
+                str.push_str(&r);
+                // skip the arguments of macros
+                rest = match following.starts_with('(') {
+                    true => skip_parenthesis(following).unwrap_or(following),
+                    false => following,
+                };
+            }
+            None => {
+                str.push_str(word);
+                rest = following;
+            }
+        }
+    }
+    str.push_str(rest);
+    str
+}
+
+/// Returns the text after the closing parenthesis of s which starts with '(',
+/// None if it is never closed
+fn skip_parenthesis(s: &str) -> Option<&str> {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&s[i + 1..]);
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Splits "name(a, b)" into the name and its arguments, None if the parenthesis
+/// is never closed
+fn parse_signature(s: &str) -> Option<(String, Option<Vec<String>>)> {
+    let Some((name, _)) = s.split_once('(') else {
+        return Some((s.trim().to_string(), None));
+    };
+    let after = skip_parenthesis(&s[name.len()..])?;
+    let inner = &s[name.len() + 1..s.len() - after.len() - 1];
+    let arguments = inner
+        .split(',')
+        .map(|a| a.trim().to_string())
+        .filter(|a| !a.is_empty())
+        .collect();
+    Some((name.trim().to_string(), Some(arguments)))
+}
+
+/// Parses "NAME value" and "NAME(a, b) body", None if the parenthesis is never closed
+fn parse_define(s: &str) -> Option<(String, Define)> {
+    let name_end = s
+        .find(|c: char| c.is_whitespace() || c == '(')
+        .unwrap_or(s.len());
+    if s[name_end..].starts_with('(') {
+        let body = skip_parenthesis(&s[name_end..])?;
+        let (name, parameters) = parse_signature(&s[..s.len() - body.len()])?;
+        let define = Define {
+            parameters,
+            body: body.trim().to_string(),
+        };
+        Some((name, define))
+    } else {
+        let define = Define {
+            parameters: None,
+            body: s[name_end..].trim().to_string(),
+        };
+        Some((s[..name_end].to_string(), define))
+    }
+}
+
+fn unclosed(line: &str) -> PreprocessError {
+    PreprocessError::Unclosed(format!("The parenthesis of \"{}\"", line))
+}
+
+fn unquote(s: &str) -> &str {
+    s.strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(s)
+}
+
+#[derive(Debug)]
+pub enum PreprocessError {
+    Io(PathBuf, std::io::Error),
+    IncludeCycle(Vec<PathBuf>),
+    IncludedTwice(PathBuf),
+    UnsupportedInclude(String),
+    Unexpected(String, String),
+    Unclosed(String),
+}
+
+impl Error for PreprocessError {}
+
+impl std::fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(path, e) => write!(f, "Can't read {}: {}", path.display(), e),
+            Self::IncludeCycle(chain) => {
+                let chain: Vec<String> = chain.iter().map(|p| p.display().to_string()).collect();
+                write!(f, "Cyclic include: {}", chain.join(" -> "))
+            }
+            Self::IncludedTwice(path) => {
+                write!(f, "{} is included more than once", path.display())
+            }
+            Self::UnsupportedInclude(s) => write!(f, "Only local files can be included: {}", s),
+            Self::Unexpected(directive, line) => {
+                write!(f, "Unexpected {} in \"{}\"", directive, line)
+            }
+            Self::Unclosed(directive) => write!(f, "{} is never closed", directive),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(text: &str) -> String {
+        Preprocessor::new()
+            .process_str(text, Path::new("."))
+            .unwrap()
+    }
+
+    #[test]
+    fn defines_and_variables_are_replaced() {
+        let text = "!define TYPE int\n!define FIELD(n, t) - n : t\n!$name = \"count\"\nclass A {\n$name : TYPE\nFIELD(x, String)\n}";
+        assert_eq!(process(text), "class A {\ncount : int\n- x : String\n}\n");
+    }
+
+    #[test]
+    fn defines_expand_to_other_defines() {
+        let text = "!define T long\n!define U T\n!define V U\n!define W X\n!define X byte\nclass A {\n- v : V\n- u : U\n- w : W\n}";
+        assert_eq!(
+            process(text),
+            "class A {\n- v : long\n- u : long\n- w : byte\n}\n"
+        );
+        // a define isn't expanded again inside of its own expansion
+        assert_eq!(process("!define A A.x\nA"), "A.x\n");
+        assert_eq!(process("!define B C\n!define C B\nB C"), "B C\n");
+    }
+
+    #[test]
+    fn unclosed_parenthesis_is_not_expanded() {
+        let text =
+            "!define F(x) - x : int\n!procedure $f($n)\n- $n : int\n!endprocedure\nF(a\n$f(a\nF(b)";
+        assert_eq!(process(text), "F(a\n$f(a\n- b : int\n");
+        // the define is only called if the parenthesis follows its name
+        assert_eq!(
+            process("!define F(x) - x : int\nF and G(y)\nF (z)"),
+            "F and G(y)\nF (z)\n"
+        );
+
+        let result = Preprocessor::new().process_str("!define G(x - x", Path::new("."));
+        assert!(matches!(result, Err(PreprocessError::Unclosed(_))));
+    }
+
+    #[test]
+    fn only_taken_branches_are_kept() {
+        let text = "!$mode = \"full\"\n!if $mode == \"full\"\na\n!elseif $mode == \"small\"\nb\n!else\nc\n!endif\n!ifdef MISSING\nd\n!endif";
+        assert_eq!(process(text), "a\n");
+    }
+
+    #[test]
+    fn procedures_are_expanded() {
+        let text =
+            "!procedure $field($n, $t)\n- $n : $t\n!endprocedure\n$field(x, int)\n$field(y, long)";
+        assert_eq!(process(text), "- x : int\n- y : long\n");
+    }
+
+    #[test]
+    fn include_cycles_are_detected() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::write(dir.join("a.puml"), "!include b.puml\nclass A").unwrap();
+        fs::write(dir.join("b.puml"), "class B\n!include a.puml").unwrap();
+
+        let result = Preprocessor::new().process_file(&dir.join("a.puml"));
+        assert!(matches!(result, Err(PreprocessError::IncludeCycle(c)) if c.len() == 3));
+
+        fs::write(dir.join("b.puml"), "class B").unwrap();
+        fs::write(
+            dir.join("a.puml"),
+            "!include b.puml\n!include b.puml\nclass A",
+        )
+        .unwrap();
+        let mut preprocessor = Preprocessor::new();
+        let text = preprocessor.process_file(&dir.join("a.puml")).unwrap();
+        assert_eq!(text, "class B\nclass A\n");
+        assert_eq!(preprocessor.files().len(), 2);
+    }
+}
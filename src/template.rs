@@ -0,0 +1,279 @@
+// Copyright (c) 2023, Oskar Ohlenmacher
+// All rights reserved
+//
+// This source code is licensed under the BSD-style license found in the
+// LICENSE file in the root directory of this source tree.
+
+use crate::model::{Class, ClassKind};
+use log::debug;
+use minijinja::{context, AutoEscape, Environment, Value};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Templates which can be replaced by the user, the built-in ones write the
+/// same code as the java backend
+const BUILTIN: [(&str, &str); 7] = [
+    ("class.jinja", include_str!("../templates/class.jinja")),
+    (
+        "interface.jinja",
+        include_str!("../templates/interface.jinja"),
+    ),
+    ("enum.jinja", include_str!("../templates/enum.jinja")),
+    ("record.jinja", include_str!("../templates/record.jinja")),
+    ("field.jinja", include_str!("../templates/field.jinja")),
+    ("method.jinja", include_str!("../templates/method.jinja")),
+    ("javadoc.jinja", include_str!("../templates/javadoc.jinja")),
+];
+
+/// Renders classes with the templates of a directory, missing ones fall back to the
+/// built-in templates
+#[derive(Debug)]
+pub struct Templates {
+    env: Environment<'static>,
+}
+
+impl Templates {
+    pub fn load(dir: Option<&Path>) -> Result<Self, TemplateError> {
+        let mut env = Environment::new();
+        env.set_trim_blocks(true);
+        env.set_keep_trailing_newline(true);
+        env.set_auto_escape_callback(|_| AutoEscape::None);
+        env.set_undefined_behavior(minijinja::UndefinedBehavior::Strict);
+
+        for (name, builtin) in BUILTIN {
+            let path = dir.map(|d| d.join(name)).filter(|p| p.is_file());
+            let source = match &path {
+                Some(path) => {
+                    debug!("Using template {}", path.display());
+                    fs::read_to_string(path)
+                        .map_err(|e| TemplateError::Read(path.clone(), e.to_string()))?
+                }
+                None => builtin.to_string(),
+            };
+            env.add_template_owned(name, source)
+                .map_err(|e| TemplateError::Render(describe(&e)))?;
+        }
+        Ok(Templates { env })
+    }
+
+    pub fn render(&self, class: &Class) -> Result<String, TemplateError> {
+        let view = class.view();
+        let inner_classes = class
+            .inner()
+            .iter()
+            .map(|c| self.render(c).map(|java| indent(&java)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let name = match class.get_kind() {
+            ClassKind::Class => "class.jinja",
+            ClassKind::Interface => "interface.jinja",
+            ClassKind::Enum => "enum.jinja",
+            ClassKind::Record => "record.jinja",
+        };
+        self.env
+            .get_template(name)
+            .and_then(|t| {
+                t.render(context! {
+                    inner_classes,
+                    ..Value::from_serialize(&view)
+                })
+            })
+            .map_err(|e| TemplateError::Render(describe(&e)))
+    }
+}
+
+/// Indents every non empty line by one level, like nested classes of the java backend
+fn indent(s: &str) -> String {
+    let mut str = String::new();
+    for line in s.lines() {
+        if !line.is_empty() {
+            str.push_str("    ");
+            str.push_str(line);
+        }
+        str.push('\n');
+    }
+    str
+}
+
+/// The error with the template and line it happened in
+fn describe(e: &minijinja::Error) -> String {
+    let mut str = e.to_string();
+    if let Some(source) = e.source() {
+        str.push_str(&format!(": {}", source));
+    }
+    str
+}
+
+#[derive(Debug)]
+pub enum TemplateError {
+    Read(PathBuf, String),
+    Render(String),
+}
+
+impl std::fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read(path, e) => write!(f, "Couldn't read template {}: {}", path.display(), e),
+            Self::Render(e) => write!(f, "Template error {}", e),
+        }
+    }
+}
+
+impl Error for TemplateError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{Backend, Java};
+    use crate::generate::Options;
+    use crate::model::{Attribute, Function, Type, View};
+
+    fn person<'a>() -> Class<'a> {
+        let mut inner = Class::build("Kind", View::Public, false)
+            .with_kind(ClassKind::Enum)
+            .with_constant("ADULT")
+            .with_constant("CHILD")
+            .with_attribute(Attribute::new(
+                View::Private,
+                "label",
+                Type::Other("String"),
+                true,
+            ));
+        inner.add_inner(
+            Class::build("Named", View::Public, false)
+                .with_kind(ClassKind::Interface)
+                .with_method(Function::new(
+                    "name",
+                    View::Public,
+                    Type::Other("String"),
+                    Vec::new(),
+                    false,
+                    false,
+                )),
+        );
+        let mut person = Class::build("Person", View::Public, false)
+            .with_attribute(Attribute::new(
+                View::Private,
+                "name",
+                Type::Other("String"),
+                false,
+            ))
+            .with_attribute(
+                Attribute::new(View::Private, "count", Type::Other("int"), false).with_static(true),
+            )
+            .with_method(Function::new(
+                "greet",
+                View::Public,
+                Type::Other("void"),
+                vec![Attribute::new(
+                    View::Normal,
+                    "other",
+                    Type::Other("Person"),
+                    false,
+                )],
+                false,
+                true,
+            ))
+            .inherits(
+                Class::build("Base", View::Public, true).with_attribute(Attribute::new(
+                    View::Protected,
+                    "id",
+                    Type::Other("long"),
+                    false,
+                )),
+            );
+        person.add_doc(None, "A person\n\nwith a name");
+        person.add_doc(Some("name"), "Full name");
+        person.add_doc(Some("greet"), "Says hello");
+        person.add_inner(inner);
+        person.set_accessors(crate::model::Accessors::GettersAndSetters);
+        person
+    }
+
+    #[test]
+    fn builtin_templates_match_java_backend() {
+        let templates = Templates::load(None).unwrap();
+        let person = person();
+        assert_eq!(templates.render(&person).unwrap(), Java.class(&person));
+
+        let mut point = Class::build("Point", View::Public, false)
+            .with_kind(ClassKind::Record)
+            .with_attribute(Attribute::new(View::Normal, "x", Type::Other("int"), false))
+            .with_attribute(Attribute::new(View::Normal, "y", Type::Other("int"), false))
+            .with_attribute(
+                Attribute::new(View::Public, "ORIGIN", Type::Other("Point"), true)
+                    .with_static(true),
+            );
+        point.add_doc(None, "A point");
+        assert_eq!(templates.render(&point).unwrap(), Java.class(&point));
+    }
+
+    #[test]
+    fn builtin_templates_match_java_backend_for_every_kind() {
+        let input = "@startuml
+abstract class Held <<sealed>> {
+ # name : String
+ - {static} anzahl : int
+ + {abstract} angreifen(ziel : Monster, regel : Regel) : boolean
+ + getName() : String
+}
+class Krieger {
+ - waffe : Waffe
+ - ruestung : int[*]
+ + angreifen(ziel : Monster, regel : Regel) : boolean
+ + {static} erzeugen(name : String) : Krieger
+}
+interface Monster {
+ + {static} MAX : int
+ + brüllen(lautstaerke : int) : void
+}
+enum Farbe {
+ ROT
+ DUNKEL_GRUEN
+ - hex : String
+ + getHex() : String
+}
+record Punkt {
+ x : int
+ y : int
+ + abstand(other : Punkt) : double
+}
+class Stufe
+class Aussen
+Held <|-- Krieger
+Monster <|.. Krieger
+Aussen +-- Stufe
+note top of Krieger : Kämpft\\nmit Waffen
+note right of Held::name : Der Name
+@enduml";
+        let tokens = crate::tokenizer::get_identifiers_from_str(input.to_string()).unwrap();
+        let mut options = Options::default();
+        options.config.accessors = crate::model::Accessors::GettersAndSetters;
+        let ast = crate::generate::parse(&tokens).unwrap();
+        let classes = crate::generate::build_classes(ast, &options).unwrap();
+        let templates = Templates::load(None).unwrap();
+        assert_eq!(classes.len(), 6);
+        for class in classes.iter() {
+            assert_eq!(templates.render(class).unwrap(), Java.class(class));
+        }
+    }
+
+    #[test]
+    fn user_templates_replace_builtin_ones() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::write(
+            dir.join("field.jinja"),
+            "{{ field.visibility }} {{ field.type }} {{ field.name }}; // {{ name }}\n",
+        )
+        .unwrap();
+
+        let templates = Templates::load(Some(dir)).unwrap();
+        let java = templates.render(&person()).unwrap();
+        assert!(java.contains("    private String name; // Person\n"));
+        // the other templates are still the built-in ones
+        assert!(java.contains(
+            "    /**\n     * Says hello\n     *\n     * @param other\n     */\n    public static void greet(Person other) {\n    }\n"
+        ));
+    }
+}
@@ -12,17 +12,25 @@
 //      example: net.beans.ClassName is in ./net/beans/ClassName.java
 //
 // add more errors
+use std::env;
 use std::error::Error;
-use std::{fs::File, io::Read, path::Path};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::mermaid;
+use crate::preprocessor::Preprocessor;
+use crate::xmi;
 
 use log::info;
+use serde::Serialize;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub enum Token {
     Class,
     AbstractClass,
     Interface,
     Enum,
+    Record,
     StartObject,
     EndObject,
     StartMethod,
@@ -34,60 +42,150 @@ pub enum Token {
     Private,
     Abstract,
     Static,
+    Sealed,
 
     Type(String),
     Variable(String),
     Name(String),
+    Constant(String),
 
     InheritesLeft,
     InheritesRight,
+    NestsLeft,
+    NestsRight,
+
+    /// Class, optional member and text of a note
+    Note(String, Option<String>, String),
 
-    Startuml,
+    Startuml(Option<String>),
     Enduml,
-    // Line(usize),
+    /// File and line (starting at 1) of the following tokens
+    Line(String, usize),
 }
 
+#[allow(dead_code)]
 enum AttributeType {
     Java,       // Type Name
     Typescript, // Name: Type
 }
 
 // TODO merge lists ot one
-pub fn get_identifiers<'a>(filepath: &Path) -> Result<Vec<Token>, Box<dyn std::error::Error>> {
-    let file = File::open(filepath)?;
+/// Returns the identifiers and every file which was read, starting with filepath
+pub fn get_identifiers(
+    filepath: &Path,
+) -> Result<(Vec<Token>, Vec<PathBuf>), Box<dyn std::error::Error>> {
+    if mermaid::is_mermaid_file(filepath) {
+        return Ok(mermaid::get_identifiers(filepath)?);
+    }
+    if xmi::is_xmi_file(filepath) {
+        return Ok(xmi::get_identifiers(filepath)?);
+    }
+    let mut preprocessor = Preprocessor::new();
+    let buffer = preprocessor
+        .process_file(filepath)
+        .map_err(|e| SearchError::Error(Box::new(e)))?;
     info!("Opened {:?} to parse from", filepath);
 
-    let mut searcher = Searcher::new(file, AttributeType::Typescript);
+    Ok((
+        tokenize(buffer, &preprocessor)?,
+        preprocessor.files().to_vec(),
+    ))
+}
+
+/// Reads the diagram from stdin, includes are relative to the current directory
+pub fn get_identifiers_from_stdin() -> Result<(Vec<Token>, Vec<PathBuf>), Box<dyn std::error::Error>>
+{
+    let mut text = String::new();
+    io::stdin()
+        .read_to_string(&mut text)
+        .map_err(|e| SearchError::Error(Box::new(e)))?;
+    info!("Read {} bytes from stdin to parse from", text.len());
+
+    Ok(get_identifiers_from_source(&text, &env::current_dir()?)?)
+}
+
+/// Preprocesses and tokenizes text which isn't read from a file, includes are relative to dir
+pub fn get_identifiers_from_source(
+    text: &str,
+    dir: &Path,
+) -> Result<(Vec<Token>, Vec<PathBuf>), SearchError> {
+    if mermaid::is_mermaid(text) {
+        let tokens = mermaid::tokenize(text, STDIN).map_err(|e| SearchError::Error(Box::new(e)))?;
+        return Ok((tokens, Vec::new()));
+    }
+    if xmi::is_xmi(text) {
+        let tokens = xmi::tokenize(text, STDIN).map_err(|e| SearchError::Error(Box::new(e)))?;
+        return Ok((tokens, Vec::new()));
+    }
+    let mut preprocessor = Preprocessor::new();
+    let buffer = preprocessor
+        .process_str(text, dir)
+        .map_err(|e| SearchError::Error(Box::new(e)))?;
+
+    Ok((
+        tokenize(buffer, &preprocessor)?,
+        preprocessor.files().to_vec(),
+    ))
+}
+
+/// Tokenizes text which isn't preprocessed, lines are counted in the text itself
+pub fn get_identifiers_from_str(buffer: String) -> Result<Vec<Token>, SearchError> {
+    let mut searcher = Searcher::new(buffer, AttributeType::Typescript);
+    searcher.search()?;
+
+    Ok(searcher.tokens)
+}
+
+/// Tokenizes the output of the preprocessor with the lines of the original files
+fn tokenize(buffer: String, preprocessor: &Preprocessor) -> Result<Vec<Token>, SearchError> {
+    let current_dir = env::current_dir().unwrap_or_default();
+    let mut searcher = Searcher::new(buffer, AttributeType::Typescript);
+    searcher.sources = preprocessor
+        .sources()
+        .map(|(file, line)| {
+            let file = match file {
+                Some(file) => file.strip_prefix(&current_dir).unwrap_or(file),
+                None => Path::new(STDIN),
+            };
+            (file.display().to_string(), line)
+        })
+        .collect();
     searcher.search()?;
 
     Ok(searcher.tokens)
 }
 
+/// File name of diagrams read from stdin
+const STDIN: &str = "-";
+
 struct Searcher {
-    file: File,
     attribute_type: AttributeType,
 
     tokens: Vec<Token>,
     buffer: String,
+    /// Name of the last declared class, "note left" without "of" belongs to it
+    last_class: Option<String>,
+    /// Alias and text of notes which aren't attached to a class yet
+    floating_notes: Vec<(String, String)>,
+    /// File and line of every line of the buffer
+    sources: Vec<(String, usize)>,
 }
 
 impl Searcher {
-    fn new(file: File, attribute_type: AttributeType) -> Self {
+    fn new(buffer: String, attribute_type: AttributeType) -> Self {
         Self {
-            file,
             attribute_type,
             tokens: Vec::new(),
-            buffer: String::new(),
+            buffer,
+            last_class: None,
+            floating_notes: Vec::new(),
+            sources: Vec::new(),
         }
     }
 
     fn search(&mut self) -> Result<(), SearchError> {
-        if let Err(e) = self.file.read_to_string(&mut self.buffer) {
-            return Err(SearchError::Error(Box::new(e)));
-        }
-
         // FIXME use &str instead of String
-        // filter out comemnts and empty lines
+        // filter out comemnts, empty lines are kept to know the line of every token
         self.buffer = self
             .buffer
             .lines()
@@ -97,40 +195,72 @@ impl Searcher {
                     .take_while(|c| *c != '\'')
                     .collect::<String>()
             })
-            .filter(|l| !l.starts_with('\'') && !l.is_empty())
-            .intersperse("\n".to_string())
-            .collect();
+            .collect::<Vec<String>>()
+            .join("\n");
         // log::debug!("{}", self.buffer);
 
-        for (line_number, line) in self.buffer.lines().enumerate() {
-            // self.tokens.push(Token::Line(line_number+1));
-            // println!("{}", line);
-            if line.starts_with("@startuml") {
-                self.tokens.push(Token::Startuml);
-                return self.search_global(line_number);
-            }
+        // every diagram continues after the @enduml of the previous one
+        let mut next_line = 0;
+        while let Some((line_number, name)) = self.find_startuml(next_line) {
+            self.tokens.push(Token::Startuml(name));
+            next_line = self.search_global(line_number)? + 1;
+        }
+        if next_line == 0 {
+            return Err(SearchError::NoStartYaml);
+        }
+        Ok(())
+    }
+
+    /// Returns the line and name of the next "@startuml name" or "@startuml(id=name)"
+    fn find_startuml(&self, line_number: usize) -> Option<(usize, Option<String>)> {
+        let (line_number, line) = self
+            .buffer
+            .lines()
+            .enumerate()
+            .skip(line_number)
+            .find(|(_, l)| l.starts_with("@startuml"))?;
+        let rest = line["@startuml".len()..].trim();
+        let name = rest
+            .strip_prefix("(id=")
+            .and_then(|r| r.strip_suffix(')'))
+            .unwrap_or(rest);
+        match name.is_empty() {
+            true => Some((line_number, None)),
+            false => Some((line_number, Some(name.to_string()))),
         }
-        Err(SearchError::NoStartYaml)
     }
 
-    fn search_global(&mut self, line_number: usize) -> Result<(), SearchError> {
+    /// Returns the line of the closing @enduml
+    fn search_global(&mut self, line_number: usize) -> Result<usize, SearchError> {
         // FIXME borrow checker issue To not return search_class, instead just call it
         for (line_number, line) in self.buffer.lines().enumerate().skip(line_number + 1) {
-            // self.tokens.push(Token::Line(line_number+1));
             // log::debug!("{} -> ({})", line_number, line);
-            if line.starts_with("class") {
+            if line.is_empty() {
+                continue;
+            }
+            self.tokens.push(line_token(&self.sources, line_number));
+            if line == "note" || line.starts_with("note ") {
+                return self.search_note(line_number);
+            } else if line.starts_with("record")
+                || (line.starts_with("class") && line.contains("<<record>>"))
+            {
+                self.tokens.push(Token::Record);
+                return self.search_class(line_number, false, false);
+            } else if line.starts_with("class") {
                 self.tokens.push(Token::Class);
-                return self.search_class(line_number, false);
+                return self.search_class(line_number, false, false);
             } else if line.starts_with("abstract") {
                 self.tokens.push(Token::AbstractClass);
-                return self.search_class(line_number, true);
+                return self.search_class(line_number, true, false);
             } else if line.starts_with("interface") {
-                // return self.search_class(line_number);
                 self.tokens.push(Token::Interface);
-                todo!("Impmlement search_interface");
+                return self.search_class(line_number, false, false);
+            } else if line.starts_with("enum") {
+                self.tokens.push(Token::Enum);
+                return self.search_class(line_number, false, true);
             } else if line.starts_with("@enduml") {
                 self.tokens.push(Token::Enduml);
-                return Ok(());
+                return Ok(line_number);
             } else {
                 // if second word is a inherit push neccessary things into tokens
                 let words: Vec<&str> = line.split_whitespace().collect();
@@ -149,6 +279,33 @@ impl Searcher {
                         // "bla <|-- "
                         self.tokens.push(Token::Name(words[2].to_string()));
                     }
+                    // "Outer +-- Inner" -> Inner is declared inside of Outer
+                    Some(&"+--") | Some(&"+..") if words.len() > 2 => {
+                        self.tokens.push(Token::Name(words[0].to_string()));
+                        self.tokens.push(Token::NestsLeft);
+                        self.tokens.push(Token::Name(words[2].to_string()));
+                    }
+                    Some(&"--+") | Some(&"..+") if words.len() > 2 => {
+                        self.tokens.push(Token::Name(words[0].to_string()));
+                        self.tokens.push(Token::NestsRight);
+                        self.tokens.push(Token::Name(words[2].to_string()));
+                    }
+                    // "N1 .. Held" attaches the floating note N1 to Held
+                    Some(_) if words.len() > 2 => {
+                        let note = self
+                            .floating_notes
+                            .iter()
+                            .find(|(alias, _)| alias == words[0] || alias == words[2]);
+                        if let Some((alias, text)) = note {
+                            let class = if alias == words[0] {
+                                words[2]
+                            } else {
+                                words[0]
+                            };
+                            self.tokens
+                                .push(Token::Note(class.to_string(), None, text.clone()));
+                        }
+                    }
                     Some(_) | None => (),
                 }
             }
@@ -156,7 +313,12 @@ impl Searcher {
         Err(SearchError::NoEndYaml)
     }
 
-    fn search_class(&mut self, line_number: usize, is_abstract: bool) -> Result<(), SearchError> {
+    fn search_class(
+        &mut self,
+        line_number: usize,
+        is_abstract: bool,
+        is_enum: bool,
+    ) -> Result<usize, SearchError> {
         // TODO
         let top_line = self.buffer.lines().nth(line_number).unwrap();
         let words: Vec<&str> = top_line.split_whitespace().skip(1).collect();
@@ -175,17 +337,32 @@ impl Searcher {
             name = s;
         }
         self.tokens.push(Token::Name(name.to_string()));
+        self.last_class = Some(name.to_string());
+        // inner classes can be marked static with "{static}" or "<<static>>"
+        if words.iter().any(|w| *w == "{static}" || *w == "<<static>>") {
+            self.tokens.push(Token::Static);
+        }
+        if words.contains(&"<<sealed>>") {
+            self.tokens.push(Token::Sealed);
+        }
+
+        // continue searching after the class body, not inside of it
+        let mut end_line = line_number;
         if words.last().unwrap().ends_with("{") {
             self.tokens.push(Token::StartObject);
-            // todo!("start search for attributes, methods etc.");
 
             for (line_number, line) in self.buffer.lines().enumerate().skip(line_number + 1) {
-                // self.tokens.push(Token::Line(line_number+1));
                 log::debug!("{} .. {:?}", line_number, line);
+                end_line = line_number;
+                if line.is_empty() {
+                    continue;
+                }
+                self.tokens.push(line_token(&self.sources, line_number));
                 if line == "}" {
                     self.tokens.push(Token::EndObject);
                     break;
                 }
+                let tokens_before = self.tokens.len();
 
                 // self.search_inner_class(line_number);
                 // TODO hwo to use function instead of very long match statement
@@ -259,7 +436,12 @@ impl Searcher {
                             }
                         }
                         if !buf.is_empty() {
-                            self.tokens.push(Token::Type(buf.clone()));
+                            // a single word in an enum is one of its constants
+                            if is_enum && self.tokens.len() == tokens_before {
+                                self.tokens.push(Token::Constant(buf.clone()));
+                            } else {
+                                self.tokens.push(Token::Type(buf.clone()));
+                            }
                         }
                     }
                     AttributeType::Java => {}
@@ -269,14 +451,102 @@ impl Searcher {
 
         // log::debug!("top line: {} -> {:?} -> name: {}", top_line, words, name);
         // log::debug!("\"{}\" -> {:#?}", top_line, words);
-        return self.search_global(line_number);
+        self.search_global(end_line)
+    }
+
+    /// Handles "note top of Held : text", "note right of Held::angreifen", "note left"
+    /// after a class and floating "note as N1" notes, text without ":" lasts until
+    /// "end note"
+    fn search_note(&mut self, line_number: usize) -> Result<usize, SearchError> {
+        let line = self.buffer.lines().nth(line_number).unwrap_or_default();
+
+        // note "text" as N1, the quoted text can contain ':'
+        if let Some(rest) = line.trim().strip_prefix("note \"") {
+            if let Some((quoted, alias)) = rest.split_once('"') {
+                let alias = alias.trim().trim_start_matches("as").trim();
+                let text = quoted.replace("\\n", "\n");
+                self.floating_notes.push((alias.to_string(), text));
+            }
+            return self.search_global(line_number);
+        }
+
+        let (head, text) = match line.split_once(':') {
+            // "::" separates class and member
+            Some(_) => split_note_text(line),
+            None => (line, None),
+        };
+        let head = head.trim();
+
+        let mut end_line = line_number;
+        let text = match text {
+            Some(text) => text.trim().replace("\\n", "\n"),
+            None => {
+                let mut lines = Vec::new();
+                for (i, l) in self.buffer.lines().enumerate().skip(line_number + 1) {
+                    end_line = i;
+                    if l.trim() == "end note" || l.trim() == "endnote" {
+                        break;
+                    }
+                    lines.push(l.trim_end());
+                }
+                lines.join("\n").trim().to_string()
+            }
+        };
+
+        let words: Vec<&str> = head.split_whitespace().skip(1).collect();
+        let target = match words.as_slice() {
+            ["as", alias] => {
+                self.floating_notes.push((alias.to_string(), text));
+                return self.search_global(end_line);
+            }
+            [_, "of", target, ..] => target.to_string(),
+            ["top" | "bottom" | "left" | "right"] => match &self.last_class {
+                Some(class) => class.clone(),
+                None => return self.search_global(end_line),
+            },
+            // notes on links or other elements
+            _ => return self.search_global(end_line),
+        };
+
+        let (class, member) = match target.split_once("::") {
+            Some((class, member)) => {
+                let member = member.split('(').next().unwrap_or_default();
+                (class.to_string(), Some(member.to_string()))
+            }
+            None => (target, None),
+        };
+        self.tokens.push(Token::Note(class, member, text));
+        self.search_global(end_line)
     }
 
     // fn search_line_in_class_ts(&mut self, line: &str) {}
 }
 
+/// Marks where the tokens of the line come from
+fn line_token(sources: &[(String, usize)], line_number: usize) -> Token {
+    match sources.get(line_number) {
+        Some((file, line)) => Token::Line(file.clone(), *line),
+        None => Token::Line(STDIN.to_string(), line_number + 1),
+    }
+}
+
+/// Splits the line at the ":" before the text of a note, "::" belongs to the target
+fn split_note_text(line: &str) -> (&str, Option<&str>) {
+    let mut rest = line;
+    let mut offset = 0;
+    while let Some(i) = rest.find(':') {
+        if rest[i + 1..].starts_with(':') {
+            offset += i + 2;
+            rest = &rest[i + 2..];
+            continue;
+        }
+        return (&line[..offset + i], Some(&line[offset + i + 1..]));
+    }
+    (line, None)
+}
+
 #[derive(Debug)]
-enum SearchError {
+pub enum SearchError {
     Error(Box<dyn Error>),
     NoStartYaml,
     NoEndYaml,
@@ -320,3 +590,85 @@ impl std::fmt::Display for SearchError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_diagram_is_tokenized() {
+        let input = "@startuml first\nclass A\n@enduml\n\n@startuml(id=second)\nclass B\n@enduml\n@startuml\n@enduml";
+        let tokens = get_identifiers_from_str(input.to_string()).unwrap();
+        let starts: Vec<&Option<String>> = tokens
+            .iter()
+            .filter_map(|t| match t {
+                Token::Startuml(name) => Some(name),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            starts,
+            vec![
+                &Some("first".to_string()),
+                &Some("second".to_string()),
+                &None
+            ]
+        );
+        assert_eq!(
+            tokens.iter().filter(|t| matches!(t, Token::Class)).count(),
+            2
+        );
+    }
+
+    #[test]
+    fn notes_are_attached_to_classes_and_members() {
+        let input = "@startuml
+class Held {
+ + angreifen(ziel : Monster) : void
+}
+note top of Held : Ein Held\\nder kämpft
+note right of Held::angreifen
+  Greift das Ziel an
+end note
+class Monster
+note left
+  class Monster is no declaration
+end note
+note as N1
+  Verbunden
+end note
+N1 .. Monster
+note \"Quoted\" as N2
+Held .. N2
+note \"Quoted: with colon\" as N3
+Monster .. N3
+@enduml";
+        let tokens = get_identifiers_from_str(input.to_string()).unwrap();
+        let notes: Vec<(&str, Option<&str>, &str)> = tokens
+            .iter()
+            .filter_map(|t| match t {
+                Token::Note(class, member, text) => {
+                    Some((class.as_str(), member.as_deref(), text.as_str()))
+                }
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            notes,
+            vec![
+                ("Held", None, "Ein Held\nder kämpft"),
+                ("Held", Some("angreifen"), "Greift das Ziel an"),
+                ("Monster", None, "class Monster is no declaration"),
+                ("Monster", None, "Verbunden"),
+                ("Held", None, "Quoted"),
+                ("Monster", None, "Quoted: with colon"),
+            ]
+        );
+        assert_eq!(
+            tokens.iter().filter(|t| matches!(t, Token::Class)).count(),
+            2
+        );
+    }
+}
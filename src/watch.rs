@@ -0,0 +1,109 @@
+// Copyright (c) 2023, Oskar Ohlenmacher
+// All rights reserved
+//
+// This source code is licensed under the BSD-style license found in the
+// LICENSE file in the root directory of this source tree.
+
+// Polls the modification times of all diagram files instead of depending on
+// platform specific file system events.
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use log::{error, info};
+
+use crate::generate::{self, CustomError, Options};
+
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Time editors get to finish writing before generating again
+pub const SETTLE_TIME: Duration = Duration::from_millis(100);
+
+/// Generates the classes and generates them again whenever an input or an
+/// included file changes. Only returns on errors which can't be fixed by editing.
+pub fn watch(
+    inputs: &[String],
+    outputlocation: Option<&str>,
+    options: &Options,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if inputs.iter().any(|i| i == generate::STDIN) {
+        return Err(Box::new(CustomError::InputNotFound(
+            "stdin can't be watched".to_string(),
+        )));
+    }
+
+    // files read by the last successful run
+    let mut read = Vec::new();
+    loop {
+        match generate::generate_files(inputs, outputlocation, options) {
+            Ok(summary) => {
+                info!("{} files changed, watching for changes", summary.changed);
+                read = summary.files;
+            }
+            Err(e) => error!("{}, watching for changes", e),
+        }
+
+        let before = snapshot(inputs, &read);
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            if snapshot(inputs, &read) != before {
+                thread::sleep(SETTLE_TIME);
+                break;
+            }
+        }
+    }
+}
+
+/// Modification times of all inputs, new files in watched directories are picked up too
+pub fn snapshot(inputs: &[String], read: &[PathBuf]) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let mut files = generate::collect_inputs(inputs).unwrap_or_default();
+    for file in read {
+        if !files.contains(file) {
+            files.push(file.clone());
+        }
+    }
+    files
+        .into_iter()
+        .map(|f| {
+            let modified = fs::metadata(&f).and_then(|m| m.modified()).ok();
+            (f, modified)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_and_touched_files_change_the_snapshot() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::write(
+            dir.join("a.puml"),
+            "@startuml\n!include types.iuml\n@enduml",
+        )
+        .unwrap();
+        fs::write(dir.join("types.iuml"), "class A").unwrap();
+        let inputs = [dir.display().to_string()];
+        let read = [dir.join("a.puml"), dir.join("types.iuml")];
+
+        let before = snapshot(&inputs, &read);
+        assert_eq!(before.len(), 2);
+        assert_eq!(snapshot(&inputs, &read), before);
+
+        fs::write(dir.join("b.puml"), "@startuml\nclass B\n@enduml").unwrap();
+        let added = snapshot(&inputs, &read);
+        assert_eq!(added.len(), 3);
+
+        // the included file isn't an input, only the last run read it
+        let later = SystemTime::now() + Duration::from_secs(10);
+        fs::File::options()
+            .write(true)
+            .open(dir.join("types.iuml"))
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_ne!(snapshot(&inputs, &read), added);
+    }
+}
@@ -0,0 +1,1247 @@
+// Copyright (c) 2023, Oskar Ohlenmacher
+// All rights reserved
+//
+// This source code is licensed under the BSD-style license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! XMI 2.x class models, read into the same tokens as PlantUML diagrams and
+//! written from the model.
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+
+use crate::model::{Attribute, Class, ClassKind, Function, View};
+use crate::tokenizer::Token;
+
+/// Extensions of files read as XMI, `.uml` files are Eclipse UML2 models
+pub const EXTENSIONS: [&str; 2] = ["xmi", "uml"];
+
+const XMI_NAMESPACE: &str = "http://www.omg.org/spec/XMI/20131001";
+const UML_NAMESPACE: &str = "http://www.omg.org/spec/UML/20131001";
+
+#[derive(Debug)]
+pub enum XmiError {
+    Io(PathBuf, std::io::Error),
+    /// Line and description of malformed XML
+    Xml(usize, String),
+    NoModel,
+}
+
+impl std::fmt::Display for XmiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(path, e) => write!(f, "Can't read {}: {}", path.display(), e),
+            Self::Xml(line, message) => write!(f, "Malformed XML on line {}: {}", line, message),
+            Self::NoModel => write!(f, "No UML model found"),
+        }
+    }
+}
+
+impl Error for XmiError {}
+
+/// Whether the file is read as XMI instead of PlantUML
+pub fn is_xmi_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| EXTENSIONS.iter().any(|x| e == *x))
+}
+
+/// Whether text which isn't read from a file is an XMI document
+pub fn is_xmi(text: &str) -> bool {
+    text.trim_start().starts_with('<') && (text.contains("xmi:XMI") || text.contains("uml:Model"))
+}
+
+/// Returns the tokens of the file and the file itself as the only one read
+pub fn get_identifiers(filepath: &Path) -> Result<(Vec<Token>, Vec<PathBuf>), XmiError> {
+    let path = fs::canonicalize(filepath).map_err(|e| XmiError::Io(filepath.to_path_buf(), e))?;
+    let text = fs::read_to_string(&path).map_err(|e| XmiError::Io(path.clone(), e))?;
+    info!("Opened {:?} to parse from", filepath);
+    let current_dir = env::current_dir().unwrap_or_default();
+    let file = path.strip_prefix(&current_dir).unwrap_or(&path);
+    Ok((tokenize(&text, &file.display().to_string())?, vec![path]))
+}
+
+/// An XML element, namespace prefixes are kept in the names
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>,
+    text: String,
+    line: usize,
+}
+
+impl Element {
+    /// Value of the attribute, a prefixed name matches any prefix
+    fn get(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| {
+                key == name
+                    || (key.contains(':') && name.contains(':') && local(key) == local(name))
+            })
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn is(&self, name: &str) -> bool {
+        self.get(name) == Some("true")
+    }
+
+    fn id(&self) -> Option<&str> {
+        self.get("xmi:id")
+    }
+
+    fn children<'e>(&'e self, name: &'e str) -> impl Iterator<Item = &'e Element> {
+        self.children.iter().filter(move |c| local(&c.name) == name)
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| local(&c.name) == name)
+    }
+
+    /// Metaclass like `Class` of `xmi:type="uml:Class"`, elements like
+    /// `<uml:Model>` name it themselves
+    fn uml_type(&self) -> &str {
+        match self.get("xmi:type") {
+            Some(t) => local(t),
+            None => local(&self.name),
+        }
+    }
+}
+
+/// Name without the namespace prefix
+fn local(name: &str) -> &str {
+    name.rsplit_once(':').map_or(name, |(_, l)| l)
+}
+
+/// Id referenced by the attribute or by a child element of the same name, the
+/// fragment of an `href` into another document is taken as the id
+fn reference<'e>(element: &'e Element, name: &str) -> Option<&'e str> {
+    element.get(name).or_else(|| {
+        let child = element.child(name)?;
+        child.get("xmi:idref").or_else(|| {
+            let href = child.get("href")?;
+            href.rsplit_once('#').map(|(_, fragment)| fragment)
+        })
+    })
+}
+
+/// Classifiers and relations of one model, packages are flattened
+#[derive(Default)]
+struct Model<'x> {
+    /// Classifiers and the classifier they are nested in
+    classifiers: Vec<(&'x Element, Option<&'x Element>)>,
+    associations: Vec<&'x Element>,
+    realizations: Vec<&'x Element>,
+    /// Every element with an id
+    ids: HashMap<&'x str, &'x Element>,
+}
+
+impl<'x> Model<'x> {
+    fn collect(&mut self, element: &'x Element, outer: Option<&'x Element>) {
+        if let Some(id) = element.id() {
+            self.ids.insert(id, element);
+        }
+        for child in element.children.iter() {
+            let is_owned = matches!(
+                local(&child.name),
+                "packagedElement" | "ownedMember" | "nestedClassifier" | "ownedType"
+            );
+            match child.uml_type() {
+                _ if !is_owned => self.collect(child, outer),
+                "Package" | "Model" => self.collect(child, None),
+                "Class" | "AssociationClass" | "Interface" | "Enumeration" | "DataType" => {
+                    self.classifiers.push((child, outer));
+                    self.collect(child, Some(child));
+                }
+                "Association" => {
+                    self.associations.push(child);
+                    self.collect(child, outer);
+                }
+                "InterfaceRealization" | "Realization" => {
+                    self.realizations.push(child);
+                    self.collect(child, outer);
+                }
+                _ => self.collect(child, outer),
+            }
+        }
+    }
+
+    /// Name of the referenced element, the reference itself if it isn't part of
+    /// the model like the primitive types of UML
+    fn name_of(&self, reference: &'x str) -> &'x str {
+        self.ids
+            .get(reference)
+            .and_then(|e| e.get("name"))
+            .unwrap_or(reference)
+    }
+
+    /// Type of a property or parameter, multiple values are a `List` if they are
+    /// ordered, a `Set` if they are unique and otherwise a multiplicity like
+    /// `String[*]`, which becomes the configured collection
+    fn type_of(&self, element: &Element) -> String {
+        let name = reference(element, "type").map_or("Object", |r| self.name_of(r));
+        let bound = |name: &str, short: &str| {
+            element
+                .child(name)
+                .and_then(|b| b.get("value"))
+                .or_else(|| element.get(short))
+        };
+        let upper = bound("upperValue", "upper");
+        let is_many =
+            upper.is_some_and(|u| u == "*" || u == "-1" || u.parse::<u32>().is_ok_and(|n| n > 1));
+        match is_many {
+            false => name.to_string(),
+            true if element.is("isOrdered") => format!("List<{}>", name),
+            true if element.is("isUnique") => format!("Set<{}>", name),
+            true if bound("lowerValue", "lower") == Some("1") => format!("{}[1..*]", name),
+            true => format!("{}[*]", name),
+        }
+    }
+
+    /// Association ends owned by the associations which can be navigated to,
+    /// by the id of the class on the other end
+    fn navigable_ends(&self) -> HashMap<&'x str, Vec<&'x Element>> {
+        let mut ends: HashMap<&str, Vec<&Element>> = HashMap::new();
+        for association in self.associations.iter() {
+            let navigable: Vec<&str> = association
+                .get("navigableOwnedEnd")
+                .map(|n| n.split_whitespace().collect())
+                .unwrap_or_default();
+            let members: Vec<&str> = match association.get("memberEnd") {
+                Some(members) => members.split_whitespace().collect(),
+                None => association
+                    .children("memberEnd")
+                    .filter_map(|m| m.get("xmi:idref"))
+                    .collect(),
+            };
+            for end in association.children("ownedEnd") {
+                let Some(id) = end.id() else {
+                    continue;
+                };
+                if !navigable.contains(&id) && !end.is("isNavigable") {
+                    continue;
+                }
+                // the class on the other end has the end as its attribute
+                let other = members
+                    .iter()
+                    .filter(|m| **m != id)
+                    .find_map(|m| self.ids.get(m).copied())
+                    .or_else(|| {
+                        association
+                            .children("ownedEnd")
+                            .find(|e| e.id() != Some(id))
+                    });
+                if let Some(owner) = other.and_then(|o| reference(o, "type")) {
+                    ends.entry(owner).or_default().push(end);
+                }
+            }
+        }
+        ends
+    }
+}
+
+/// Converts every model of the XMI document to tokens, one diagram for each model
+pub fn tokenize(text: &str, file: &str) -> Result<Vec<Token>, XmiError> {
+    let document = parse_xml(text)?;
+    let mut roots = Vec::new();
+    find_models(&document, &mut roots);
+    if roots.is_empty() {
+        return Err(XmiError::NoModel);
+    }
+    let mut tokens = Vec::new();
+    for root in roots {
+        push_model(root, file, &mut tokens);
+    }
+    Ok(tokens)
+}
+
+/// Models are the root of `.uml` files and inside of `xmi:XMI` in XMI files
+fn find_models<'x>(element: &'x Element, models: &mut Vec<&'x Element>) {
+    for child in element.children.iter() {
+        match child.uml_type() {
+            "Model" | "Package" => models.push(child),
+            "XMI" => find_models(child, models),
+            _ => (),
+        }
+    }
+}
+
+fn push_model(root: &Element, file: &str, tokens: &mut Vec<Token>) {
+    let mut model = Model::default();
+    model.collect(root, None);
+    let ends = model.navigable_ends();
+    let line = |element: &Element| Token::Line(file.to_string(), element.line);
+    // line, master or outer class, relation and the inheriting or inner class
+    let mut relations = Vec::new();
+    let mut notes = Vec::new();
+
+    tokens.push(Token::Startuml(root.get("name").map(str::to_string)));
+    for (classifier, outer) in model.classifiers.iter() {
+        let Some(name) = classifier.get("name") else {
+            warn!(
+                "Skipped a classifier without a name on line {}",
+                classifier.line
+            );
+            continue;
+        };
+        tokens.push(line(classifier));
+        tokens.push(match classifier.uml_type() {
+            "Interface" => Token::Interface,
+            "Enumeration" => Token::Enum,
+            "DataType" => Token::Record,
+            _ if classifier.is("isAbstract") => Token::AbstractClass,
+            _ => Token::Class,
+        });
+        tokens.push(Token::Name(name.to_string()));
+        tokens.push(Token::StartObject);
+        for literal in classifier.children("ownedLiteral") {
+            if let Some(constant) = literal.get("name") {
+                tokens.push(line(literal));
+                tokens.push(Token::Constant(constant.to_string()));
+            }
+        }
+        let owned_ends = classifier.id().and_then(|id| ends.get(id));
+        for attribute in classifier
+            .children("ownedAttribute")
+            .chain(owned_ends.into_iter().flatten().copied())
+        {
+            let vartype = model.type_of(attribute);
+            // association ends are often unnamed
+            let varname = match attribute.get("name") {
+                Some(varname) => varname.to_string(),
+                None => lower_first(model.name_of(reference(attribute, "type").unwrap_or("value"))),
+            };
+            tokens.push(line(attribute));
+            push_modifiers(attribute, tokens);
+            tokens.push(Token::Variable(varname));
+            tokens.push(Token::Type(vartype));
+        }
+        for operation in classifier.children("ownedOperation") {
+            let Some(method) = operation.get("name") else {
+                continue;
+            };
+            tokens.push(line(operation));
+            push_modifiers(operation, tokens);
+            tokens.push(Token::Name(method.to_string()));
+            tokens.push(Token::StartMethod);
+            let mut return_type = None;
+            for parameter in operation.children("ownedParameter") {
+                let vartype = model.type_of(parameter);
+                match parameter.get("direction") {
+                    Some("return") => return_type = Some(vartype),
+                    _ => {
+                        let varname = parameter.get("name").unwrap_or("arg");
+                        tokens.push(Token::Variable(varname.to_string()));
+                        tokens.push(Token::Type(vartype));
+                    }
+                }
+            }
+            tokens.push(Token::EndMethod);
+            // operations without a return parameter don't return anything
+            tokens.push(Token::Type(
+                return_type.unwrap_or_else(|| "void".to_string()),
+            ));
+        }
+        tokens.push(Token::EndObject);
+
+        if let Some(outer) = outer.and_then(|o| o.get("name")) {
+            relations.push((classifier.line, outer, Token::NestsLeft, name));
+        }
+        for generalization in classifier.children("generalization") {
+            if let Some(general) = reference(generalization, "general") {
+                let master = model.name_of(general);
+                relations.push((generalization.line, master, Token::InheritesLeft, name));
+            }
+        }
+        for realization in classifier.children("interfaceRealization") {
+            let contract =
+                reference(realization, "contract").or_else(|| reference(realization, "supplier"));
+            if let Some(contract) = contract {
+                let interface = model.name_of(contract);
+                relations.push((realization.line, interface, Token::InheritesLeft, name));
+            }
+        }
+        push_comments(classifier, name, &model, &mut notes);
+    }
+    // realizations which aren't owned by the realizing class
+    for realization in model.realizations.iter() {
+        let (Some(client), Some(supplier)) = (
+            reference(realization, "client"),
+            reference(realization, "supplier").or_else(|| reference(realization, "contract")),
+        ) else {
+            continue;
+        };
+        let is_interface = model
+            .ids
+            .get(supplier)
+            .is_some_and(|s| s.uml_type() == "Interface");
+        if realization.uml_type() == "InterfaceRealization" || is_interface {
+            let (interface, class) = (model.name_of(supplier), model.name_of(client));
+            relations.push((realization.line, interface, Token::InheritesLeft, class));
+        }
+    }
+
+    for (number, left, relation, right) in relations {
+        tokens.push(Token::Line(file.to_string(), number));
+        tokens.push(Token::Name(left.to_string()));
+        tokens.push(relation);
+        tokens.push(Token::Name(right.to_string()));
+    }
+    for (number, note) in notes {
+        tokens.push(Token::Line(file.to_string(), number));
+        tokens.push(note);
+    }
+    tokens.push(Token::Enduml);
+}
+
+/// Visibility, which is public if it isn't written, and classifiers of a member
+fn push_modifiers(member: &Element, tokens: &mut Vec<Token>) {
+    tokens.push(match member.get("visibility") {
+        Some("private") => Token::Private,
+        Some("protected") => Token::Protected,
+        Some("package") => Token::PackagePrivate,
+        _ => Token::Public,
+    });
+    if member.is("isStatic") {
+        tokens.push(Token::Static);
+    }
+    if member.is("isAbstract") {
+        tokens.push(Token::Abstract);
+    }
+}
+
+/// Comments of the class and its members become notes, a comment belongs to the
+/// member it annotates or is owned by
+fn push_comments(
+    classifier: &Element,
+    class: &str,
+    model: &Model,
+    notes: &mut Vec<(usize, Token)>,
+) {
+    let members: Vec<&Element> = classifier
+        .children("ownedAttribute")
+        .chain(classifier.children("ownedOperation"))
+        .collect();
+    let owned = classifier
+        .children("ownedComment")
+        .map(|c| (c, None))
+        .chain(
+            members
+                .iter()
+                .flat_map(|m| m.children("ownedComment").map(|c| (c, m.get("name")))),
+        );
+    for (comment, owner) in owned {
+        let body = comment
+            .get("body")
+            .map(str::to_string)
+            .or_else(|| comment.child("body").map(|b| b.text.clone()))
+            .unwrap_or_default();
+        if body.trim().is_empty() {
+            continue;
+        }
+        let annotated = comment
+            .get("annotatedElement")
+            .and_then(|a| a.split_whitespace().next())
+            .and_then(|id| model.ids.get(id).copied())
+            .filter(|e| members.iter().any(|m| std::ptr::eq(*m, *e)))
+            .and_then(|e| e.get("name"));
+        let member = owner.or(annotated).map(str::to_string);
+        let note = Token::Note(class.to_string(), member, body.trim().to_string());
+        notes.push((comment.line, note));
+    }
+}
+
+fn lower_first(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn parse_xml(text: &str) -> Result<Element, XmiError> {
+    // the document is the first element on the stack
+    let mut stack = vec![Element::default()];
+    let mut rest = text;
+    let mut line = 1;
+    while let Some(start) = rest.find('<') {
+        let content = &rest[..start];
+        line += content.matches('\n').count();
+        current(&mut stack).text.push_str(&unescape(content));
+        rest = &rest[start..];
+
+        let len = if let Some(comment) = rest.strip_prefix("<!--") {
+            comment
+                .find("-->")
+                .ok_or_else(|| XmiError::Xml(line, "comment is not closed".to_string()))?
+                + 7
+        } else if let Some(data) = rest.strip_prefix("<![CDATA[") {
+            let end = data
+                .find("]]>")
+                .ok_or_else(|| XmiError::Xml(line, "CDATA section is not closed".to_string()))?;
+            current(&mut stack).text.push_str(&data[..end]);
+            end + 12
+        } else if rest.starts_with("<?") || rest.starts_with("<!") {
+            rest.find('>')
+                .ok_or_else(|| XmiError::Xml(line, "declaration is not closed".to_string()))?
+                + 1
+        } else {
+            let end = tag_end(rest)
+                .ok_or_else(|| XmiError::Xml(line, "tag is not closed".to_string()))?;
+            let tag = &rest[1..end];
+            if let Some(name) = tag.strip_prefix('/') {
+                let name = name.trim();
+                if stack.len() < 2 {
+                    return Err(XmiError::Xml(line, format!("</{}> closes nothing", name)));
+                }
+                let element = stack.pop().unwrap_or_default();
+                if element.name != name {
+                    let message = format!("</{}> closes <{}>", name, element.name);
+                    return Err(XmiError::Xml(line, message));
+                }
+                current(&mut stack).children.push(element);
+            } else {
+                let (tag, is_empty) = match tag.strip_suffix('/') {
+                    Some(tag) => (tag, true),
+                    None => (tag, false),
+                };
+                let element = start_tag(tag, line)?;
+                match is_empty {
+                    true => current(&mut stack).children.push(element),
+                    false => stack.push(element),
+                }
+            }
+            end + 1
+        };
+        line += rest[..len].matches('\n').count();
+        rest = &rest[len..];
+    }
+    if stack.len() > 1 {
+        let message = format!("<{}> is not closed", current(&mut stack).name);
+        return Err(XmiError::Xml(line, message));
+    }
+    Ok(stack.pop().unwrap_or_default())
+}
+
+fn current(stack: &mut [Element]) -> &mut Element {
+    let last = stack.len() - 1;
+    &mut stack[last]
+}
+
+/// Index of the `>` which ends the tag at the start of the text
+fn tag_end(text: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '>') => return Some(i),
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Element of a start tag without the angle brackets
+fn start_tag(tag: &str, line: usize) -> Result<Element, XmiError> {
+    let tag = tag.trim();
+    let (name, mut rest) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+    let mut element = Element {
+        name: name.to_string(),
+        line,
+        ..Default::default()
+    };
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return Ok(element);
+        }
+        let Some((key, value)) = rest.split_once('=') else {
+            return Err(XmiError::Xml(
+                line,
+                format!("attribute {} has no value", rest),
+            ));
+        };
+        let key = key.trim();
+        let value = value.trim_start();
+        let quoted = value
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')
+            .and_then(|quote| {
+                let end = value[1..].find(quote)? + 1;
+                Some((&value[1..end], &value[end + 1..]))
+            });
+        let Some((value, remaining)) = quoted else {
+            return Err(XmiError::Xml(
+                line,
+                format!("value of {} is not quoted", key),
+            ));
+        };
+        element.attributes.push((key.to_string(), unescape(value)));
+        rest = remaining;
+    }
+}
+
+fn unescape(text: &str) -> String {
+    let mut str = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        str.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest.find(';').map(|end| &rest[1..end]);
+        let c = entity.and_then(|e| match e {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            e => e
+                .strip_prefix("#x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| e.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        });
+        match (entity, c) {
+            (Some(entity), Some(c)) => {
+                str.push(c);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                str.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    str.push_str(rest);
+    str
+}
+
+fn escape(text: &str) -> String {
+    let mut str = String::new();
+    for c in text.chars() {
+        match c {
+            '&' => str.push_str("&amp;"),
+            '<' => str.push_str("&lt;"),
+            '>' => str.push_str("&gt;"),
+            '"' => str.push_str("&quot;"),
+            '\n' => str.push_str("&#10;"),
+            c => str.push(c),
+        }
+    }
+    str
+}
+
+/// Writes the classes as one XMI 2.5 model inside of the package. Types which
+/// aren't classes of the model are declared as primitive types and attributes
+/// with a class as their type are associations. UML can't say that a class is
+/// sealed or a static nested class, that is left out
+pub fn write(classes: &[Class], package: Option<&str>) -> String {
+    let mut writer = Writer::default();
+    writer.used.insert("model".to_string());
+    for class in classes.iter() {
+        writer.declare(class);
+    }
+    writer.line(0, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    writer.line(
+        0,
+        &format!(
+            r#"<xmi:XMI xmi:version="20131001" xmlns:xmi="{}" xmlns:uml="{}">"#,
+            XMI_NAMESPACE, UML_NAMESPACE
+        ),
+    );
+    writer.line(1, r#"<uml:Model xmi:id="model" name="model">"#);
+    let packages: Vec<&str> = package.map(|p| p.split('.').collect()).unwrap_or_default();
+    for (i, name) in packages.iter().enumerate() {
+        let id = writer.id(&packages[..=i].join("."));
+        writer.line(
+            2 + i,
+            &format!(
+                r#"<packagedElement xmi:type="uml:Package" xmi:id="{}" name="{}">"#,
+                id,
+                escape(name)
+            ),
+        );
+    }
+    let depth = 2 + packages.len();
+    for class in classes.iter() {
+        writer.class(class, "packagedElement", depth);
+    }
+    for (id, member, end, owner) in std::mem::take(&mut writer.associations) {
+        writer.line(
+            depth,
+            &format!(
+                r#"<packagedElement xmi:type="uml:Association" xmi:id="{}" memberEnd="{} {}">"#,
+                id, member, end
+            ),
+        );
+        writer.line(
+            depth + 1,
+            &format!(
+                r#"<ownedEnd xmi:id="{}" type="{}" association="{}"/>"#,
+                end, owner, id
+            ),
+        );
+        writer.line(depth, "</packagedElement>");
+    }
+    for (id, client, supplier) in std::mem::take(&mut writer.realizations) {
+        writer.line(
+            depth,
+            &format!(
+                r#"<packagedElement xmi:type="uml:Realization" xmi:id="{}" client="{}" supplier="{}"/>"#,
+                id, client, supplier
+            ),
+        );
+    }
+    for (name, id) in std::mem::take(&mut writer.types) {
+        writer.line(
+            depth,
+            &format!(
+                r#"<packagedElement xmi:type="uml:PrimitiveType" xmi:id="{}" name="{}"/>"#,
+                id,
+                escape(&name)
+            ),
+        );
+    }
+    for i in (0..packages.len()).rev() {
+        writer.line(2 + i, "</packagedElement>");
+    }
+    writer.line(1, "</uml:Model>");
+    writer.line(0, "</xmi:XMI>");
+    writer.str
+}
+
+#[derive(Default)]
+struct Writer {
+    str: String,
+    used: HashSet<String>,
+    /// Ids of the classes by their names
+    classes: HashMap<String, String>,
+    /// Names and ids of the types which aren't classes of the model
+    types: Vec<(String, String)>,
+    /// Ids of the associations, the attribute, the opposite end and the class
+    /// which has the attribute
+    associations: Vec<(String, String, String, String)>,
+    /// Ids of the realizations of records, the record and the interface, a data
+    /// type can't own its realizations
+    realizations: Vec<(String, String, String)>,
+}
+
+impl Writer {
+    fn line(&mut self, depth: usize, text: &str) {
+        self.str.push_str(&"  ".repeat(depth));
+        self.str.push_str(text);
+        self.str.push('\n');
+    }
+
+    /// A new id which is a valid XML name based on the text
+    fn id(&mut self, base: &str) -> String {
+        let mut id: String = base
+            .chars()
+            .map(
+                |c| match c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                    true => c,
+                    false => '_',
+                },
+            )
+            .collect();
+        if !id.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+            id.insert(0, '_');
+        }
+        let mut unique = id.clone();
+        let mut n = 2;
+        while !self.used.insert(unique.clone()) {
+            unique = format!("{}-{}", id, n);
+            n += 1;
+        }
+        unique
+    }
+
+    fn declare(&mut self, class: &Class) {
+        let id = self.id(class.name());
+        self.classes.insert(class.name().to_string(), id);
+        for inner in class.inner().iter() {
+            self.declare(inner);
+        }
+    }
+
+    /// Id of the class or of the primitive type with the name
+    fn reference(&mut self, name: &str) -> String {
+        if let Some(id) = self.classes.get(name) {
+            return id.clone();
+        }
+        if let Some((_, id)) = self.types.iter().find(|(t, _)| t == name) {
+            return id.clone();
+        }
+        let id = self.id(&format!("type-{}", name));
+        self.types.push((name.to_string(), id.clone()));
+        id
+    }
+
+    fn class(&mut self, class: &Class, tag: &str, depth: usize) {
+        let id = self.classes[class.name()].clone();
+        let metaclass = match class.get_kind() {
+            ClassKind::Class => "Class",
+            ClassKind::Interface => "Interface",
+            ClassKind::Enum => "Enumeration",
+            ClassKind::Record => "DataType",
+        };
+        let mut head = format!(
+            r#"<{} xmi:type="uml:{}" xmi:id="{}" name="{}""#,
+            tag,
+            metaclass,
+            id,
+            escape(class.name())
+        );
+        if class.get_kind() == ClassKind::Class && class.is_abstract() {
+            head.push_str(r#" isAbstract="true""#);
+        }
+        let is_record = class.get_kind() == ClassKind::Record;
+        let is_empty = class.doc().is_none()
+            && class.extends().is_none()
+            && (class.implements().is_empty() || is_record)
+            && class.constants().is_empty()
+            && class.attributes().is_empty()
+            && class.methods().is_empty()
+            && class.inner().is_empty();
+        if is_empty {
+            self.line(depth, &format!("{}/>", head));
+            return;
+        }
+        self.line(depth, &format!("{}>", head));
+        if let Some(doc) = class.doc() {
+            self.comment(&id, doc, depth + 1);
+        }
+        let mut generals: Vec<&str> = class.extends().map(|m| m.name()).into_iter().collect();
+        let mut contracts = Vec::new();
+        for interface in class.implements().iter() {
+            match class.get_kind() {
+                ClassKind::Interface => generals.push(interface.name()),
+                _ => contracts.push(interface.name()),
+            }
+        }
+        for general in generals {
+            let general = self.reference(general);
+            let generalization = self.id(&format!("{}-generalization", id));
+            self.line(
+                depth + 1,
+                &format!(
+                    r#"<generalization xmi:id="{}" general="{}"/>"#,
+                    generalization, general
+                ),
+            );
+        }
+        for contract in contracts {
+            let contract = self.reference(contract);
+            let realization = self.id(&format!("{}-realization", id));
+            if is_record {
+                self.realizations.push((realization, id.clone(), contract));
+                continue;
+            }
+            self.line(
+                depth + 1,
+                &format!(
+                    r#"<interfaceRealization xmi:id="{}" client="{}" supplier="{}" contract="{}"/>"#,
+                    realization, id, contract, contract
+                ),
+            );
+        }
+        for constant in class.constants().iter() {
+            let literal = self.id(&format!("{}-{}", id, constant));
+            self.line(
+                depth + 1,
+                &format!(
+                    r#"<ownedLiteral xmi:id="{}" name="{}"/>"#,
+                    literal,
+                    escape(constant)
+                ),
+            );
+        }
+        for a in class.attributes().iter() {
+            self.attribute(&id, *a, depth + 1);
+        }
+        for f in class.methods().iter() {
+            self.operation(&id, f, depth + 1);
+        }
+        for inner in class.inner().iter() {
+            self.class(inner, "nestedClassifier", depth + 1);
+        }
+        self.line(depth, &format!("</{}>", tag));
+    }
+
+    fn attribute(&mut self, owner: &str, a: Attribute, depth: usize) {
+        let id = self.id(&format!("{}-{}", owner, a.name()));
+        let (element, typed, multiplicity) = self.typed(&id, &a.vartype().to_string());
+        let mut head = format!(
+            r#"<ownedAttribute xmi:id="{}" name="{}" visibility="{}""#,
+            id,
+            escape(a.name()),
+            visibility(a.visibility())
+        );
+        if a.is_static() {
+            head.push_str(r#" isStatic="true""#);
+        }
+        head.push_str(&typed);
+        if self.classes.contains_key(&element) {
+            let association = self.id(&format!("{}-association", id));
+            let end = self.id(&format!("{}-end", association));
+            head.push_str(&format!(r#" association="{}""#, association));
+            self.associations
+                .push((association, id.clone(), end, owner.to_string()));
+        }
+        self.close(head, &id, a.doc(), multiplicity, "ownedAttribute", depth);
+    }
+
+    fn operation(&mut self, owner: &str, f: &Function, depth: usize) {
+        let id = self.id(&format!("{}-{}", owner, f.name()));
+        let mut head = format!(
+            r#"<ownedOperation xmi:id="{}" name="{}" visibility="{}""#,
+            id,
+            escape(f.name()),
+            visibility(f.visibility())
+        );
+        if f.is_static() {
+            head.push_str(r#" isStatic="true""#);
+        }
+        if f.is_abstract() {
+            head.push_str(r#" isAbstract="true""#);
+        }
+        let mut parameters: Vec<(String, Option<&str>)> = f
+            .parameters()
+            .iter()
+            .map(|p| (p.vartype().to_string(), Some(p.name())))
+            .collect();
+        let return_type = f.return_type().to_string();
+        if !return_type.is_empty() && return_type != "void" {
+            parameters.push((return_type, None));
+        }
+        let mut lines = Vec::new();
+        for (vartype, name) in parameters {
+            let parameter = self.id(&format!("{}-{}", id, name.unwrap_or("return")));
+            let (_, typed, multiplicity) = self.typed(&parameter, &vartype);
+            let head = match name {
+                Some(name) => format!(
+                    r#"<ownedParameter xmi:id="{}" name="{}"{}"#,
+                    parameter,
+                    escape(name),
+                    typed
+                ),
+                None => format!(
+                    r#"<ownedParameter xmi:id="{}" direction="return"{}"#,
+                    parameter, typed
+                ),
+            };
+            match multiplicity.is_empty() {
+                true => lines.push(format!("{}/>", head)),
+                false => {
+                    lines.push(format!("{}>", head));
+                    lines.extend(multiplicity.iter().map(|m| format!("  {}", m)));
+                    lines.push("</ownedParameter>".to_string());
+                }
+            }
+        }
+        self.close(head, &id, f.doc(), lines, "ownedOperation", depth);
+    }
+
+    /// Writes the element with its comment and the lines inside of it
+    fn close(
+        &mut self,
+        head: String,
+        id: &str,
+        doc: Option<&str>,
+        lines: Vec<String>,
+        tag: &str,
+        depth: usize,
+    ) {
+        if doc.is_none() && lines.is_empty() {
+            self.line(depth, &format!("{}/>", head));
+            return;
+        }
+        self.line(depth, &format!("{}>", head));
+        if let Some(doc) = doc {
+            self.comment(id, doc, depth + 1);
+        }
+        for line in lines.iter() {
+            self.line(depth + 1, line);
+        }
+        self.line(depth, &format!("</{}>", tag));
+    }
+
+    fn comment(&mut self, owner: &str, doc: &str, depth: usize) {
+        let id = self.id(&format!("{}-comment", owner));
+        self.line(
+            depth,
+            &format!(
+                r#"<ownedComment xmi:id="{}" annotatedElement="{}"><body>{}</body></ownedComment>"#,
+                id,
+                owner,
+                escape(doc)
+            ),
+        );
+    }
+
+    /// Type of a property or parameter and the attributes and lines which
+    /// declare it, a `List` or `Set` of one type and multiplicities like
+    /// `String[*]` are written as the type with an upper bound of `*`
+    fn typed(&mut self, id: &str, vartype: &str) -> (String, String, Vec<String>) {
+        let collection = vartype.split_once('<').and_then(|(collection, rest)| {
+            let element = rest.strip_suffix('>')?.trim();
+            let is_simple = !element.contains(['<', ',']);
+            (matches!(collection.trim(), "List" | "Set") && is_simple)
+                .then_some((collection.trim(), element))
+        });
+        let multiple = [("[*]", "0"), ("[0..*]", "0"), ("[1..*]", "1")]
+            .iter()
+            .find_map(|(suffix, lower)| Some((vartype.strip_suffix(suffix)?.trim(), *lower)));
+        let (element, mut attributes, lower) = match (collection, multiple) {
+            (Some(("Set", element)), _) => (element, r#" isUnique="true""#.to_string(), "0"),
+            (Some((_, element)), _) => (
+                element,
+                r#" isOrdered="true" isUnique="false""#.to_string(),
+                "0",
+            ),
+            (None, Some((element, lower))) => (element, String::new(), lower),
+            (None, None) => (vartype, String::new(), "0"),
+        };
+        attributes.push_str(&format!(r#" type="{}""#, self.reference(element)));
+        // the value of the lower bound is only written if it isn't the default
+        let lower = match lower {
+            "0" => String::new(),
+            lower => format!(r#" value="{}""#, lower),
+        };
+        let multiplicity = match collection.is_some() || multiple.is_some() {
+            true => vec![
+                format!(
+                    r#"<lowerValue xmi:type="uml:LiteralInteger" xmi:id="{}"{}/>"#,
+                    self.id(&format!("{}-lower", id)),
+                    lower
+                ),
+                format!(
+                    r#"<upperValue xmi:type="uml:LiteralUnlimitedNatural" xmi:id="{}" value="*"/>"#,
+                    self.id(&format!("{}-upper", id))
+                ),
+            ],
+            false => Vec::new(),
+        };
+        (element.to_string(), attributes, multiplicity)
+    }
+}
+
+fn visibility(view: View) -> &'static str {
+    match view {
+        View::Public => "public",
+        View::Private => "private",
+        View::Protected => "protected",
+        View::Normal => "package",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{Backend, Java};
+    use crate::generate;
+    use crate::tokenizer;
+
+    const SHOP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<xmi:XMI xmi:version="20131001" xmlns:xmi="http://www.omg.org/spec/XMI/20131001" xmlns:uml="http://www.omg.org/spec/UML/20131001">
+  <!-- exported by hand -->
+  <uml:Model xmi:id="m" name="Shop">
+    <packagedElement xmi:type="uml:Package" xmi:id="p" name="shop">
+      <packagedElement xmi:type="uml:Class" xmi:id="c1" name="Order">
+        <ownedComment xmi:id="c1-doc" annotatedElement="c1-total">
+          <body>Sum &amp; taxes</body>
+        </ownedComment>
+        <interfaceRealization xmi:id="r1" client="c1" supplier="i1" contract="i1"/>
+        <ownedAttribute xmi:id="c1-id" name="id" visibility="private">
+          <type xmi:type="uml:PrimitiveType" href="pathmap://UML_LIBRARIES/UMLPrimitiveTypes.library.uml#Integer"/>
+        </ownedAttribute>
+        <ownedAttribute xmi:id="c1-tags" name="tags" isUnique="true">
+          <type href="http://www.omg.org/spec/UML/20131001/PrimitiveTypes.xmi#String"/>
+          <upperValue xmi:type="uml:LiteralUnlimitedNatural" xmi:id="c1-tags-upper" value="*"/>
+        </ownedAttribute>
+        <ownedOperation xmi:id="c1-total" name="total">
+          <ownedParameter xmi:id="c1-total-r" direction="return" type="t1"/>
+        </ownedOperation>
+      </packagedElement>
+      <packagedElement xmi:type="uml:Class" xmi:id="c2" name="Item">
+        <generalization xmi:id="g1" general="c3"/>
+      </packagedElement>
+      <packagedElement xmi:type="uml:Class" xmi:id="c3" name="Product" isAbstract="true">
+        <ownedOperation xmi:id="c3-price" name="price" visibility="protected" isAbstract="true">
+          <ownedParameter xmi:id="c3-price-c" name="currency" type="t2"/>
+          <ownedParameter xmi:id="c3-price-r" direction="return" type="t1"/>
+        </ownedOperation>
+      </packagedElement>
+      <packagedElement xmi:type="uml:Interface" xmi:id="i1" name="Priced"/>
+      <packagedElement xmi:type="uml:Enumeration" xmi:id="t2" name="Currency">
+        <ownedLiteral xmi:id="t2-eur" name="EUR"/>
+        <ownedLiteral xmi:id="t2-usd" name="USD"/>
+      </packagedElement>
+      <packagedElement xmi:type="uml:Association" xmi:id="a1" memberEnd="a1-order a1-items" navigableOwnedEnd="a1-items">
+        <ownedEnd xmi:id="a1-order" type="c1" association="a1"/>
+        <ownedEnd xmi:id="a1-items" name="items" type="c2" association="a1" isOrdered="true">
+          <upperValue xmi:type="uml:LiteralUnlimitedNatural" xmi:id="a1-items-upper" value="*"/>
+        </ownedEnd>
+      </packagedElement>
+      <packagedElement xmi:type="uml:PrimitiveType" xmi:id="t1" name="double"/>
+    </packagedElement>
+  </uml:Model>
+</xmi:XMI>
+"#;
+
+    fn java(tokens: &[Token]) -> Vec<String> {
+        let ast = generate::parse(tokens).unwrap();
+        let classes = generate::resolve(ast, false).unwrap();
+        classes.iter().map(|c| Java.class(c)).collect()
+    }
+
+    #[test]
+    fn xmi_is_read_like_plantuml() {
+        let tokens = tokenize(SHOP, "shop.xmi").unwrap();
+        assert!(matches!(&tokens[0], Token::Startuml(Some(name)) if name == "Shop"));
+        assert!(tokens
+            .iter()
+            .any(|t| matches!(t, Token::Line(file, 6) if file == "shop.xmi")));
+
+        let plantuml = "@startuml
+class Order {
+ - id : Integer
+ + tags : Set<String>
+ + total() : double
+ + items : List<Item>
+}
+class Item
+abstract class Product {
+ # {abstract} price(currency : Currency) : double
+}
+interface Priced
+enum Currency {
+ EUR
+ USD
+}
+Priced <|.. Order
+Product <|-- Item
+note right of Order::total : Sum & taxes
+@enduml";
+        let expected = tokenizer::get_identifiers_from_str(plantuml.to_string()).unwrap();
+        let mut got = java(&tokens);
+        let mut want = java(&expected);
+        got.sort();
+        want.sort();
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn written_models_are_read_back() {
+        let input = "@startuml
+abstract class Shape {
+ # id : long
+ + {abstract} area() : double
+}
+class Circle {
+ - center : Point
+ + points : List<Point>
+ + {static} unit : Circle
+ + area() : double
+ + scale(by : double, around : Point) : Circle
+}
+interface Named
+enum Color {
+ RED
+ GREEN
+}
+class Point
+class Outer
+class Inner
+Shape <|-- Circle
+Named <|.. Circle
+Outer +-- Inner
+note top of Circle : A \"circle\" < 2
+@enduml";
+        let tokens = tokenizer::get_identifiers_from_str(input.to_string()).unwrap();
+        let classes = generate::resolve(generate::parse(&tokens).unwrap(), false).unwrap();
+        let written = write(&classes, Some("shop"));
+        assert!(written.contains(
+            r#"      <packagedElement xmi:type="uml:Class" xmi:id="Circle" name="Circle">
+        <ownedComment xmi:id="Circle-comment" annotatedElement="Circle"><body>A &quot;circle&quot; &lt; 2</body></ownedComment>
+        <generalization xmi:id="Circle-generalization" general="Shape"/>
+        <interfaceRealization xmi:id="Circle-realization" client="Circle" supplier="Named" contract="Named"/>
+        <ownedAttribute xmi:id="Circle-center" name="center" visibility="private" type="Point" association="Circle-center-association"/>
+"#
+        ));
+        assert!(written
+            .contains(r#"<nestedClassifier xmi:type="uml:Class" xmi:id="Inner" name="Inner"/>"#));
+        assert!(written.contains(
+            r#"<packagedElement xmi:type="uml:PrimitiveType" xmi:id="type-double" name="double"/>"#
+        ));
+
+        let again = tokenize(&written, "-").unwrap();
+        assert_eq!(java(&tokens), java(&again));
+
+        assert!(matches!(
+            tokenize("<xmi:XMI>\n<uml:Model>\n</xmi:XMI>", "-"),
+            Err(XmiError::Xml(3, _))
+        ));
+    }
+
+    #[test]
+    fn records_realize_interfaces_outside_of_the_data_type() {
+        let input = "@startuml
+record Point {
+ x : int
+ y : int
+}
+interface Named
+Named <|.. Point
+@enduml";
+        let tokens = tokenizer::get_identifiers_from_str(input.to_string()).unwrap();
+        let classes = generate::resolve(generate::parse(&tokens).unwrap(), false).unwrap();
+        let written = write(&classes, None);
+        assert!(!written.contains("interfaceRealization"));
+        assert!(written.contains(
+            r#"    <packagedElement xmi:type="uml:Realization" xmi:id="Point-realization" client="Point" supplier="Named"/>
+"#
+        ));
+
+        let again = tokenize(&written, "-").unwrap();
+        assert_eq!(java(&tokens), java(&again));
+        assert!(java(&again)[0].starts_with("public record Point(int x, int y) implements Named {"));
+    }
+
+    #[test]
+    fn void_operations_and_multiplicities_are_read_back() {
+        let input = "@startuml
+class Repository {
+ - tags : String[*]
+ - owners : int[1..*]
+ + fetch(id : long) : void
+}
+@enduml";
+        let tokens = tokenizer::get_identifiers_from_str(input.to_string()).unwrap();
+        let classes = generate::resolve(generate::parse(&tokens).unwrap(), false).unwrap();
+        let written = write(&classes, None);
+        assert!(!written.contains("[*]"));
+        assert!(written.contains(
+            r#"<ownedAttribute xmi:id="Repository-owners" name="owners" visibility="private" type="type-int">
+        <lowerValue xmi:type="uml:LiteralInteger" xmi:id="Repository-owners-lower" value="1"/>
+"#
+        ));
+        assert!(written.contains(
+            r#"<ownedOperation xmi:id="Repository-fetch" name="fetch" visibility="public">"#
+        ));
+
+        let again = tokenize(&written, "-").unwrap();
+        let options = generate::Options::default();
+        let classes = generate::build_classes(generate::parse(&again).unwrap(), &options).unwrap();
+        let java = Java.file(&classes[0], &options).unwrap();
+        assert!(java.contains("    private List<String> tags;\n"));
+        assert!(java.contains("    private List<Integer> owners;\n"));
+        assert!(java.contains("    public void fetch(long id) {\n"));
+    }
+}
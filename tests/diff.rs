@@ -0,0 +1,55 @@
+// Copyright (c) 2023, Oskar Ohlenmacher
+// All rights reserved
+//
+// This source code is licensed under the BSD-style license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![cfg(feature = "cli")]
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn diff(old: &Path, new: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_plantuml2java"))
+        .arg("diff")
+        .args([old, new])
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn breaking_changes_set_the_exit_code() {
+    let tmp = tempfile::tempdir().unwrap();
+    let old = tmp.path().join("old.puml");
+    let added = tmp.path().join("added.puml");
+    let removed = tmp.path().join("removed.puml");
+    fs::write(
+        &old,
+        "@startuml\nclass Held {\n + name : String\n}\n@enduml\n",
+    )
+    .unwrap();
+    fs::write(
+        &added,
+        "@startuml\nclass Held {\n + name : String\n + alter : int\n}\n@enduml\n",
+    )
+    .unwrap();
+    fs::write(&removed, "@startuml\nclass Held\n@enduml\n").unwrap();
+
+    let unchanged = diff(&old, &old);
+    assert_eq!(unchanged.status.code(), Some(0));
+    assert_eq!(unchanged.stdout, b"0 changes, 0 breaking\n");
+
+    let compatible = diff(&old, &added);
+    assert_eq!(compatible.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8(compatible.stdout).unwrap(),
+        "+ Held::alter : int - added\n1 changes, 0 breaking\n"
+    );
+
+    let breaking = diff(&old, &removed);
+    assert_eq!(breaking.status.code(), Some(1));
+    assert!(String::from_utf8(breaking.stdout)
+        .unwrap()
+        .starts_with("- Held::name : String - removed [breaking]\n"));
+}
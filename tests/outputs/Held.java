@@ -1,8 +1,6 @@
 public class Held {
 
-    public  Held() {
-    
-
+    public Held() {
     }
 
 
@@ -0,0 +1,36 @@
+// Copyright (c) 2023, Oskar Ohlenmacher
+// All rights reserved
+//
+// This source code is licensed under the BSD-style license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![cfg(feature = "cli")]
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[test]
+fn diagrams_are_read_from_stdin_and_printed_to_stdout() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_plantuml2java"))
+        .args(["-i", "-", "--stdout"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"@startuml\nclass Held {\n - name : String\n}\ninterface Monster\n@enduml\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let held = stdout.find("// ==== Held.java ====\n").unwrap();
+    let monster = stdout.find("// ==== Monster.java ====\n").unwrap();
+    assert!(held < monster);
+    assert!(stdout[held..monster].contains("public class Held {\n    private String name;\n"));
+    assert!(stdout[monster..].contains("public interface Monster {"));
+}